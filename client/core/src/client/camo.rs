@@ -1,10 +1,11 @@
 use super::receive::get_accounts_receivable;
 use crate::client::CoreClient;
 use crate::frontiers::{FrontierInfo, NewFrontiers};
-use crate::rpc::{RpcManager, RpcResult, RpcSuccess};
+use crate::history::CachedHistory;
+use crate::rpc::{RpcFailures, RpcManager, RpcResult, RpcSuccess};
 use crate::wallet::{DerivedAccountInfo, WalletDB, WalletSeed};
 use futures::future;
-use log::{debug, error};
+use log::{debug, error, info, warn};
 use nanopyrs::{
     camo::{CamoAccount, Notification},
     constants::CAMO_RECIPIENT_DUST_THRESHOLD,
@@ -13,6 +14,10 @@ use nanopyrs::{
 };
 use zeroize::Zeroize;
 
+/// When checking for camo payments, notification blocks and derived accounts are downloaded
+/// in batches of this size, concurrently
+const CAMO_RECEIVABLE_BATCH_SIZE: usize = 25;
+
 #[derive(Debug, Clone, Default, Zeroize)]
 pub struct RescanData {
     /// Receivable transactions
@@ -23,6 +28,21 @@ pub struct RescanData {
     pub derived_info: Vec<DerivedAccountInfo>,
     /// Block that scanning ended on (`previous` field of the last scanned block)
     pub new_head: Option<[u8; 32]>,
+    /// Number of blocks of the notification account's history that were scanned
+    pub blocks_scanned: usize,
+    /// Newly-downloaded account history, for the history cache (`None` if the cache was used)
+    pub history: Option<CachedHistory>,
+}
+
+#[derive(Debug, Clone, Default, Zeroize)]
+pub struct ReceivableData {
+    /// Receivable transactions
+    pub receivable: Vec<Receivable>,
+    /// Info of newly-found derived accounts
+    pub derived_info: Vec<DerivedAccountInfo>,
+    /// Derived accounts whose receivable transactions could not be downloaded.
+    /// They are still included in `derived_info`, and should be checked again later.
+    pub unchecked: Vec<Account>,
 }
 
 /// Used to determine which derived accounts have value when re-scanning history for camo payments
@@ -84,16 +104,30 @@ async fn get_notification_blocks(
         })
         .map(|receivable| receivable.block_hash)
         .collect::<Vec<[u8; 32]>>();
-    download_notification_blocks(client, &hashes).await
+
+    let batches = hashes
+        .chunks(CAMO_RECEIVABLE_BATCH_SIZE)
+        .map(|batch| download_notification_blocks(client, batch));
+    let mut notification_blocks = vec![];
+    let mut rpc_failures = RpcFailures::default();
+    for batch in future::try_join_all(batches).await? {
+        let (blocks, batch_failures) = batch.into();
+        notification_blocks.extend(blocks);
+        rpc_failures.merge_with(batch_failures);
+    }
+    Ok((notification_blocks, rpc_failures).into())
 }
 
 /// Get the destination accounts of camo payments, given the notification blocks.
 ///
+/// If `trace` is set, each derivation is logged at `info` level (excluding the shared secret).
+///
 /// Returns `DerivedAccountInfo`'s for the wallet DB.
 fn get_camo_destinations_from_blocks(
     wallet_db: &WalletDB,
     seed: &WalletSeed,
     notification_blocks: Vec<Block>,
+    trace: bool,
 ) -> Vec<DerivedAccountInfo> {
     if notification_blocks.is_empty() {
         return vec![];
@@ -104,6 +138,11 @@ fn get_camo_destinations_from_blocks(
     let mut derived_account_info = vec![];
     for notification_block in notification_blocks.iter() {
         let block_hash = hex::encode_upper(notification_block.hash());
+        // epoch blocks do not send anything, so they cannot be notifications
+        if notification_block.block_type.is_epoch() {
+            debug!("Skipping epoch block {block_hash}");
+            continue;
+        }
         debug!("Scanning {block_hash}");
 
         let recipient = if let Ok(recipient) = notification_block.link_as_account() {
@@ -133,6 +172,12 @@ fn get_camo_destinations_from_blocks(
         let account = key.to_account();
 
         debug!("Derived {account} from {block_hash}");
+        if trace {
+            info!(
+                "Camo trace: notification block {block_hash} to {recipient} with payload {}, derived {account}",
+                notification_block.representative
+            );
+        }
 
         accounts_to_scan.push(key.to_account());
         derived_account_info.push(info);
@@ -146,53 +191,97 @@ fn get_camo_destinations_from_blocks(
 /// but with `count` set to `config::RPC_ACCOUNT_HISTORY_BATCH_SIZE`,
 /// and `offset` multiplied by `config::RPC_ACCOUNT_HISTORY_BATCH_SIZE`.
 ///
+/// If `use_cache` is `true`, the account history is taken from `client.history_cache` when possible.
+/// Otherwise, it is downloaded and returned so that it can be cached.
+///
 /// Note that the destination accounts are *not* scanned, only calculated.
 async fn download_historical_notifications(
     client: &CoreClient,
     account: &CamoAccount,
     head: Option<[u8; 32]>,
     offset: Option<usize>,
-) -> RpcResult<(Vec<DerivedAccountInfo>, Option<[u8; 32]>)> {
-    // TODO: maybe cache account histories to avoid re-downloading?
-    let (history, mut rpc_failures) = RpcManager()
-        .account_history(
-            &client.config,
-            &account.signer_account(),
-            client.config.RPC_ACCOUNT_HISTORY_BATCH_SIZE,
+    use_cache: bool,
+    trace: bool,
+) -> RpcResult<(
+    Vec<DerivedAccountInfo>,
+    Option<[u8; 32]>,
+    usize,
+    Option<CachedHistory>,
+)> {
+    let notification_account = account.signer_account();
+    let frontier = client
+        .frontiers_db
+        .account_frontier(&notification_account)
+        .map(|frontier| frontier.block.hash());
+    let cached = client
+        .history_cache
+        .get(&notification_account, head, offset, frontier)
+        .filter(|_| use_cache);
+
+    let (history, new_history, mut rpc_failures) = if let Some(cached) = cached {
+        debug!("Using cached history for {notification_account}");
+        (cached.blocks.clone(), None, RpcFailures::default())
+    } else {
+        let (history, rpc_failures): (Vec<Block>, _) = RpcManager()
+            .account_history(
+                &client.config,
+                &notification_account,
+                client.config.RPC_ACCOUNT_HISTORY_BATCH_SIZE,
+                head,
+                offset.map(|offset| offset * client.config.RPC_ACCOUNT_HISTORY_BATCH_SIZE),
+            )
+            .await?
+            .into();
+        let new_history = CachedHistory {
+            account: notification_account.clone(),
             head,
-            offset.map(|offset| offset * client.config.RPC_ACCOUNT_HISTORY_BATCH_SIZE),
-        )
-        .await?
-        .into();
+            offset,
+            frontier,
+            blocks: history.clone(),
+        };
+        (history, Some(new_history), rpc_failures)
+    };
     let new_head = history.last().map(|last| last.previous);
+    let blocks_scanned = history.len();
     debug!(
         "Found {} blocks to scan for {}",
         history.len(),
         account.signer_account()
     );
 
-    let notification_hashes: Vec<[u8; 32]> = history.iter().map(|block| block.link).collect();
+    // the `link` field of epoch blocks does not refer to a block
+    let notification_hashes: Vec<[u8; 32]> = history
+        .iter()
+        .filter(|block| !block.block_type.is_epoch())
+        .map(|block| block.link)
+        .collect();
     let (blocks, blocks_failures) = download_notification_blocks(client, &notification_hashes)
         .await?
         .into();
     rpc_failures.merge_with(blocks_failures);
 
     let destinations_info =
-        get_camo_destinations_from_blocks(&client.wallet_db, &client.seed, blocks);
+        get_camo_destinations_from_blocks(&client.wallet_db, &client.seed, blocks, trace);
 
-    Ok(((destinations_info, new_head), rpc_failures).into())
+    Ok((
+        (destinations_info, new_head, blocks_scanned, new_history),
+        rpc_failures,
+    )
+        .into())
 }
 
 /// Get the receivable camo payments, given the normal receivable payments.
 /// Internally, the notification blocks are downloaded and passed to `get_camo_destinations_from_blocks()`.
 ///
-/// Returns receivable payments, as well as `DerivedAccountInfo`'s for the wallet DB.
+/// Notification blocks and the receivable transactions of derived accounts are downloaded in concurrent batches.
+/// If the receivable transactions of a batch of derived accounts could not be downloaded,
+/// those accounts are returned in `ReceivableData::unchecked` instead of returning `Err`.
 ///
 /// Note that the number of receivable payments per account that can be returned at one time is limited by `ACCOUNTS_RECEIVABLE_BATCH_SIZE`.
 pub async fn get_camo_receivable(
     client: &CoreClient,
     initial_receivable: &[Receivable],
-) -> RpcResult<(Vec<Receivable>, Vec<DerivedAccountInfo>)> {
+) -> RpcResult<ReceivableData> {
     if initial_receivable.is_empty() {
         return Ok(RpcSuccess::default());
     }
@@ -202,8 +291,12 @@ pub async fn get_camo_receivable(
             .await?
             .into();
 
-    let destinations_info: Vec<DerivedAccountInfo> =
-        get_camo_destinations_from_blocks(&client.wallet_db, &client.seed, notification_blocks);
+    let destinations_info: Vec<DerivedAccountInfo> = get_camo_destinations_from_blocks(
+        &client.wallet_db,
+        &client.seed,
+        notification_blocks,
+        false,
+    );
     let destination_accounts: Vec<Account> = destinations_info
         .iter()
         .map(|info| &info.account)
@@ -211,33 +304,55 @@ pub async fn get_camo_receivable(
         .collect();
 
     // get receivable transactions for derived accounts
-    let (camo_receivable, rpc_failures_2) = get_accounts_receivable(client, &destination_accounts)
-        .await?
-        .into();
-    rpc_failures.merge_with(rpc_failures_2);
-    Ok(((camo_receivable, destinations_info), rpc_failures).into())
+    let batches: Vec<&[Account]> = destination_accounts
+        .chunks(CAMO_RECEIVABLE_BATCH_SIZE)
+        .collect();
+    let results = future::join_all(
+        batches
+            .iter()
+            .map(|batch| get_accounts_receivable(client, batch)),
+    )
+    .await;
+    let mut camo_receivable = vec![];
+    let mut unchecked = vec![];
+    for (batch, result) in batches.into_iter().zip(results) {
+        match result {
+            Ok(success) => {
+                let (batch_receivable, rpc_failures_2) = success.into();
+                camo_receivable.extend(batch_receivable);
+                rpc_failures.merge_with(rpc_failures_2);
+            }
+            Err(err) => {
+                warn!(
+                    "Could not check {} derived accounts for receivable transactions: {err}",
+                    batch.len()
+                );
+                unchecked.extend_from_slice(batch);
+            }
+        }
+    }
+
+    let data = ReceivableData {
+        receivable: camo_receivable,
+        derived_info: destinations_info,
+        unchecked,
+    };
+    Ok((data, rpc_failures).into())
 }
 
-/// Scan part of the notification account's history for camo payments.
-///
-/// Mostly aligns with the `account_history` API,
-/// but with `count` set to `config::RPC_ACCOUNT_HISTORY_BATCH_SIZE`,
-/// and `offset` multiplied by `config::RPC_ACCOUNT_HISTORY_BATCH_SIZE`.
+/// Download the frontiers and receivable transactions of derived accounts,
+/// returning them as `RescanData` (with `new_head`, `blocks_scanned` and `history` left empty).
 ///
 /// `filter` determines whether or not to filter accounts with no value (0 balance or pending transactions).
-///
-/// Note that the destination accounts are *not* scanned, only calculated.
-pub async fn rescan_notifications_partial(
+pub async fn check_derived_accounts(
     client: &CoreClient,
-    account: &CamoAccount,
-    head: Option<[u8; 32]>,
-    offset: Option<usize>,
+    mut info: Vec<DerivedAccountInfo>,
     filter: bool,
 ) -> RpcResult<RescanData> {
-    let ((mut info, new_head), mut rpc_failures) =
-        download_historical_notifications(client, account, head, offset)
-            .await?
-            .into();
+    if info.is_empty() {
+        return Ok((RescanData::default(), RpcFailures::default()).into());
+    }
+
     let derived_accounts: Vec<Account> = info.iter().map(|info| &info.account).cloned().collect();
     let (frontiers, receivable) = future::try_join(
         client.download_frontiers(&derived_accounts),
@@ -245,8 +360,7 @@ pub async fn rescan_notifications_partial(
     )
     .await?;
 
-    let (frontiers, rpc_failures_1) = frontiers.into();
-    rpc_failures.merge_with(rpc_failures_1);
+    let (frontiers, mut rpc_failures) = frontiers.into();
     let (receivable, rpc_failures_2) = receivable.into();
     rpc_failures.merge_with(rpc_failures_2);
 
@@ -258,8 +372,157 @@ pub async fn rescan_notifications_partial(
     let rescan = RescanData {
         receivable,
         new_frontiers: frontiers,
+        derived_info: info,
+        ..RescanData::default()
+    };
+    Ok((rescan, rpc_failures).into())
+}
+
+/// Scan part of the notification account's history for camo payments,
+/// *without* checking the derived accounts that were found.
+///
+/// The returned `RescanData` only has `derived_info`, `new_head`, `blocks_scanned` and `history` set:
+/// the derived accounts can then be checked in batches with `check_derived_accounts()`,
+/// bounding memory use when very many camo payments were found.
+///
+/// The arguments are as in `rescan_notifications_partial()`.
+pub async fn scan_notifications_partial(
+    client: &CoreClient,
+    account: &CamoAccount,
+    head: Option<[u8; 32]>,
+    offset: Option<usize>,
+    use_cache: bool,
+    trace: bool,
+) -> RpcResult<RescanData> {
+    let ((info, new_head, blocks_scanned, history), rpc_failures) =
+        download_historical_notifications(client, account, head, offset, use_cache, trace)
+            .await?
+            .into();
+    let rescan = RescanData {
         derived_info: info,
         new_head,
+        blocks_scanned,
+        history,
+        ..RescanData::default()
     };
     Ok((rescan, rpc_failures).into())
 }
+
+/// Scan part of the notification account's history for camo payments.
+///
+/// Mostly aligns with the `account_history` API,
+/// but with `count` set to `config::RPC_ACCOUNT_HISTORY_BATCH_SIZE`,
+/// and `offset` multiplied by `config::RPC_ACCOUNT_HISTORY_BATCH_SIZE`.
+///
+/// `filter` determines whether or not to filter accounts with no value (0 balance or pending transactions).
+/// `use_cache` determines whether or not to use `client.history_cache` instead of re-downloading the account history.
+/// `trace` determines whether or not to log each derivation step (excluding secrets) at `info` level.
+///
+/// Note that the destination accounts are *not* scanned, only calculated.
+/// Use `download_derived_histories()` to download their histories.
+pub async fn rescan_notifications_partial(
+    client: &CoreClient,
+    account: &CamoAccount,
+    head: Option<[u8; 32]>,
+    offset: Option<usize>,
+    filter: bool,
+    use_cache: bool,
+    trace: bool,
+) -> RpcResult<RescanData> {
+    let (scan, mut rpc_failures) =
+        scan_notifications_partial(client, account, head, offset, use_cache, trace)
+            .await?
+            .into();
+    let (checked, rpc_failures_1) = check_derived_accounts(client, scan.derived_info, filter)
+        .await?
+        .into();
+    rpc_failures.merge_with(rpc_failures_1);
+
+    let rescan = RescanData {
+        receivable: checked.receivable,
+        new_frontiers: checked.new_frontiers,
+        derived_info: checked.derived_info,
+        new_head: scan.new_head,
+        blocks_scanned: scan.blocks_scanned,
+        history: scan.history,
+    };
+    Ok((rescan, rpc_failures).into())
+}
+
+/// Download the latest `count` blocks of each of the given (destination) accounts, most recent first.
+///
+/// Unopened accounts have an empty history.
+pub async fn download_derived_histories(
+    client: &CoreClient,
+    accounts: &[Account],
+    count: usize,
+) -> RpcResult<Vec<Vec<Block>>> {
+    let requests = accounts
+        .iter()
+        .map(|account| RpcManager().account_history(&client.config, account, count, None, None));
+    let mut histories = vec![];
+    let mut rpc_failures = RpcFailures::default();
+    for history in future::try_join_all(requests).await? {
+        let (history, history_failures) = history.into();
+        histories.push(history);
+        rpc_failures.merge_with(history_failures);
+    }
+    Ok((histories, rpc_failures).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CoreClientConfig;
+    use nanopyrs::{
+        camo::{CamoVersions, NotificationV1},
+        BlockType, Key, Signature,
+    };
+
+    fn fake_seed() -> WalletSeed {
+        WalletSeed::from([7; 32])
+    }
+
+    fn fake_db() -> (WalletDB, CamoAccount) {
+        let mut db = WalletDB::default();
+        let (camo_key, camo_info) = fake_seed()
+            .get_camo_key(3, CamoVersions::decode_from_bits(0x01))
+            .unwrap();
+        db.camo_account_db
+            .insert(&CoreClientConfig::test_default(), camo_info)
+            .unwrap();
+        (db, camo_key.to_camo_account())
+    }
+
+    fn fake_notification_block(camo_account: &CamoAccount, block_type: BlockType) -> Block {
+        let sender = Key::from_seed(&[8; 32].into(), 0);
+        let (_, notification) = camo_account.sender_ecdh(&sender, [29; 32]);
+        let Notification::V1(NotificationV1 {
+            recipient,
+            representative_payload,
+        }) = &notification;
+        Block {
+            block_type,
+            account: sender.to_account(),
+            previous: [1; 32],
+            representative: representative_payload.clone(),
+            balance: 0,
+            link: recipient.compressed.to_bytes(),
+            signature: Signature::default(),
+            work: [0; 8],
+        }
+    }
+
+    #[test]
+    fn skip_epoch_blocks() {
+        let (db, camo_account) = fake_db();
+
+        let send = fake_notification_block(&camo_account, BlockType::Send);
+        let derived = get_camo_destinations_from_blocks(&db, &fake_seed(), vec![send], false);
+        assert!(derived.len() == 1);
+
+        let epoch = fake_notification_block(&camo_account, BlockType::Epoch);
+        let derived = get_camo_destinations_from_blocks(&db, &fake_seed(), vec![epoch], false);
+        assert!(derived.is_empty());
+    }
+}
@@ -0,0 +1,81 @@
+use super::{choose_representatives, CoreClient};
+use crate::error::CoreClientError;
+use crate::frontiers::{FrontierInfo, NewFrontiers};
+use crate::rpc::{ClientRpc, RpcResult, WorkManager};
+use nanopyrs::{Account, Block, BlockType, Signature};
+
+/// Create a signed `change` block, setting the account's representative to `representative`.
+///
+/// Cached proof-of-work will be used, if there is any.
+/// Otherwise, the `work` field is left blank.
+fn create_change_block(
+    client: &CoreClient,
+    frontier: &FrontierInfo,
+    representative: Account,
+) -> Result<Block, CoreClientError> {
+    // a change block cannot be the first block of an account
+    if frontier.is_unopened() {
+        return Err(CoreClientError::ChangeUnopenedAccount);
+    }
+
+    let representative = choose_representatives(
+        client,
+        frontier.block.representative.clone(),
+        Some(representative),
+    );
+
+    let block = Block {
+        block_type: BlockType::Change,
+        account: frontier.block.account.clone(),
+        previous: frontier.block.hash(),
+        representative,
+        balance: frontier.block.balance,
+        link: [0; 32],
+        signature: Signature::default(),
+        work: frontier.cached_work().unwrap_or([0; 8]),
+    };
+    client.sign_block(block)
+}
+
+/// Change the representative of an account.
+/// **Does** cache work for the next block, if enabled.
+pub async fn change(
+    client: &CoreClient,
+    work_client: &mut WorkManager,
+    account: &Account,
+    representative: Account,
+) -> RpcResult<NewFrontiers> {
+    let frontier = client
+        .frontiers_db
+        .account_frontier(account)
+        .ok_or(CoreClientError::AccountNotFound)?;
+    let change_block = create_change_block(client, frontier, representative)?;
+    let (info, rpc_failures) = ClientRpc()
+        .auto_publish_unsynced(&client.config, work_client, frontier, change_block, None)
+        .await?
+        .into();
+    Ok((vec![info].into(), rpc_failures).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CoreClientConfig;
+    use crate::wallet::WalletSeed;
+
+    #[test]
+    fn change_unopened_account() {
+        let client = CoreClient::new(WalletSeed::from([7; 32]), CoreClientConfig::test_default());
+        let account = client.seed.get_key(0).0.to_account();
+        let representative = client.seed.get_key(1).0.to_account();
+        let result = create_change_block(
+            &client,
+            &FrontierInfo::new_unopened(account),
+            representative,
+        );
+        assert!(matches!(
+            result,
+            Err(CoreClientError::ChangeUnopenedAccount)
+        ));
+    }
+}
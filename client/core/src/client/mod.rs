@@ -1,43 +1,104 @@
 mod camo;
+mod change;
 mod receive;
 mod send;
 
-use super::config::CoreClientConfig;
+use super::config::{CoreClientConfig, RepresentativeStrategy};
 use super::error::CoreClientError;
 use super::frontiers::{FrontierInfo, FrontiersDB, NewFrontiers};
-use super::rpc::{ClientRpc, RpcFailures, RpcResult, RpcSuccess, WorkManager};
-use super::wallet::{DerivedAccountInfo, WalletDB, WalletSeed};
-use camo::{get_camo_receivable, rescan_notifications_partial};
-use log::{error, trace, warn};
+use super::history::HistoryCache;
+use super::rpc::{ClientRpc, RpcFailures, RpcManager, RpcResult, RpcSuccess, WorkManager};
+use super::wallet::{DerivedAccountInfo, SeedSigner, Signer, WalletDB, WalletSeed};
+use camo::{
+    check_derived_accounts, download_derived_histories, get_camo_receivable,
+    rescan_notifications_partial, scan_notifications_partial,
+};
+use change::change;
+use futures::future;
+use log::{debug, error, trace, warn};
 use nanopyrs::{
     camo::{CamoAccount, Notification},
     rpc::Receivable,
-    Account,
+    Account, Block,
 };
 use rand::seq::SliceRandom;
 use receive::{get_accounts_receivable, receive, receive_block, ReceiveResult};
 use send::{send, send_camo, sender_ecdh};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use zeroize::Zeroize;
 
-pub use camo::RescanData;
+pub use camo::{ReceivableData, RescanData};
 pub use send::{CamoPayment, Payment};
 
+/// Choose the representative of a new block: `option` if given, otherwise as per `REPRESENTATIVE_STRATEGY`.
+///
+/// Unless the strategy is `Fixed`, the current representative is kept if it is in `REPRESENTATIVES`.
 pub(crate) fn choose_representatives(
-    config: &CoreClientConfig,
+    client: &CoreClient,
     current: Account,
     option: Option<Account>,
 ) -> Account {
     if let Some(rep) = option {
         return rep;
     }
+    let config = &client.config;
+    if let RepresentativeStrategy::Fixed(rep) = &config.REPRESENTATIVE_STRATEGY {
+        return rep.as_ref().clone();
+    }
     if config.REPRESENTATIVES.contains(&current) {
         return current;
     }
-    config
-        .REPRESENTATIVES
-        .choose(&mut rand::thread_rng())
-        .expect("no representatives to choose from")
-        .clone()
+    match config.REPRESENTATIVE_STRATEGY {
+        RepresentativeStrategy::RoundRobin => {
+            assert!(
+                !config.REPRESENTATIVES.is_empty(),
+                "no representatives to choose from"
+            );
+            let next = client.round_robin.fetch_add(1, Ordering::Relaxed);
+            config.REPRESENTATIVES[next % config.REPRESENTATIVES.len()].clone()
+        }
+        _ => config
+            .REPRESENTATIVES
+            .choose(&mut rand::thread_rng())
+            .expect("no representatives to choose from")
+            .clone(),
+    }
+}
+
+/// What `republish_block()` did with a block
+enum RepublishOutcome {
+    Confirmed,
+    Republished,
+    Skipped,
+}
+
+/// Republish the block unless `block_info` reports it as confirmed
+async fn republish_block(
+    config: &CoreClientConfig,
+    block: &Block,
+) -> Result<(RepublishOutcome, RpcFailures), CoreClientError> {
+    let hash = block.hash();
+    let (info, mut failures) = RpcManager().block_info(config, hash).await?.into();
+    if info.is_some_and(|info| info.confirmed) {
+        return Ok((RepublishOutcome::Confirmed, failures));
+    }
+
+    match RpcManager().process(config, block).await {
+        Ok(success) => {
+            failures.merge_with(success.failures);
+            debug!("Republished {}", hex::encode_upper(hash));
+            Ok((RepublishOutcome::Republished, failures))
+        }
+        // already known to the network
+        Err(CoreClientError::BlockRejected(rejection)) if rejection == "Old block" => {
+            Ok((RepublishOutcome::Skipped, failures))
+        }
+        Err(err) => {
+            warn!("Failed to republish {}: {err}", hex::encode_upper(hash));
+            Ok((RepublishOutcome::Skipped, failures))
+        }
+    }
 }
 
 #[derive(Debug, Clone, Zeroize)]
@@ -47,25 +108,94 @@ pub struct CoreClient {
 
     pub wallet_db: WalletDB,
     pub frontiers_db: FrontiersDB,
+    /// Account histories downloaded while rescanning
+    pub history_cache: HistoryCache,
+
+    /// Signs blocks for this wallet (`SeedSigner` by default)
+    #[zeroize(skip)]
+    pub signer: Box<dyn Signer>,
+    /// Index of the next representative to use with `RepresentativeStrategy::RoundRobin`
+    #[zeroize(skip)]
+    round_robin: Arc<AtomicUsize>,
 }
 impl CoreClient {
     pub fn new(seed: WalletSeed, config: CoreClientConfig) -> CoreClient {
         CoreClient {
+            signer: Box::new(SeedSigner::new(seed.clone())),
             seed,
             config,
             wallet_db: WalletDB::default(),
             frontiers_db: FrontiersDB::default(),
+            history_cache: HistoryCache::default(),
+            round_robin: Arc::default(),
+        }
+    }
+
+    /// Sign the given block with this wallet's signer, returning it with a signature attached
+    pub fn sign_block(&self, block: Block) -> Result<Block, CoreClientError> {
+        self.signer.sign_block(&self.wallet_db, block)
+    }
+
+    /// Download the network's current work difficulty, and use it for `WORK_DIFFICULTY` and `WORK_DIFFICULTY_RECEIVE`.
+    /// Returns `true` if either of them changed.
+    pub async fn update_work_difficulty(&mut self) -> Result<bool, CoreClientError> {
+        let difficulty = RpcManager().active_difficulty(&self.config).await?;
+        let difficulty = self.handle_rpc_success(difficulty);
+        let changed = self.config.WORK_DIFFICULTY != difficulty.send
+            || self.config.WORK_DIFFICULTY_RECEIVE != difficulty.receive;
+        if changed {
+            debug!(
+                "Work difficulty changed to {:016x} (receive: {:016x})",
+                difficulty.send, difficulty.receive
+            );
+        }
+        self.config.WORK_DIFFICULTY = difficulty.send;
+        self.config.WORK_DIFFICULTY_RECEIVE = difficulty.receive;
+        Ok(changed)
+    }
+
+    /// Whether or not the account's balance may be counted and spent, as per `CONFIRMATION_REQUIRED`
+    pub fn is_spendable(&self, account: &Account) -> bool {
+        !self.config.CONFIRMATION_REQUIRED || self.frontiers_db.is_confirmed(account)
+    }
+
+    /// Returns `Err` if the account's frontier is not confirmed, as required by `CONFIRMATION_REQUIRED`
+    pub fn check_spendable(&self, account: &Account) -> Result<(), CoreClientError> {
+        match self.is_spendable(account) {
+            true => Ok(()),
+            false => Err(CoreClientError::UnconfirmedFrontier),
         }
     }
 
+    /// The amount to send when emptying an account: its balance minus `RESERVE_RAW`.
+    ///
+    /// Returns `None` if nothing can be sent: the account is unopened, its private key is not known,
+    /// its frontier is unconfirmed (if `CONFIRMATION_REQUIRED`), or its balance does not exceed `RESERVE_RAW`.
+    pub fn sweepable_balance(&self, account: &Account) -> Option<u128> {
+        let frontier = self.frontiers_db.account_frontier(account)?;
+        if frontier.is_unopened()
+            || self.wallet_db.find_key(&self.seed, account).is_none()
+            || !self.is_spendable(account)
+        {
+            return None;
+        }
+        let amount = frontier
+            .block
+            .balance
+            .saturating_sub(self.config.RESERVE_RAW);
+        (amount > 0).then_some(amount)
+    }
+
     /// Returns the frontiers of all `nano_` accounts in the wallet with `balance >= amount`,
-    /// excluding the given accounts, sorted by `balance` low to high
+    /// excluding the given accounts, watch-only accounts and (if `CONFIRMATION_REQUIRED`) unconfirmed frontiers,
+    /// sorted by `balance` low to high
     pub fn accounts_with_balance(&self, amount: u128, exclude: &[Account]) -> Vec<&FrontierInfo> {
         let mut frontiers = self
             .wallet_db
-            .all_nano_accounts()
+            .spendable_nano_accounts()
             .iter()
             .filter(|account| !exclude.contains(account))
+            .filter(|account| self.is_spendable(account))
             .filter_map(|account| self.frontiers_db.account_frontier(account))
             .filter(|block| block.block.balance >= amount)
             .collect::<Vec<&FrontierInfo>>();
@@ -73,15 +203,34 @@ impl CoreClient {
         frontiers
     }
 
-    /// Get the wallet's balance, according to this database
+    /// The accounts counted by `wallet_balance()`
+    fn balance_accounts(&self) -> Vec<Account> {
+        self.wallet_db
+            .spendable_nano_accounts()
+            .into_iter()
+            .filter(|account| self.is_spendable(account))
+            .collect()
+    }
+
+    /// Get the wallet's balance, according to this database.
+    /// Watch-only accounts, and (if `CONFIRMATION_REQUIRED`) unconfirmed frontiers, are not included.
     pub fn wallet_balance(&self) -> u128 {
         self.frontiers_db
-            .accounts_balances(&self.wallet_db.all_nano_accounts())
+            .accounts_balances(&self.balance_accounts())
             .iter()
             .map(|balance| balance.unwrap_or(0))
             .sum()
     }
 
+    /// The amount sent by unconfirmed blocks of the accounts counted by `wallet_balance()`,
+    /// which it already excludes (see `FrontiersDB::pending_send()`)
+    pub fn wallet_pending_send(&self) -> u128 {
+        self.balance_accounts()
+            .iter()
+            .map(|account| self.frontiers_db.pending_send(account))
+            .sum()
+    }
+
     /// Find the derived accounts in the DB, given the master camo account
     pub fn get_derived_accounts_from_master(&self, master: &CamoAccount) -> Vec<Account> {
         self.wallet_db
@@ -100,6 +249,96 @@ impl CoreClient {
             .await
     }
 
+    /// If `CONFIRMATION_REQUIRED`, download the confirmation status of the wallet's unconfirmed frontiers,
+    /// returning the hashes of those which are now confirmed
+    pub async fn download_confirmed(&self) -> RpcResult<Vec<[u8; 32]>> {
+        let hashes = match self.config.CONFIRMATION_REQUIRED {
+            true => self
+                .frontiers_db
+                .unconfirmed_hashes(&self.wallet_db.spendable_nano_accounts()),
+            false => vec![],
+        };
+        ClientRpc().download_confirmed(&self.config, &hashes).await
+    }
+
+    /// If `CONFIRMATION_REQUIRED`, download and record the confirmation status of the wallet's unconfirmed frontiers.
+    ///
+    /// Confirmation status is not saved with the wallet, so this should be called after loading it.
+    pub async fn update_confirmed(&mut self) -> Result<(), CoreClientError> {
+        let confirmed = self.download_confirmed().await?;
+        let confirmed = self.handle_rpc_success(confirmed);
+        self.frontiers_db.set_confirmed(&confirmed);
+        Ok(())
+    }
+
+    /// Wait for the given blocks to be confirmed, for at most `CONFIRMATION_TIMEOUT_SECONDS`.
+    /// Returns `true` if all of them were confirmed in time.
+    ///
+    /// Confirmed frontiers are recorded, as with `update_confirmed()`.
+    pub async fn wait_for_confirmation(
+        &mut self,
+        hashes: &[[u8; 32]],
+    ) -> Result<bool, CoreClientError> {
+        let confirmed = ClientRpc()
+            .wait_for_confirmation(&self.config, hashes)
+            .await?;
+        let confirmed = self.handle_rpc_success(confirmed);
+        self.frontiers_db.set_confirmed(&confirmed);
+        Ok(hashes.iter().all(|hash| confirmed.contains(hash)))
+    }
+
+    /// Re-submit the frontier block of each of the wallet's accounts which `block_info` does not report as confirmed,
+    /// in case it was dropped by the network (e.g. if the wallet crashed before it was confirmed).
+    /// The block's own work is reused. Returns the hashes of the blocks which were republished.
+    ///
+    /// The blocks are checked concurrently, and `progress` is called with the number checked so far and the total.
+    /// Republishing a block which the network already has is harmless, so this may be called at any time.
+    /// Confirmed frontiers are recorded, as with `update_confirmed()`.
+    pub async fn republish_pending(
+        &mut self,
+        progress: impl Fn(usize, usize),
+    ) -> Result<Vec<[u8; 32]>, CoreClientError> {
+        let blocks: Vec<Block> = self
+            .wallet_db
+            .spendable_nano_accounts()
+            .iter()
+            .filter(|account| !self.frontiers_db.is_confirmed(account))
+            .filter_map(|account| self.frontiers_db.account_frontier(account))
+            .map(|frontier| frontier.block.clone())
+            .collect();
+
+        let checked = AtomicUsize::new(0);
+        let results = future::join_all(blocks.iter().map(|block| async {
+            let result = republish_block(&self.config, block).await;
+            progress(checked.fetch_add(1, Ordering::Relaxed) + 1, blocks.len());
+            result
+        }))
+        .await;
+
+        let mut confirmed = vec![];
+        let mut republished = vec![];
+        let mut error = None;
+        for (block, result) in blocks.iter().zip(results) {
+            match result {
+                Ok((RepublishOutcome::Confirmed, failures)) => {
+                    self.handle_rpc_failures(failures);
+                    confirmed.push(block.hash());
+                }
+                Ok((RepublishOutcome::Republished, failures)) => {
+                    self.handle_rpc_failures(failures);
+                    republished.push(block.hash());
+                }
+                Ok((RepublishOutcome::Skipped, failures)) => self.handle_rpc_failures(failures),
+                Err(err) => error = Some(err),
+            }
+        }
+        self.frontiers_db.set_confirmed(&confirmed);
+        match error {
+            Some(err) => Err(err),
+            None => Ok(republished),
+        }
+    }
+
     /// Download the frontiers of any unknown accounts.
     pub async fn download_unknown_frontiers(&self) -> RpcResult<NewFrontiers> {
         let unknown = self
@@ -109,23 +348,20 @@ impl CoreClient {
     }
 
     /// Get all receivable payments for these accounts, including camo payments.
-    /// Returns receivable payments, as well as `DerivedAccountInfo`'s for the wallet DB.
+    /// Returns receivable payments, as well as `DerivedAccountInfo`'s for the wallet DB,
+    /// and any derived accounts which could not be checked for receivable payments.
     ///
     /// Note that the number of receivable payments per account that can be returned at one time is limited by `ACCOUNTS_RECEIVABLE_BATCH_SIZE`.
-    pub async fn download_receivable(
-        &self,
-        accounts: &[Account],
-    ) -> RpcResult<(Vec<Receivable>, Vec<DerivedAccountInfo>)> {
+    pub async fn download_receivable(&self, accounts: &[Account]) -> RpcResult<ReceivableData> {
         // get receivable for normal payments
         let (receivable, mut rpc_failures) = get_accounts_receivable(self, accounts).await?.into();
         // get receivable for camo payments
-        let ((camo_receivable, derived_account_info), rpc_failures_2) =
-            get_camo_receivable(self, &receivable).await?.into();
+        let (mut data, rpc_failures_2) = get_camo_receivable(self, &receivable).await?.into();
 
         // camo payments should be received first in order to prevent losses in the event of a crash
-        let receivable = [camo_receivable, receivable].concat();
+        data.receivable.extend(receivable);
         rpc_failures.merge_with(rpc_failures_2);
-        Ok(((receivable, derived_account_info), rpc_failures).into())
+        Ok((data, rpc_failures).into())
     }
 
     /// Scan part of the notification account's history for camo payments.
@@ -135,26 +371,72 @@ impl CoreClient {
     /// and `offset` multiplied by `config::RPC_ACCOUNT_HISTORY_BATCH_SIZE`.
     ///
     /// `filter` determines whether or not to filter accounts with no value (0 balance or pending transactions).
+    /// `use_cache` determines whether or not to use `history_cache` instead of re-downloading the account history.
+    /// `trace` determines whether or not to log each derivation step (excluding secrets) at `info` level.
     ///
     /// Note that the destination accounts are *not* scanned, only calculated.
+    /// Use `download_derived_histories()` to download their histories.
     pub async fn rescan_notifications_partial(
         &self,
         account: &CamoAccount,
         head: Option<[u8; 32]>,
         offset: Option<usize>,
         filter: bool,
+        use_cache: bool,
+        trace: bool,
     ) -> RpcResult<RescanData> {
-        rescan_notifications_partial(self, account, head, offset, filter).await
+        rescan_notifications_partial(self, account, head, offset, filter, use_cache, trace).await
+    }
+
+    /// Scan part of the notification account's history for camo payments,
+    /// *without* checking the derived accounts that were found.
+    ///
+    /// The derived accounts (`derived_info`) can then be checked in batches with `check_derived_accounts()`.
+    /// See `rescan_notifications_partial()` for the arguments.
+    pub async fn scan_notifications_partial(
+        &self,
+        account: &CamoAccount,
+        head: Option<[u8; 32]>,
+        offset: Option<usize>,
+        use_cache: bool,
+        trace: bool,
+    ) -> RpcResult<RescanData> {
+        scan_notifications_partial(self, account, head, offset, use_cache, trace).await
+    }
+
+    /// Download the frontiers and receivable transactions of derived accounts found by `scan_notifications_partial()`.
+    ///
+    /// `filter` determines whether or not to filter accounts with no value (0 balance or pending transactions).
+    pub async fn check_derived_accounts(
+        &self,
+        info: Vec<DerivedAccountInfo>,
+        filter: bool,
+    ) -> RpcResult<RescanData> {
+        check_derived_accounts(self, info, filter).await
+    }
+
+    /// Download the latest `count` blocks of each of the given (destination) accounts, most recent first.
+    ///
+    /// Unopened accounts have an empty history.
+    pub async fn download_derived_histories(
+        &self,
+        accounts: &[Account],
+        count: usize,
+    ) -> RpcResult<Vec<Vec<Block>>> {
+        download_derived_histories(self, accounts, count).await
     }
 
     /// Receive a single transaction, returning the new frontier of that account (a `receive` block).
     /// **Does** cache work for the next block, if enabled.
+    ///
+    /// `new_representative` overrides the representative chosen for the `receive` block, if given.
     pub async fn receive_block(
         &self,
         work_client: &mut WorkManager,
         receivable: &Receivable,
+        new_representative: Option<Account>,
     ) -> RpcResult<NewFrontiers> {
-        receive_block(self, work_client, receivable).await
+        receive_block(self, work_client, receivable, new_representative).await
     }
 
     /// Receive a single transaction, returning the new frontier of that account (a `receive` block).
@@ -170,12 +452,25 @@ impl CoreClient {
     }
 
     /// Send to a `nano_` account.
+    ///
+    /// `custom_difficulty` overrides the default work difficulty for the send block, if given.
     pub async fn send(
         &self,
         work_client: &mut WorkManager,
         payment: Payment,
+        custom_difficulty: Option<[u8; 8]>,
+    ) -> RpcResult<NewFrontiers> {
+        send(self, work_client, payment, custom_difficulty).await
+    }
+
+    /// Change the representative of an opened account by publishing a `change` block.
+    pub async fn change(
+        &self,
+        work_client: &mut WorkManager,
+        account: &Account,
+        representative: Account,
     ) -> RpcResult<NewFrontiers> {
-        send(self, work_client, payment).await
+        change(self, work_client, account, representative).await
     }
 
     /// Send to a `camo_` account.
@@ -283,13 +578,81 @@ impl CoreClient {
                 }
             };
             trace!("CoreClient::handle_work_results(): caching work for work hash {as_hex}");
-            let result = self
+            let cached = self
                 .frontiers_db
                 .add_work(&self.config, result.work_hash, work);
-            if let Err(err) = result {
-                error!("CoreClient::handle_work_results(): {err}: could not find frontier {as_hex} in DB")
+            match cached {
+                Ok(true) => (),
+                // request new work immediately, rather than waiting for `needs_work()` to notice
+                Ok(false) => {
+                    if work_client.reject_work(&self.config, result.work_hash) {
+                        debug!(
+                            "CoreClient::handle_work_results(): re-requesting work for {as_hex}"
+                        );
+                        work_client.request_work(&self.config, result.work_hash, None);
+                    } else {
+                        warn!("CoreClient::handle_work_results(): giving up on work for {as_hex}: too many invalid results");
+                    }
+                }
+                Err(err) => {
+                    error!("CoreClient::handle_work_results(): {err}: could not find frontier {as_hex} in DB")
+                }
             }
         }
         Ok(should_save)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nanopyrs::{BlockType, Signature};
+
+    fn opened_frontier(account: Account, balance: u128) -> FrontierInfo {
+        let block = Block {
+            block_type: BlockType::Receive,
+            account: account.clone(),
+            previous: [1; 32],
+            representative: account,
+            balance,
+            link: [2; 32],
+            signature: Signature::default(),
+            work: [0; 8],
+        };
+        FrontierInfo::new(block, None)
+    }
+
+    #[test]
+    fn sweepable_balance() {
+        let mut config = CoreClientConfig::test_default();
+        config.RESERVE_RAW = 3;
+        let mut client = CoreClient::new(WalletSeed::from([7; 32]), config.clone());
+        let (_, info) = client.seed.get_key(0);
+        let (_, empty_info) = client.seed.get_key(1);
+        let account = info.account.clone();
+        let empty = empty_info.account.clone();
+        let unknown = client.seed.get_key(2).0.to_account();
+        client.wallet_db.account_db.insert(&config, info).unwrap();
+        client
+            .wallet_db
+            .account_db
+            .insert(&config, empty_info)
+            .unwrap();
+
+        let frontiers = vec![
+            opened_frontier(account.clone(), 10),
+            opened_frontier(empty.clone(), 3),
+            opened_frontier(unknown.clone(), 10),
+        ];
+        client.frontiers_db.insert(frontiers.into()).unwrap();
+
+        // a sweep leaves `RESERVE_RAW` behind
+        assert!(client.sweepable_balance(&account) == Some(7));
+        assert!(client.sweepable_balance(&empty).is_none());
+        assert!(client.sweepable_balance(&unknown).is_none());
+
+        client.config.RESERVE_RAW = 0;
+        assert!(client.sweepable_balance(&account) == Some(10));
+        assert!(client.sweepable_balance(&empty) == Some(3));
+    }
+}
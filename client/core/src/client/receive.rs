@@ -1,15 +1,20 @@
 use super::{choose_representatives, CoreClient};
+use crate::config::{ReceiveOrder, RepresentativeStrategy};
 use crate::error::CoreClientError;
 use crate::frontiers::{FrontierInfo, NewFrontiers};
 use crate::rpc::{ClientRpc, RpcFailures, RpcManager, RpcResult, RpcSuccess, WorkManager};
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use nanopyrs::{rpc::Receivable, Account, Block, BlockType, Signature};
+use rand::prelude::{thread_rng, SliceRandom};
 use std::collections::HashMap;
 
 #[derive(Debug)]
 pub struct ReceiveFailure {
     pub err: CoreClientError,
     pub unreceived: Vec<Receivable>,
+    /// transactions which were not received because they would overflow the recipient's balance
+    /// (these are also in `unreceived`)
+    pub overflowed: Vec<Receivable>,
 }
 #[derive(Debug)]
 pub struct ReceiveResult {
@@ -19,6 +24,14 @@ pub struct ReceiveResult {
     pub failures: Result<(), ReceiveFailure>,
 }
 
+fn would_overflow(recipient_frontier: &FrontierInfo, receivable: &Receivable) -> bool {
+    recipient_frontier
+        .block
+        .balance
+        .checked_add(receivable.amount)
+        .is_none()
+}
+
 /// Create a signed `receive` block for the given pending transaction.
 ///
 /// Cached proof-of-work will be used, if there is any.
@@ -33,13 +46,8 @@ fn create_receive_block(
     let work = recipient_frontier.cached_work().unwrap_or([0; 8]);
 
     // sanity check balance
-    if recipient_frontier
-        .block
-        .balance
-        .checked_add(receivable.amount)
-        .is_none()
-    {
-        return Err(CoreClientError::FrontierBalanceOverflow);
+    if would_overflow(recipient_frontier, receivable) {
+        return Err(CoreClientError::ReceivableBalanceOverflow);
     }
 
     let previous = if recipient_frontier.is_unopened() {
@@ -49,7 +57,7 @@ fn create_receive_block(
     };
 
     let representative = choose_representatives(
-        &client.config,
+        client,
         recipient_frontier.block.representative.clone(),
         new_representative,
     );
@@ -64,7 +72,7 @@ fn create_receive_block(
         signature: Signature::default(),
         work,
     };
-    client.wallet_db.sign_block(&client.seed, block)
+    client.sign_block(block)
 }
 
 /// Get the receivable payments for the given accounts.
@@ -98,17 +106,86 @@ pub async fn receive_block(
     client: &CoreClient,
     work_client: &mut WorkManager,
     receivable: &Receivable,
+    new_representative: Option<Account>,
 ) -> RpcResult<NewFrontiers> {
     let frontier = &client
         .frontiers_db
         .account_frontier(&receivable.recipient)
         .ok_or(CoreClientError::AccountNotFound)?;
-    let receive_block = create_receive_block(client, receivable, frontier, None)?;
-    let (info, rpc_failures) = ClientRpc()
-        .auto_publish_unsynced(&client.config, work_client, frontier, receive_block.clone())
+    let (info, rpc_failures) = receive_block_unsynced(
+        client,
+        work_client,
+        receivable,
+        frontier,
+        new_representative,
+    )
+    .await?
+    .into();
+    Ok((vec![info].into(), rpc_failures).into())
+}
+
+/// Retry receiving a transaction using only nodes which support `account_history` and `block_info`,
+/// in case the source block was pruned by the nodes used previously.
+/// **Does** cache work for the next block, if enabled.
+///
+/// Returns `CoreClientError::SourceBlockUnavailable` if no node can serve the source block.
+async fn receive_block_full_history(
+    client: &CoreClient,
+    work_client: &mut WorkManager,
+    receivable: &Receivable,
+    frontier: &FrontierInfo,
+    new_representative: Option<Account>,
+) -> RpcResult<FrontierInfo> {
+    let mut full_history_config = client.config.clone();
+    full_history_config
+        .RPCS
+        .retain(|rpc| rpc.commands.account_history && rpc.commands.block_info);
+    if full_history_config.RPCS.is_empty() {
+        return Err(CoreClientError::RpcCommandFailed);
+    }
+
+    let block_hash = hex::encode_upper(receivable.block_hash);
+    info!("Retrying transaction {block_hash} using full-history nodes...");
+
+    let source = RpcManager()
+        .block_info(&full_history_config, receivable.block_hash)
+        .await;
+    let mut rpc_failures = match source {
+        Ok(RpcSuccess {
+            item: Some(_),
+            failures,
+        }) => failures,
+        _ => {
+            warn!("Source block {block_hash} could not be found on any node");
+            return Err(CoreClientError::SourceBlockUnavailable);
+        }
+    };
+
+    let mut receive_block = create_receive_block(client, receivable, frontier, new_representative)?;
+    let (work, work_failures) = ClientRpc()
+        .get_work(
+            &client.config,
+            work_client,
+            frontier,
+            &receive_block.block_type,
+            None,
+        )?
+        .into();
+    receive_block.work = work;
+    rpc_failures.merge_with(work_failures);
+
+    let next_work_hash = receive_block.hash();
+    let (info, publish_failures) = ClientRpc()
+        .publish(&full_history_config, frontier, receive_block)
         .await?
         .into();
-    Ok((vec![info].into(), rpc_failures).into())
+    rpc_failures.merge_with(publish_failures);
+
+    // Cache work for next block
+    if client.config.ENABLE_WORK_CACHE {
+        work_client.request_work(&client.config, next_work_hash, None);
+    }
+    Ok((info, rpc_failures).into())
 }
 
 /// Receive a single transaction, returning the new frontier of that account (a `receive` block).
@@ -120,22 +197,129 @@ async fn receive_block_unsynced(
     work_client: &mut WorkManager,
     receivable: &Receivable,
     frontier: &FrontierInfo,
+    new_representative: Option<Account>,
 ) -> RpcResult<FrontierInfo> {
-    let receive_block = create_receive_block(client, receivable, frontier, None)?;
-    ClientRpc()
-        .auto_publish_unsynced(&client.config, work_client, frontier, receive_block)
-        .await
+    let (new_representative, mut rpc_failures) = if new_representative.is_some() {
+        (new_representative, RpcFailures::default())
+    } else if frontier.is_unopened()
+        && client.config.CHECK_NEW_ACCOUNT_REPRESENTATIVES
+        && client.config.REPRESENTATIVE_STRATEGY == RepresentativeStrategy::Random
+    {
+        choose_weighted_representative(client).await.into()
+    } else {
+        (None, RpcFailures::default())
+    };
+
+    let receive_block =
+        create_receive_block(client, receivable, frontier, new_representative.clone())?;
+    let result = ClientRpc()
+        .auto_publish_unsynced(&client.config, work_client, frontier, receive_block, None)
+        .await;
+
+    let result = match result {
+        Err(CoreClientError::SourceBlockMissing(failures))
+            if client.config.RECEIVE_RETRY_FULL_HISTORY_NODES =>
+        {
+            rpc_failures.merge_with(failures);
+            receive_block_full_history(
+                client,
+                work_client,
+                receivable,
+                frontier,
+                new_representative,
+            )
+            .await
+        }
+        result => result,
+    };
+
+    let (info, failures) = result?.into();
+    rpc_failures.merge_with(failures);
+    Ok((info, rpc_failures).into())
+}
+
+/// Choose a representative for a new account, skipping any which have no voting weight.
+///
+/// Returns `None` (use the default choice) if no representative could be verified.
+async fn choose_weighted_representative(client: &CoreClient) -> RpcSuccess<Option<Account>> {
+    let mut rpc_failures = RpcFailures::default();
+    let mut representatives = client.config.REPRESENTATIVES.clone();
+    representatives.shuffle(&mut thread_rng());
+
+    for representative in representatives {
+        let info = match RpcManager()
+            .account_info(&client.config, &representative)
+            .await
+        {
+            Ok(success) => {
+                let (info, failures) = success.into();
+                rpc_failures.merge_with(failures);
+                info
+            }
+            Err(err) => {
+                warn!("Could not check representatives: {err}");
+                break;
+            }
+        };
+
+        match info {
+            Some(info) if info.weight > 0 => {
+                return (Some(representative), rpc_failures).into();
+            }
+            _ => warn!("Representative {representative} has no voting weight, skipping"),
+        }
+    }
+    (None, rpc_failures).into()
+}
+
+/// Remove duplicate transactions (by block hash), and sort the remainder according to `order`
+fn order_receivables(receivables: Vec<Receivable>, order: ReceiveOrder) -> Vec<Receivable> {
+    let mut deduped: Vec<Receivable> = Vec::with_capacity(receivables.len());
+    for receivable in receivables {
+        if deduped
+            .iter()
+            .any(|existing| existing.block_hash == receivable.block_hash)
+        {
+            let block_hash = hex::encode_upper(receivable.block_hash);
+            debug!("Ignoring duplicate receivable transaction {block_hash}");
+            continue;
+        }
+        deduped.push(receivable)
+    }
+
+    match order {
+        ReceiveOrder::Amount => {
+            deduped.sort_by_key(|receivable| std::cmp::Reverse(receivable.amount))
+        }
+        ReceiveOrder::Account => {
+            let mut accounts: Vec<Account> = vec![];
+            for receivable in &deduped {
+                if !accounts.contains(&receivable.recipient) {
+                    accounts.push(receivable.recipient.clone())
+                }
+            }
+            deduped.sort_by_key(|receivable| {
+                accounts
+                    .iter()
+                    .position(|account| account == &receivable.recipient)
+            })
+        }
+        ReceiveOrder::Block => (),
+    }
+    deduped
 }
 
 /// Receive a batch of transactions, returning the new frontiers of the accounts (`receive` blocks).
 /// **Does** cache work for the next block, if enabled.
 ///
-/// Transactions are processed in batches of size `config::RPC_RECEIVE_TRANSACTIONS_BATCH_SIZE`.
+/// Duplicate transactions are ignored, and the remainder are received in the order given by `config::RECEIVE_ORDER`.
 pub async fn receive(
     client: &CoreClient,
     work_client: &mut WorkManager,
     receivables: Vec<Receivable>,
 ) -> ReceiveResult {
+    let receivables = order_receivables(receivables, client.config.RECEIVE_ORDER);
+
     // Instead of relying on the database,
     // which will become out-of-sync when an account receives more than one transaction,
     // we instead create a mini-database which will be updated and eventually returned by this method.
@@ -157,19 +341,31 @@ pub async fn receive(
     let mut err: Option<CoreClientError> = None;
     // the hashes of transactions which were NOT successfully received
     let mut unreceived: Vec<Receivable> = vec![];
+    // transactions which were skipped, since they would overflow the recipient's balance
+    let mut overflowed: Vec<Receivable> = vec![];
 
     // the hashes of transactions which were successfully received
     let mut successfully_received: Vec<[u8; 32]> = vec![];
 
     for (i, receivable) in receivables.iter().enumerate() {
-        let receive_future = receive_block_unsynced(
-            client,
-            work_client,
-            receivable,
-            frontiers
-                .get(&receivable.recipient)
-                .expect("Failed to catch invalid receivable transaction"),
-        );
+        let frontier = frontiers
+            .get(&receivable.recipient)
+            .expect("Failed to catch invalid receivable transaction");
+
+        // skip this transaction, but keep receiving the others
+        if would_overflow(frontier, receivable) {
+            let block_hash = hex::encode_upper(receivable.block_hash);
+            let recipient = &receivable.recipient;
+            warn!(
+                "Skipping transaction {block_hash}: it would overflow the balance of {recipient}"
+            );
+            overflowed.push(receivable.clone());
+            err = Some(CoreClientError::ReceivableBalanceOverflow);
+            continue;
+        }
+
+        let receive_future =
+            receive_block_unsynced(client, work_client, receivable, frontier, None);
 
         info!(
             "Receiving transaction {} out of {}...",
@@ -206,7 +402,11 @@ pub async fn receive(
 
     let frontiers: Vec<FrontierInfo> = frontiers.into_values().collect();
     let unreceived: Result<(), ReceiveFailure> = match err {
-        Some(err) => Err(ReceiveFailure { err, unreceived }),
+        Some(err) => Err(ReceiveFailure {
+            err,
+            unreceived,
+            overflowed,
+        }),
         None => Ok(()),
     };
     ReceiveResult {
@@ -214,3 +414,63 @@ pub async fn receive(
         failures: unreceived,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fake_account_1() -> Account {
+        "nano_3t6k35gi95xu6tergt6p69ck76ogmitsa8mnijtpxm9fkcm736xtoncuohr3"
+            .parse()
+            .unwrap()
+    }
+    fn fake_account_2() -> Account {
+        "nano_3qb6o6i1tkzr6jwr5s7eehfxwg9x6eemitdinbpi7u8bjjwsgqfj4wzser3x"
+            .parse()
+            .unwrap()
+    }
+
+    fn fake_receivables() -> Vec<Receivable> {
+        vec![
+            Receivable {
+                recipient: fake_account_1(),
+                block_hash: [1; 32],
+                amount: 5,
+            },
+            Receivable {
+                recipient: fake_account_2(),
+                block_hash: [2; 32],
+                amount: 10,
+            },
+            Receivable {
+                recipient: fake_account_1(),
+                block_hash: [3; 32],
+                amount: 1,
+            },
+            Receivable {
+                recipient: fake_account_2(),
+                block_hash: [2; 32],
+                amount: 10,
+            },
+        ]
+    }
+
+    fn hashes(receivables: &[Receivable]) -> Vec<[u8; 32]> {
+        receivables
+            .iter()
+            .map(|receivable| receivable.block_hash)
+            .collect()
+    }
+
+    #[test]
+    fn order_receivables() {
+        let block = super::order_receivables(fake_receivables(), ReceiveOrder::Block);
+        assert!(hashes(&block) == [[1; 32], [2; 32], [3; 32]]);
+
+        let amount = super::order_receivables(fake_receivables(), ReceiveOrder::Amount);
+        assert!(hashes(&amount) == [[2; 32], [1; 32], [3; 32]]);
+
+        let account = super::order_receivables(fake_receivables(), ReceiveOrder::Account);
+        assert!(hashes(&account) == [[1; 32], [3; 32], [2; 32]]);
+    }
+}
@@ -56,6 +56,11 @@ fn create_send_block(
         return Err(CoreClientError::InvalidPayment);
     }
 
+    // a send block cannot be the first block of an account
+    if sender_frontier.is_unopened() {
+        return Err(CoreClientError::SendFromUnopenedAccount);
+    }
+
     let work = sender_frontier.cached_work().unwrap_or([0; 8]);
     let frontier = &sender_frontier.block;
 
@@ -64,15 +69,8 @@ fn create_send_block(
         return Err(CoreClientError::NotEnoughCoins);
     }
 
-    let previous = if sender_frontier.is_unopened() {
-        // should never occur with a send block, but just in case
-        [0; 32]
-    } else {
-        sender_frontier.block.hash()
-    };
-
     let representative = choose_representatives(
-        &client.config,
+        client,
         sender_frontier.block.representative.clone(),
         payment.new_representative.clone(),
     );
@@ -80,22 +78,25 @@ fn create_send_block(
     let block = Block {
         block_type: BlockType::Send,
         account: payment.sender.clone(),
-        previous,
+        previous: frontier.hash(),
         representative,
         balance: frontier.balance - payment.amount,
         link: payment.recipient.compressed.to_bytes(),
         signature: Signature::default(),
         work,
     };
-    client.wallet_db.sign_block(&client.seed, block)
+    client.sign_block(block)
 }
 
 /// Send to a `nano_` account.
 /// **Does** cache work for the next block, if enabled.
+///
+/// `custom_difficulty` overrides the default work difficulty for the send block, if given.
 pub async fn send(
     client: &CoreClient,
     work_client: &mut WorkManager,
     payment: Payment,
+    custom_difficulty: Option<[u8; 8]>,
 ) -> RpcResult<NewFrontiers> {
     if payment.sender == payment.recipient {
         return Err(CoreClientError::InvalidPayment);
@@ -107,7 +108,13 @@ pub async fn send(
         .ok_or(CoreClientError::AccountNotFound)?;
     let send_block = create_send_block(client, payment, frontier)?;
     let (info, rpc_failures) = ClientRpc()
-        .auto_publish_unsynced(&client.config, work_client, frontier, send_block)
+        .auto_publish_unsynced(
+            &client.config,
+            work_client,
+            frontier,
+            send_block,
+            custom_difficulty,
+        )
         .await?
         .into();
     Ok((vec![info].into(), rpc_failures).into())
@@ -117,16 +124,16 @@ pub async fn send(
 /// **Does not** cache work for the next block.
 async fn camo_auto_publish_blocks(
     client: &CoreClient,
-    notification_block: Block,
-    send_block: Block,
+    (notifier_frontier, notification_block): (&FrontierInfo, Block),
+    (sender_frontier, send_block): (&FrontierInfo, Block),
 ) -> RpcResult<(FrontierInfo, FrontierInfo)> {
     let mut rpc_failures = RpcFailures::default();
     let (notification_frontier, notification_failures) = ClientRpc()
-        .publish(&client.config, notification_block)
+        .publish(&client.config, notifier_frontier, notification_block)
         .await?
         .into();
     let (send_frontier, send_failures) = ClientRpc()
-        .publish(&client.config, send_block)
+        .publish(&client.config, sender_frontier, send_block)
         .await?
         .into();
     rpc_failures.merge_with(notification_failures);
@@ -150,6 +157,9 @@ async fn _send_camo_same(
         .frontiers_db
         .account_frontier(&payment.sender)
         .ok_or(CoreClientError::AccountNotFound)?;
+    if sender_frontier.is_unopened() {
+        return Err(CoreClientError::SendFromUnopenedAccount);
+    }
 
     let total_amount = payment.notification_amount + payment.sender_amount;
     if sender_frontier.block.balance < total_amount {
@@ -190,12 +200,24 @@ async fn _send_camo_same(
     // Publish both blocks: Notification first, to minimize damage if an error occurs
     info!("Creating notifier transaction (this might take a while)...");
     let (sender_frontier, mut rpc_failures) = ClientRpc()
-        .auto_publish_unsynced(&client.config, work_client, sender_frontier, notify_block)
+        .auto_publish_unsynced(
+            &client.config,
+            work_client,
+            sender_frontier,
+            notify_block,
+            None,
+        )
         .await?
         .into();
     info!("Creating sender transaction (this might take a while)...");
     let (sender_frontier, rpc_failures_2) = ClientRpc()
-        .auto_publish_unsynced(&client.config, work_client, &sender_frontier, send_block)
+        .auto_publish_unsynced(
+            &client.config,
+            work_client,
+            &sender_frontier,
+            send_block,
+            None,
+        )
         .await?
         .into();
     rpc_failures.merge_with(rpc_failures_2);
@@ -234,13 +256,18 @@ pub async fn send_camo(
         .account_frontier(&payment.notifier)
         .ok_or(CoreClientError::AccountNotFound)?;
 
-    // ensure that we have work for both blocks
-    let notification_work = ClientRpc().get_work(config, work_client, notifier_frontier)?;
-    let send_work = ClientRpc().get_work(config, work_client, sender_frontier)?;
-    let (notification_work, work_failures_1) = notification_work.into();
-    let (send_work, work_failures_2) = send_work.into();
-    rpc_failures.merge_with(work_failures_1);
-    rpc_failures.merge_with(work_failures_2);
+    // ensure that we have work for both blocks, requesting it concurrently
+    let frontiers = [
+        (*notifier_frontier, BlockType::Send),
+        (*sender_frontier, BlockType::Send),
+    ];
+    let (works, work_failures) = ClientRpc()
+        .get_work_many(config, work_client, &frontiers)?
+        .into();
+    let [notification_work, send_work]: [[u8; 8]; 2] = works
+        .try_into()
+        .expect("broken get_work_many() code: wrong number of results");
+    rpc_failures.merge_with(work_failures);
 
     info!("Creating sender block...");
     let sender_key = client
@@ -280,11 +307,16 @@ pub async fn send_camo(
 
     // cache work for future transactions
     if config.ENABLE_WORK_CACHE {
-        work_client.request_work(config, notification_block.hash());
-        work_client.request_work(config, send_block.hash());
+        work_client.request_work(config, notification_block.hash(), None);
+        work_client.request_work(config, send_block.hash(), None);
     }
 
-    let publish_success = camo_auto_publish_blocks(client, notification_block, send_block).await?;
+    let publish_success = camo_auto_publish_blocks(
+        client,
+        (notifier_frontier, notification_block),
+        (sender_frontier, send_block),
+    )
+    .await?;
 
     let ((notification_frontier, send_frontier), publish_failures) = publish_success.into();
     rpc_failures.merge_with(publish_failures);
@@ -293,3 +325,51 @@ pub async fn send_camo(
 
     Ok((frontiers, rpc_failures).into())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CoreClientConfig, RepresentativeStrategy};
+    use crate::wallet::WalletSeed;
+
+    #[test]
+    fn send_from_unopened_account() {
+        let client = CoreClient::new(WalletSeed::from([7; 32]), CoreClientConfig::test_default());
+        let sender = client.seed.get_key(0).0.to_account();
+        let payment = Payment {
+            sender: sender.clone(),
+            amount: 0,
+            recipient: client.seed.get_key(1).0.to_account(),
+            new_representative: None,
+        };
+        let result = create_send_block(&client, payment, &FrontierInfo::new_unopened(sender));
+        assert!(matches!(
+            result,
+            Err(CoreClientError::SendFromUnopenedAccount)
+        ));
+    }
+
+    #[test]
+    fn representative_strategy() {
+        let mut config = CoreClientConfig::test_default();
+        let seed = WalletSeed::from([7; 32]);
+        let reps: Vec<Account> = (0..3).map(|i| seed.get_key(i).0.to_account()).collect();
+        let other = seed.get_key(3).0.to_account();
+        config.REPRESENTATIVES = reps.clone();
+
+        // the current representative is kept, unless it is not in `REPRESENTATIVES`
+        config.REPRESENTATIVE_STRATEGY = RepresentativeStrategy::RoundRobin;
+        let client = CoreClient::new(seed.clone(), config.clone());
+        assert!(choose_representatives(&client, reps[1].clone(), None) == reps[1]);
+        let chosen: Vec<Account> = (0..4)
+            .map(|_| choose_representatives(&client, other.clone(), None))
+            .collect();
+        assert!(chosen == [&reps[..], &reps[..1]].concat());
+
+        config.REPRESENTATIVE_STRATEGY = RepresentativeStrategy::Fixed(Box::new(other.clone()));
+        let client = CoreClient::new(seed, config);
+        assert!(choose_representatives(&client, reps[1].clone(), None) == other);
+        // an explicit representative always takes priority
+        assert!(choose_representatives(&client, other.clone(), Some(reps[2].clone())) == reps[2]);
+    }
+}
@@ -1,7 +1,8 @@
 use crate::constants::*;
-use crate::rpc::Rpc;
-use nanopyrs::{camo::CamoVersion, Account};
-use serde::{Deserialize, Serialize};
+use crate::rpc::{dedup_rpcs, Rpc};
+use nanopyrs::{camo::CamoVersion, Account, BlockType};
+use rand::seq::SliceRandom;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
 use serde_with::{serde_as, DisplayFromStr};
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
@@ -9,6 +10,221 @@ fn default_true() -> bool {
     true
 }
 
+/// Order in which transactions are received when receiving in bulk
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Zeroize, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReceiveOrder {
+    /// Largest transactions first
+    Amount,
+    /// Grouped by recipient account, in the order the accounts first appear
+    Account,
+    /// The order in which the transactions were given
+    #[default]
+    Block,
+}
+
+/// How representatives are chosen for new blocks, when the account's current representative is not kept
+#[serde_as]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Zeroize, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RepresentativeStrategy {
+    /// A random representative from `REPRESENTATIVES`
+    #[default]
+    Random,
+    /// Always this representative, even if the current one is in `REPRESENTATIVES`
+    Fixed(#[serde_as(as = "Box<DisplayFromStr>")] Box<Account>),
+    /// Each of `REPRESENTATIVES` in turn
+    #[serde(rename = "round_robin")]
+    RoundRobin,
+}
+
+/// An account in the wallet, identified by either its index or its address
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Zeroize, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum AccountSelector {
+    Index(u32),
+    Account(#[serde_as(as = "Box<DisplayFromStr>")] Box<Account>),
+}
+impl AccountSelector {
+    pub fn matches(&self, index: u32, account: &Account) -> bool {
+        match self {
+            AccountSelector::Index(selected) => *selected == index,
+            AccountSelector::Account(selected) => selected.as_ref() == account,
+        }
+    }
+}
+
+/// A range of account indices, inclusive at both ends
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Zeroize, Serialize, Deserialize)]
+pub struct IndexRange {
+    #[serde(default)]
+    pub min: u32,
+    #[serde(default = "default_index_range_max")]
+    pub max: u32,
+}
+impl IndexRange {
+    pub fn contains(&self, index: u32) -> bool {
+        (self.min..=self.max).contains(&index)
+    }
+}
+
+fn default_index_range_max() -> u32 {
+    u32::MAX
+}
+
+/// Pin an RPC command to a specific node
+#[derive(Debug, Clone, PartialEq, Eq, Zeroize, Serialize, Deserialize)]
+pub struct RpcPin {
+    /// RPC command, e.g. `process`
+    pub command: String,
+    /// URL of the node, which must also be in `RPCS`
+    pub url: String,
+    /// Only ever use this node for the command, instead of trying it first
+    #[serde(default)]
+    pub exclusive: bool,
+}
+
+fn default_receive_split_threshold() -> u128 {
+    ONE_NANO * 100
+}
+
+fn default_auto_consolidate_camo_threshold() -> u128 {
+    ONE_MILLI_NANO
+}
+
+fn default_receivable_cache_limit() -> usize {
+    1000
+}
+
+fn default_wallet_kdf_memory_kib() -> u32 {
+    19 * 1024
+}
+
+fn default_wallet_kdf_iterations() -> u32 {
+    2
+}
+
+fn default_wallet_kdf_parallelism() -> u32 {
+    1
+}
+
+/// Lowest `WALLET_KDF_MEMORY_KIB` accepted, so that a typo cannot make the password trivial to brute-force
+const MIN_WALLET_KDF_MEMORY_KIB: u32 = 8 * 1024;
+
+fn deserialize_wallet_kdf_memory_kib<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<u32, D::Error> {
+    let memory_kib = u32::deserialize(deserializer)?;
+    if memory_kib < MIN_WALLET_KDF_MEMORY_KIB {
+        return Err(D::Error::custom(format!(
+            "WALLET_KDF_MEMORY_KIB must be at least {MIN_WALLET_KDF_MEMORY_KIB}"
+        )));
+    }
+    Ok(memory_kib)
+}
+
+fn deserialize_wallet_kdf_iterations<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<u32, D::Error> {
+    let iterations = u32::deserialize(deserializer)?;
+    if iterations == 0 {
+        return Err(D::Error::custom("WALLET_KDF_ITERATIONS must be at least 1"));
+    }
+    Ok(iterations)
+}
+
+fn deserialize_wallet_kdf_parallelism<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<u32, D::Error> {
+    let parallelism = u32::deserialize(deserializer)?;
+    if parallelism == 0 {
+        return Err(D::Error::custom(
+            "WALLET_KDF_PARALLELISM must be at least 1",
+        ));
+    }
+    Ok(parallelism)
+}
+
+fn default_rpc_log_size() -> usize {
+    50
+}
+
+fn default_price_api_url() -> String {
+    "https://api.coingecko.com/api/v3/simple/price?ids=nano&vs_currencies={currency}".into()
+}
+
+fn default_price_cache_seconds() -> u64 {
+    ONE_MINUTE * 5
+}
+
+fn default_work_regeneration_limit() -> usize {
+    3
+}
+
+/// Number of logical cores minus one, leaving a core free for the rest of the system
+fn default_local_work_threads() -> usize {
+    std::thread::available_parallelism()
+        .map(|threads| threads.get().saturating_sub(1))
+        .unwrap_or(1)
+        .max(1)
+}
+
+fn deserialize_local_work_threads<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<usize, D::Error> {
+    let threads = usize::deserialize(deserializer)?;
+    if threads == 0 {
+        return Err(D::Error::custom("LOCAL_WORK_THREADS must be at least 1"));
+    }
+    Ok(threads)
+}
+
+fn default_max_concurrent_work_requests() -> usize {
+    2
+}
+
+fn deserialize_max_concurrent_work_requests<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<usize, D::Error> {
+    let requests = usize::deserialize(deserializer)?;
+    if requests == 0 {
+        return Err(D::Error::custom(
+            "MAX_CONCURRENT_WORK_REQUESTS must be at least 1",
+        ));
+    }
+    Ok(requests)
+}
+
+fn default_work_difficulty_receive() -> u64 {
+    0xfffffe0000000000
+}
+
+/// RPCs with the same URL are merged, see `dedup_rpcs()`
+fn deserialize_rpcs<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Rpc>, D::Error> {
+    Ok(dedup_rpcs(Vec::<Rpc>::deserialize(deserializer)?))
+}
+
+fn default_rpc_retry_cooldown_seconds() -> u64 {
+    10
+}
+
+fn default_rpc_command_retry_budget() -> usize {
+    24
+}
+
+fn default_confirmation_timeout_seconds() -> u64 {
+    30
+}
+
+fn default_priority_work_difficulty() -> u64 {
+    0xfffffffc00000000
+}
+
+fn default_work_difficulty_auto_interval() -> u64 {
+    600
+}
+
 #[allow(non_snake_case)]
 #[serde_as]
 #[derive(Debug, Clone, Zeroize, ZeroizeOnDrop, Serialize, Deserialize)]
@@ -17,6 +233,18 @@ pub struct CoreClientConfig {
     /// Does not apply to camo payments.
     pub NORMAL_DUST_THRESHOLD: u128,
 
+    /// Amount (in raw) to leave on an account when sending its full balance (including sweeps and consolidation),
+    /// also taken into account when automatically selecting accounts to send from.
+    /// Useful for keeping accounts opened (added in v0.1.2)
+    #[serde(default)]
+    pub RESERVE_RAW: u128,
+
+    /// Limit on the number of cached receivable transactions.
+    /// When exceeded, the smallest transactions are dropped from the cache (they will re-appear on refresh).
+    /// (added in v0.1.2)
+    #[serde(default = "default_receivable_cache_limit")]
+    pub RECEIVABLE_CACHE_LIMIT: usize,
+
     /// Limit on the number of normal and camo accounts in the database.
     /// The limit is separate for each account type.
     ///
@@ -31,8 +259,84 @@ pub struct CoreClientConfig {
     pub RPC_USE_BANNED_NODES_AS_BACKUP: bool,
     /// Number of times to re-attempt a failed RPC command
     pub RPC_RETRY_LIMIT: usize,
+    /// When re-attempting a failed RPC command, RPCs which failed it less than this many seconds ago
+    /// are tried last. Unlike a ban, this only lasts for the one command (added in v0.1.2)
+    #[serde(default = "default_rpc_retry_cooldown_seconds")]
+    pub RPC_RETRY_COOLDOWN_SECONDS: u64,
+    /// Maximum number of re-attempts shared by all of the RPC commands made by a single wallet command,
+    /// so that commands making many RPC calls cannot retry for too long. `0` means unlimited (added in v0.1.2)
+    #[serde(default = "default_rpc_command_retry_budget")]
+    pub RPC_COMMAND_RETRY_BUDGET: usize,
+    /// Return an error on the first failed RPC request, instead of trying other RPCs.
+    /// Useful for debugging a specific node (added in v0.1.2)
+    #[serde(default)]
+    pub RPC_STRICT_MODE: bool,
+    /// Nodes to use first (or exclusively) for specific RPC commands,
+    /// e.g. to only publish blocks through a trusted node (added in v0.1.2)
+    #[serde(default)]
+    pub RPC_PINNED: Vec<RpcPin>,
+    /// Number of recent RPC requests to keep in memory for debugging (added in v0.1.2)
+    #[serde(default = "default_rpc_log_size")]
+    pub RPC_LOG_SIZE: usize,
+    /// Proxy for price requests (see `PRICE_API_URL`).
+    /// RPC requests cannot be sent through a proxy yet, so none are made while this or any RPC's own proxy is set,
+    /// rather than connecting directly (added in v0.1.2)
+    #[serde(default)]
+    pub GLOBAL_PROXY: Option<String>,
+    /// Listen for incoming transactions on a node's websocket, in addition to finding them with `refresh`.
+    /// Camo payments are still only found with `refresh`.
+    /// Ignored if `GLOBAL_PROXY` or any RPC's own proxy is set, since websockets cannot use a proxy (added in v0.1.2)
+    #[serde(default)]
+    pub WEBSOCKET_ENABLED: bool,
+    /// `ws://` URLs of node websockets, each tried in turn whenever the connection drops (added in v0.1.2)
+    #[serde(default)]
+    pub WEBSOCKET_URLS: Vec<String>,
+    /// URL for `balance --fiat`, returning the price of Nano in the format of CoinGecko's `simple/price` API.
+    /// `{currency}` is replaced by the requested currency. Requests go through `GLOBAL_PROXY` if set,
+    /// and are not made at all if it is a SOCKS proxy, which is not supported here (added in v0.1.2)
+    #[serde(default = "default_price_api_url")]
+    pub PRICE_API_URL: String,
+    /// How long a downloaded price is reused for (added in v0.1.2)
+    #[serde(default = "default_price_cache_seconds")]
+    pub PRICE_CACHE_SECONDS: u64,
     /// Default work difficulty
     pub WORK_DIFFICULTY: u64,
+    /// Work difficulty used for receive blocks, which the network accepts at a lower threshold.
+    /// Cached work is still generated at `WORK_DIFFICULTY`, since the next block's type is unknown (added in v0.1.2)
+    #[serde(default = "default_work_difficulty_receive")]
+    pub WORK_DIFFICULTY_RECEIVE: u64,
+    /// Work difficulty used for high-priority transactions (added in v0.1.2)
+    #[serde(default = "default_priority_work_difficulty")]
+    pub PRIORITY_WORK_DIFFICULTY: u64,
+    /// Periodically download the network's current work difficulty with `active_difficulty`,
+    /// overwriting `WORK_DIFFICULTY` and `WORK_DIFFICULTY_RECEIVE` (added in v0.1.2)
+    #[serde(default)]
+    pub WORK_DIFFICULTY_AUTO: bool,
+    /// How often to download the network's work difficulty, if `WORK_DIFFICULTY_AUTO` (added in v0.1.2)
+    #[serde(default = "default_work_difficulty_auto_interval")]
+    pub WORK_DIFFICULTY_AUTO_INTERVAL_SECONDS: u64,
+    /// Number of times work will be immediately re-requested for a frontier after invalid work was returned.
+    /// Once exceeded, no more work is requested for that frontier until the wallet is restarted (added in v0.1.2)
+    #[serde(default = "default_work_regeneration_limit")]
+    pub WORK_REGENERATION_LIMIT: usize,
+    /// Number of CPU threads used to generate work locally, which must be at least 1.
+    /// Defaults to the number of logical cores minus one (added in v0.1.2)
+    #[serde(
+        default = "default_local_work_threads",
+        deserialize_with = "deserialize_local_work_threads"
+    )]
+    pub LOCAL_WORK_THREADS: usize,
+    /// Maximum number of work cache requests in progress at once, which must be at least 1.
+    /// Higher values increase the load on RPCs which support `work_generate` (added in v0.1.2)
+    #[serde(
+        default = "default_max_concurrent_work_requests",
+        deserialize_with = "deserialize_max_concurrent_work_requests"
+    )]
+    pub MAX_CONCURRENT_WORK_REQUESTS: usize,
+    /// Generate work with the local CPU (using `LOCAL_WORK_THREADS`) if no RPC which supports
+    /// `work_generate` is usable, or if they all fail. Likely very slow (added in v0.1.2)
+    #[serde(default)]
+    pub WORK_LOCAL_ENABLED: bool,
 
     /// `count` field of `accounts_receivable`
     pub RPC_ACCOUNTS_RECEIVABLE_BATCH_SIZE: usize,
@@ -43,14 +347,109 @@ pub struct CoreClientConfig {
     /// Enable setting work cache (added in v0.1.1)
     #[serde(default = "default_true")]
     pub ENABLE_WORK_CACHE: bool,
+    /// If receiving a transaction fails because the nodes used do not have its source block,
+    /// retry using only nodes which support `account_history` and `block_info`, in case it was pruned (added in v0.1.2)
+    #[serde(default = "default_true")]
+    pub RECEIVE_RETRY_FULL_HISTORY_NODES: bool,
+    /// When opening a new account, skip representatives which have no voting weight.
+    /// Requires an extra `account_info` request per representative checked,
+    /// and only applies to the `random` `REPRESENTATIVE_STRATEGY` (added in v0.1.2)
+    #[serde(default)]
+    pub CHECK_NEW_ACCOUNT_REPRESENTATIVES: bool,
+    /// Minimum amount of a received transaction for it to be split with `receive --split` (added in v0.1.2)
+    #[serde(default = "default_receive_split_threshold")]
+    pub RECEIVE_SPLIT_THRESHOLD: u128,
+    /// Order in which transactions are received when receiving in bulk (added in v0.1.2)
+    #[serde(default)]
+    pub RECEIVE_ORDER: ReceiveOrder,
+    /// Forget receivable transactions which would overflow the recipient's balance,
+    /// instead of keeping them to be received later (added in v0.1.2)
+    #[serde(default)]
+    pub RECEIVE_DROP_OVERFLOWING: bool,
+    /// On `refresh`, list cached receivable transactions which are no longer receivable
+    /// (e.g. received elsewhere), instead of silently forgetting them (added in v0.1.2)
+    #[serde(default = "default_true")]
+    pub REPORT_VANISHED_RECEIVABLE: bool,
+    /// Only check for and bulk-receive transactions on accounts whose index is within this range,
+    /// e.g. with `min` set to 10 to leave accounts 0-9 alone. Derived accounts use the index of their camo account,
+    /// and watch-only accounts are always included (added in v0.1.2)
+    #[serde(default)]
+    pub RECEIVE_INDEX_RANGE: Option<IndexRange>,
+    /// After receiving camo payments, send the funds of each derived account into its camo account's signer account
+    /// (or `AUTO_CONSOLIDATE_CAMO_DESTINATION`), as with `consolidate` (added in v0.1.2)
+    #[serde(default)]
+    pub AUTO_CONSOLIDATE_CAMO: bool,
+    /// Derived accounts with less than this to send (i.e. above `RESERVE_RAW`) are left alone by `AUTO_CONSOLIDATE_CAMO`.
+    /// Accounts holding no more than `RESERVE_RAW` are always left alone (added in v0.1.2)
+    #[serde(default = "default_auto_consolidate_camo_threshold")]
+    pub AUTO_CONSOLIDATE_CAMO_THRESHOLD: u128,
+    /// Account to consolidate into instead of the camo account's signer account (added in v0.1.2)
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub AUTO_CONSOLIDATE_CAMO_DESTINATION: Option<Account>,
+
+    /// Only count and spend the balances of accounts whose frontier block the network reports as confirmed,
+    /// downloading confirmation status with `blocks_info` as needed (added in v0.1.2).
+    /// Confirmation status is not saved, so it is downloaded again when the wallet is loaded, and on 'refresh'.
+    #[serde(default)]
+    pub CONFIRMATION_REQUIRED: bool,
+    /// How long `--wait` waits for a published block to be confirmed (added in v0.1.2)
+    #[serde(default = "default_confirmation_timeout_seconds")]
+    pub CONFIRMATION_TIMEOUT_SECONDS: u64,
+    /// When a wallet is loaded, re-submit each account's frontier block if the network does not report it as confirmed,
+    /// in case it was dropped after a crash (added in v0.1.2)
+    #[serde(default)]
+    pub REPUBLISH_ON_LOAD: bool,
+
+    /// Account to always list first in `balance` and `accounts`, by index or address (added in v0.1.2)
+    #[serde(default)]
+    pub PRIMARY_ACCOUNT: Option<AccountSelector>,
+    /// Lock the wallet after this many seconds without a command, requiring the password again.
+    /// `0` disables auto-locking (added in v0.1.2)
+    #[serde(default)]
+    pub AUTO_LOCK_SECONDS: u64,
+    /// Argon2 memory cost (in KiB) of the key derived from the password when a wallet is saved, which must be at least 8192.
+    /// Higher values make the password harder to brute-force, but the wallet slower to unlock.
+    /// Existing wallets keep the parameters they were saved with until saved again (added in v0.1.2)
+    #[serde(
+        default = "default_wallet_kdf_memory_kib",
+        deserialize_with = "deserialize_wallet_kdf_memory_kib"
+    )]
+    pub WALLET_KDF_MEMORY_KIB: u32,
+    /// Argon2 iterations, which must be at least 1, see `WALLET_KDF_MEMORY_KIB` (added in v0.1.2)
+    #[serde(
+        default = "default_wallet_kdf_iterations",
+        deserialize_with = "deserialize_wallet_kdf_iterations"
+    )]
+    pub WALLET_KDF_ITERATIONS: u32,
+    /// Argon2 parallelism, which must be at least 1, see `WALLET_KDF_MEMORY_KIB` (added in v0.1.2)
+    #[serde(
+        default = "default_wallet_kdf_parallelism",
+        deserialize_with = "deserialize_wallet_kdf_parallelism"
+    )]
+    pub WALLET_KDF_PARALLELISM: u32,
 
     /// Default version to use for generating `camo_` addresses
     pub DEFAULT_CAMO_VERSIONS: Vec<CamoVersion>,
+    /// With `send_camo --auto`, refuse to send if no account other than the sender can be the notifier,
+    /// instead of warning and using the sender, which links the payment to its notification (added in v0.1.2)
+    #[serde(default)]
+    pub STRICT_AUTO_NOTIFIER: bool,
 
     /// Representatives for connecting to the Nano network
     #[serde_as(as = "Vec<DisplayFromStr>")]
     pub REPRESENTATIVES: Vec<Account>,
-    /// RPCs to use for connecting to the Nano network
+    /// How to choose the representative of new blocks: `random`, `round_robin`, or `!fixed <account>` (added in v0.1.2)
+    #[serde(default)]
+    pub REPRESENTATIVE_STRATEGY: RepresentativeStrategy,
+    /// Representatives to choose from when deliberately changing an account's representative.
+    /// Falls back to `REPRESENTATIVES` if empty (added in v0.1.2)
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(default)]
+    pub VOTING_REPRESENTATIVES: Vec<Account>,
+    /// RPCs to use for connecting to the Nano network.
+    /// RPCs with the same URL are merged when loaded.
+    #[serde(deserialize_with = "deserialize_rpcs")]
     pub RPCS: Vec<Rpc>,
 }
 impl CoreClientConfig {
@@ -62,23 +461,94 @@ impl CoreClientConfig {
         CoreClientConfig {
             NORMAL_DUST_THRESHOLD: ONE_MICRO_NANO,
 
+            RESERVE_RAW: 0,
+
+            RECEIVABLE_CACHE_LIMIT: default_receivable_cache_limit(),
+
             DB_NUMBER_OF_ACCOUNTS_LIMIT: 20,
 
             RPC_INVALID_DATA_BAN_TIME: ONE_HOUR * 12,
             RPC_FAILURE_BAN_TIME: ONE_MINUTE * 20,
             RPC_USE_BANNED_NODES_AS_BACKUP: true,
             RPC_RETRY_LIMIT: 8,
+            RPC_RETRY_COOLDOWN_SECONDS: default_rpc_retry_cooldown_seconds(),
+            RPC_COMMAND_RETRY_BUDGET: default_rpc_command_retry_budget(),
+            RPC_STRICT_MODE: false,
+            RPC_PINNED: vec![],
+            RPC_LOG_SIZE: default_rpc_log_size(),
+            GLOBAL_PROXY: None,
+            WEBSOCKET_ENABLED: false,
+            WEBSOCKET_URLS: vec![],
+            PRICE_API_URL: default_price_api_url(),
+            PRICE_CACHE_SECONDS: default_price_cache_seconds(),
             WORK_DIFFICULTY: 0xfffffff800000000,
+            WORK_DIFFICULTY_RECEIVE: default_work_difficulty_receive(),
+            PRIORITY_WORK_DIFFICULTY: default_priority_work_difficulty(),
+            WORK_DIFFICULTY_AUTO: false,
+            WORK_DIFFICULTY_AUTO_INTERVAL_SECONDS: default_work_difficulty_auto_interval(),
+            WORK_REGENERATION_LIMIT: default_work_regeneration_limit(),
+            LOCAL_WORK_THREADS: default_local_work_threads(),
+            MAX_CONCURRENT_WORK_REQUESTS: default_max_concurrent_work_requests(),
+            WORK_LOCAL_ENABLED: false,
 
             RPC_ACCOUNTS_RECEIVABLE_BATCH_SIZE: 25,
             RPC_ACCOUNT_HISTORY_BATCH_SIZE: 50,
             RPC_RECEIVE_TRANSACTIONS_BATCH_SIZE: 3,
             ENABLE_WORK_CACHE: true,
+            RECEIVE_RETRY_FULL_HISTORY_NODES: true,
+            CHECK_NEW_ACCOUNT_REPRESENTATIVES: false,
+            RECEIVE_SPLIT_THRESHOLD: default_receive_split_threshold(),
+            RECEIVE_ORDER: ReceiveOrder::default(),
+            RECEIVE_DROP_OVERFLOWING: false,
+            REPORT_VANISHED_RECEIVABLE: true,
+            RECEIVE_INDEX_RANGE: None,
+            AUTO_CONSOLIDATE_CAMO: false,
+            AUTO_CONSOLIDATE_CAMO_THRESHOLD: default_auto_consolidate_camo_threshold(),
+            AUTO_CONSOLIDATE_CAMO_DESTINATION: None,
+            CONFIRMATION_REQUIRED: false,
+            CONFIRMATION_TIMEOUT_SECONDS: default_confirmation_timeout_seconds(),
+            REPUBLISH_ON_LOAD: false,
+
+            PRIMARY_ACCOUNT: None,
+            AUTO_LOCK_SECONDS: 0,
+            WALLET_KDF_MEMORY_KIB: default_wallet_kdf_memory_kib(),
+            WALLET_KDF_ITERATIONS: default_wallet_kdf_iterations(),
+            WALLET_KDF_PARALLELISM: default_wallet_kdf_parallelism(),
 
             DEFAULT_CAMO_VERSIONS: vec![CamoVersion::One],
+            STRICT_AUTO_NOTIFIER: false,
 
             REPRESENTATIVES: reps,
-            RPCS: rpcs,
+            REPRESENTATIVE_STRATEGY: RepresentativeStrategy::default(),
+            VOTING_REPRESENTATIVES: vec![],
+            RPCS: dedup_rpcs(rpcs),
+        }
+    }
+
+    /// Representatives to choose from for `change` blocks:
+    /// `VOTING_REPRESENTATIVES`, or `REPRESENTATIVES` if none are configured
+    pub fn voting_representatives(&self) -> &[Account] {
+        if self.VOTING_REPRESENTATIVES.is_empty() {
+            &self.REPRESENTATIVES
+        } else {
+            &self.VOTING_REPRESENTATIVES
+        }
+    }
+
+    /// Choose a random representative for a `change` block
+    pub fn choose_voting_representative(&self) -> Account {
+        self.voting_representatives()
+            .choose(&mut rand::thread_rng())
+            .expect("no representatives to choose from")
+            .clone()
+    }
+
+    /// Default work difficulty for a block of this type: `WORK_DIFFICULTY_RECEIVE` for receive blocks,
+    /// and `WORK_DIFFICULTY` otherwise
+    pub fn work_difficulty(&self, block_type: &BlockType) -> [u8; 8] {
+        match block_type {
+            BlockType::Receive => self.WORK_DIFFICULTY_RECEIVE.to_be_bytes(),
+            _ => self.WORK_DIFFICULTY.to_be_bytes(),
         }
     }
 
@@ -89,6 +559,58 @@ impl CoreClientConfig {
             vec![],
         );
         config.WORK_DIFFICULTY = 0;
+        config.WORK_DIFFICULTY_RECEIVE = 0;
+        config.PRIORITY_WORK_DIFFICULTY = 0;
         config
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_work_threads() {
+        assert!(default_local_work_threads() >= 1);
+        assert!(CoreClientConfig::test_default().LOCAL_WORK_THREADS >= 1);
+
+        let threads = deserialize_local_work_threads(serde_json::Value::from(4)).unwrap();
+        assert!(threads == 4);
+        assert!(deserialize_local_work_threads(serde_json::Value::from(0)).is_err());
+    }
+
+    #[test]
+    fn representative_strategy() {
+        let account = nanopyrs::constants::get_genesis_account();
+        let fixed: RepresentativeStrategy =
+            serde_json::from_value(serde_json::json!({ "fixed": account.to_string() })).unwrap();
+        assert!(fixed == RepresentativeStrategy::Fixed(Box::new(account)));
+
+        let round_robin: RepresentativeStrategy =
+            serde_json::from_value(serde_json::json!("round_robin")).unwrap();
+        assert!(round_robin == RepresentativeStrategy::RoundRobin);
+        assert!(
+            serde_json::from_value::<RepresentativeStrategy>(serde_json::json!("fixed")).is_err()
+        );
+    }
+
+    #[test]
+    fn max_concurrent_work_requests() {
+        let requests = deserialize_max_concurrent_work_requests(serde_json::Value::from(4));
+        assert!(requests.unwrap() == 4);
+        assert!(deserialize_max_concurrent_work_requests(serde_json::Value::from(0)).is_err());
+    }
+
+    #[test]
+    fn wallet_kdf_params() {
+        let memory_kib = serde_json::Value::from(default_wallet_kdf_memory_kib());
+        assert!(deserialize_wallet_kdf_memory_kib(memory_kib).is_ok());
+        let too_low = serde_json::Value::from(MIN_WALLET_KDF_MEMORY_KIB - 1);
+        assert!(deserialize_wallet_kdf_memory_kib(too_low).is_err());
+
+        assert!(deserialize_wallet_kdf_iterations(serde_json::Value::from(2)).unwrap() == 2);
+        assert!(deserialize_wallet_kdf_iterations(serde_json::Value::from(0)).is_err());
+        assert!(deserialize_wallet_kdf_parallelism(serde_json::Value::from(1)).unwrap() == 1);
+        assert!(deserialize_wallet_kdf_parallelism(serde_json::Value::from(0)).is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::rpc::RpcFailures;
 use nanopyrs::{rpc::RpcError, NanoError};
 use thiserror::Error;
 use tokio::task::JoinError;
@@ -10,24 +11,60 @@ pub enum CoreClientError {
     RpcError(#[from] RpcError),
     #[error(transparent)]
     JoinError(#[from] JoinError),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
     #[error("the given RPC command could not be performed on any known node")]
     RpcCommandFailed,
+    #[error("the command's RPC retry budget was used up (see 'RPC_COMMAND_RETRY_BUDGET')")]
+    RpcRetryBudgetExhausted,
+    #[error("RPC requests cannot be sent through a proxy yet: unset 'GLOBAL_PROXY' and the RPCs' own proxies to connect directly")]
+    ProxyUnsupported,
+    #[error("RPC command '{command}' failed on {url} (strict mode): {err}")]
+    RpcStrictModeFailure {
+        command: String,
+        url: String,
+        err: RpcError,
+    },
+    #[error("failed to get the price of Nano: {0}")]
+    PriceError(String),
     #[error("no usable RPC could be found")]
     NoUsableRPCs,
+    #[error("failed to generate work locally")]
+    LocalWorkFailed,
     #[error("invalid seed")]
     InvalidSeed,
+    #[error("invalid mnemonic: {0}")]
+    InvalidMnemonic(String),
     #[error("account not found")]
     AccountNotFound,
     #[error("the number of accounts in the DB has reached the limit")]
     DBAccountLimitReached,
     #[error("not enough coins")]
     NotEnoughCoins,
+    #[error("cannot send from an unopened account: receive funds to it first")]
+    SendFromUnopenedAccount,
+    #[error("cannot change the representative of an unopened account: receive funds to it first")]
+    ChangeUnopenedAccount,
+    #[error("the account's frontier has not been confirmed by the network")]
+    UnconfirmedFrontier,
+    #[error("the node rejected the block: {0}")]
+    BlockRejected(String),
+    #[error("fork: the cached frontier of {account} is out of date (the network's frontier is {head}): try 'refresh'")]
+    Fork { account: String, head: String },
     #[error("amount below dust threshold")]
     BelowDustThreshold,
     #[error("invalid payment: account cannot send coins to itself")]
     InvalidPayment,
     #[error("the blocks database detected a balance overflow")]
     FrontierBalanceOverflow,
+    #[error("receiving the transaction would overflow the account's balance")]
+    ReceivableBalanceOverflow,
     #[error("the blocks database detected an invalid epoch block")]
     InvalidEpochBlock,
+    #[error("the source block of the receivable transaction could not be found on any node")]
+    SourceBlockUnavailable,
+    #[error("the source block of the receivable transaction is missing on every node tried (it may have been pruned)")]
+    SourceBlockMissing(RpcFailures),
+    #[error("websocket error: {0}")]
+    WebsocketError(String),
 }
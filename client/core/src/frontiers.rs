@@ -2,20 +2,13 @@ use crate::{error::CoreClientError, CoreClientConfig};
 use log::{debug, error};
 use nanopyrs::{block::check_work, rpc::BlockInfo, Account, Block, BlockType, Signature};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
 macro_rules! search {
-    ($vec: expr, Hash, $value: expr) => {{
-        $vec.iter().position(|item| &item.block.hash() == &$value)
-    }};
-
     ($vec: expr, WorkHash, $value: expr) => {{
         $vec.iter().position(|item| &item.work_hash() == &$value)
     }};
-
-    ($vec: expr, Account, $value: expr) => {{
-        $vec.iter().position(|item| &item.block.account == $value)
-    }};
 }
 
 #[derive(Debug, Clone, Default, Zeroize, Serialize, Deserialize)]
@@ -31,6 +24,16 @@ impl NewFrontiers {
     pub fn merge_with(&mut self, other: NewFrontiers) {
         self.new.extend(other.new);
     }
+
+    /// Remove any frontiers which do not belong to one of the given accounts, and return them
+    pub(crate) fn take_unrequested(&mut self, accounts: &[Account]) -> Vec<FrontierInfo> {
+        let (requested, unrequested) = self
+            .new
+            .drain(..)
+            .partition(|frontier| accounts.contains(&frontier.block.account));
+        self.new = requested;
+        unrequested
+    }
 }
 impl From<Vec<Block>> for NewFrontiers {
     fn from(value: Vec<Block>) -> Self {
@@ -103,9 +106,11 @@ impl FrontierInfo {
         self.cached_work
     }
 
-    pub fn cache_work(&mut self, config: &CoreClientConfig, work: [u8; 8]) {
+    /// Returns `false` if the work was invalid, and was not cached
+    pub fn cache_work(&mut self, config: &CoreClientConfig, work: [u8; 8]) -> bool {
         self.cached_work = Some(work);
-        if !self.has_valid_work(config) {
+        let valid = self.has_valid_work(config);
+        if !valid {
             let account = &self.block.account;
             let work_hash = hex::encode_upper(self.work_hash());
             let work = hex::encode(work);
@@ -114,15 +119,22 @@ impl FrontierInfo {
             );
             self.clear_work();
         }
+        valid
     }
 
     pub fn clear_work(&mut self) {
         self.cached_work = None
     }
 
-    pub fn has_valid_work(&mut self, config: &CoreClientConfig) -> bool {
+    /// Whether or not the cached work meets `WORK_DIFFICULTY`, and can be used for any block type
+    pub fn has_valid_work(&self, config: &CoreClientConfig) -> bool {
+        self.has_valid_work_for(config, &BlockType::Send)
+    }
+
+    /// Whether or not the cached work meets the difficulty of the next block, given its type
+    pub fn has_valid_work_for(&self, config: &CoreClientConfig, block_type: &BlockType) -> bool {
         if let Some(work) = self.cached_work {
-            check_work(self.work_hash(), config.WORK_DIFFICULTY.to_be_bytes(), work)
+            check_work(self.work_hash(), config.work_difficulty(block_type), work)
         } else {
             false
         }
@@ -143,17 +155,52 @@ impl From<FrontierInfo> for (Block, Option<[u8; 8]>) {
 }
 
 #[derive(Debug, Clone, Default, Zeroize, ZeroizeOnDrop, Serialize, Deserialize)]
+#[serde(from = "FrontiersDBSerde", into = "FrontiersDBSerde")]
 pub struct FrontiersDB {
-    pub frontiers: Vec<FrontierInfo>,
+    frontiers: Vec<FrontierInfo>,
 
     /// This is for sanity checking, and **not** necessarily the wallet's balance
     frontiers_balance: u128,
+
+    /// Index of each account's frontier in `frontiers`
+    #[zeroize(skip)]
+    accounts: HashMap<Account, usize>,
+    /// Index of each frontier block in `frontiers`
+    #[zeroize(skip)]
+    hashes: HashMap<[u8; 32], usize>,
+    /// Frontier blocks which the network reported as confirmed.
+    /// Not saved to disk, since it can be re-downloaded with `CoreClient::update_confirmed()`,
+    /// which frontends should do when loading a wallet.
+    #[zeroize(skip)]
+    confirmed: HashSet<[u8; 32]>,
+    /// Amount sent by each unconfirmed frontier block, plus the unconfirmed blocks before it.
+    /// Only known for blocks which directly follow the account's previous frontier in the database.
+    /// Not saved to disk, like `confirmed`.
+    #[zeroize(skip)]
+    pending_sends: HashMap<[u8; 32], u128>,
 }
 impl FrontiersDB {
+    /// Index the frontiers from `start` onwards, e.g. after they were shifted by a removal
+    fn _reindex(&mut self, start: usize) {
+        for (index, frontier) in self.frontiers.iter().enumerate().skip(start) {
+            self.accounts.insert(frontier.block.account.clone(), index);
+            self.hashes.insert(frontier.block.hash(), index);
+        }
+    }
+
+    fn _account_index(&self, account: &Account) -> Option<usize> {
+        self.accounts.get(account).copied()
+    }
+
+    /// The frontiers in the database, in the order they were added
+    pub fn frontiers(&self) -> &[FrontierInfo] {
+        &self.frontiers
+    }
+
     /// Returns `None` if the frontier could not be found
     fn get_hash(&self, hash: [u8; 32]) -> Option<&FrontierInfo> {
-        let index = search!(self.frontiers, Hash, hash)?;
-        Some(&self.frontiers[index])
+        let index = self.hashes.get(&hash)?;
+        Some(&self.frontiers[*index])
     }
 
     /// Returns `None` if the frontier could not be found
@@ -177,7 +224,7 @@ impl FrontiersDB {
         let block = &new.block;
         let mut total = self.frontiers_balance;
 
-        let index = search!(self.frontiers, Account, &new.block.account);
+        let index = self._account_index(&new.block.account);
 
         if let Some(index) = index {
             // if this account already has a DB entry
@@ -217,6 +264,9 @@ impl FrontiersDB {
             "broken FrontiersDB code: account already exists in the DB"
         );
         self.frontiers_balance += new.block.balance;
+        self.accounts
+            .insert(new.block.account.clone(), self.frontiers.len());
+        self.hashes.insert(new.block.hash(), self.frontiers.len());
         self.frontiers.push(new);
     }
 
@@ -228,7 +278,26 @@ impl FrontiersDB {
         );
         self.frontiers_balance -= self.frontiers[index].block.balance;
         self.frontiers_balance += new.block.balance;
-        std::mem::replace(&mut self.frontiers[index], new)
+        self.hashes.insert(new.block.hash(), index);
+        let old = std::mem::replace(&mut self.frontiers[index], new);
+        // the hash is unchanged if the same frontier is inserted again
+        let old_hash = old.block.hash();
+        let new = &self.frontiers[index].block;
+        if new.hash() != old_hash {
+            self.hashes.remove(&old_hash);
+            let old_pending = match self.confirmed.remove(&old_hash) {
+                true => 0,
+                false => self.pending_sends.remove(&old_hash).unwrap_or(0),
+            };
+            let sent = match new.block_type {
+                BlockType::Send => old.block.balance.saturating_sub(new.balance),
+                _ => 0,
+            };
+            if new.previous == old_hash && old_pending + sent > 0 {
+                self.pending_sends.insert(new.hash(), old_pending + sent);
+            }
+        }
+        old
     }
 
     /// Adds or updates the frontier of an account which may or may not already be in the database
@@ -244,7 +313,13 @@ impl FrontiersDB {
     /// Remove an account from the database, and return its frontier
     fn _remove(&mut self, index: usize) -> FrontierInfo {
         self.frontiers_balance -= self.frontiers[index].block.balance;
-        self.frontiers.remove(index)
+        let removed = self.frontiers.remove(index);
+        self.accounts.remove(&removed.block.account);
+        self.hashes.remove(&removed.block.hash());
+        self.confirmed.remove(&removed.block.hash());
+        self.pending_sends.remove(&removed.block.hash());
+        self._reindex(index);
+        removed
     }
 
     /// Add or update an account's frontier.
@@ -277,7 +352,7 @@ impl FrontiersDB {
 
     /// Remove an account from the database, and return whether or not it was in the database
     pub fn remove(&mut self, account: &Account) -> Result<FrontierInfo, CoreClientError> {
-        search!(self.frontiers, Account, account)
+        self._account_index(account)
             .map(|index| self._remove(index))
             .ok_or(CoreClientError::AccountNotFound)
     }
@@ -295,7 +370,7 @@ impl FrontiersDB {
 
     /// Returns `None` if the account could not be found
     pub fn account_frontier(&self, account: &Account) -> Option<&FrontierInfo> {
-        self._get_index(search!(self.frontiers, Account, account))
+        self._get_index(self._account_index(account))
     }
 
     /// Returns `None` if an account could not be found
@@ -315,9 +390,10 @@ impl FrontiersDB {
             .collect()
     }
 
-    /// Returns `None` if the account could not be found
+    /// Returns `None` if the account could not be found.
+    /// The block must not be modified, since the database is indexed by account and hash.
     pub fn account_frontier_mut(&mut self, account: &Account) -> Option<&mut FrontierInfo> {
-        self._get_index_mut(search!(self.frontiers, Account, account))
+        self._get_index_mut(self._account_index(account))
     }
 
     /// Returns `None` if the account could not be found
@@ -334,38 +410,100 @@ impl FrontiersDB {
             .collect()
     }
 
+    /// Whether or not the account's frontier is known to be confirmed.
+    /// Unopened accounts are always confirmed, and unknown accounts never are.
+    pub fn is_confirmed(&self, account: &Account) -> bool {
+        self.account_frontier(account).is_some_and(|frontier| {
+            frontier.is_unopened() || self.confirmed.contains(&frontier.block.hash())
+        })
+    }
+
+    /// Mark these frontier blocks as confirmed. Hashes which are not frontiers in the database are ignored.
+    pub fn set_confirmed(&mut self, hashes: &[[u8; 32]]) {
+        for hash in hashes {
+            if self.hashes.contains_key(hash) {
+                self.confirmed.insert(*hash);
+                self.pending_sends.remove(hash);
+            }
+        }
+    }
+
+    /// The amount sent by the account's blocks which are not known to be confirmed,
+    /// which its balance already excludes. Returns `0` if it is not known.
+    pub fn pending_send(&self, account: &Account) -> u128 {
+        if self.is_confirmed(account) {
+            return 0;
+        }
+        self.account_frontier(account)
+            .and_then(|frontier| self.pending_sends.get(&frontier.block.hash()))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Return the hashes of these accounts' frontiers which are not known to be confirmed
+    pub fn unconfirmed_hashes(&self, accounts: &[Account]) -> Vec<[u8; 32]> {
+        accounts
+            .iter()
+            .filter(|account| !self.is_confirmed(account))
+            .filter_map(|account| self.account_frontier(account))
+            .map(|frontier| frontier.block.hash())
+            .collect()
+    }
+
     /// Set the cached work for an account's frontier.
-    /// Returns `Err` if the action was not successful.
+    /// Returns `Err` if the action was not successful, or `Ok(false)` if the work was invalid.
     pub fn set_account_work(
         &mut self,
         config: &CoreClientConfig,
         account: &Account,
         work: [u8; 8],
-    ) -> Result<(), CoreClientError> {
+    ) -> Result<bool, CoreClientError> {
         if let Some(info) = self.account_frontier_mut(account) {
-            info.cache_work(config, work);
-            Ok(())
+            Ok(info.cache_work(config, work))
         } else {
             Err(CoreClientError::AccountNotFound)
         }
     }
 
     /// Set the cached work for an account's frontier.
-    /// Returns `Err` if the action was not successful.
+    /// Returns `Err` if the action was not successful, or `Ok(false)` if the work was invalid.
     pub fn add_work(
         &mut self,
         config: &CoreClientConfig,
         work_hash: [u8; 32],
         work: [u8; 8],
-    ) -> Result<(), CoreClientError> {
+    ) -> Result<bool, CoreClientError> {
         if let Some(info) = self.get_work_hash_mut(work_hash) {
-            info.cache_work(config, work);
+            Ok(info.cache_work(config, work))
+        } else {
+            Err(CoreClientError::AccountNotFound)
+        }
+    }
+
+    /// Clear the cached work for a work hash.
+    /// Returns `Err` if the action was not successful.
+    pub fn clear_work_hash(&mut self, work_hash: [u8; 32]) -> Result<(), CoreClientError> {
+        if let Some(info) = self.get_work_hash_mut(work_hash) {
+            info.clear_work();
             Ok(())
         } else {
             Err(CoreClientError::AccountNotFound)
         }
     }
 
+    /// Clear any cached work which does not meet the current work difficulty.
+    /// Returns the number of work caches cleared.
+    pub fn clear_invalid_work(&mut self, config: &CoreClientConfig) -> usize {
+        let mut cleared = 0;
+        for frontier in &mut self.frontiers {
+            if frontier.cached_work.is_some() && !frontier.has_valid_work(config) {
+                frontier.clear_work();
+                cleared += 1;
+            }
+        }
+        cleared
+    }
+
     /// Return work hashes which do not have cached work.
     pub fn needs_work(&self) -> Vec<[u8; 32]> {
         self.frontiers
@@ -395,10 +533,41 @@ impl FrontiersDB {
     }
 }
 
+/// `FrontiersDB` as it is serialized, without its indexes
+#[derive(Serialize, Deserialize)]
+struct FrontiersDBSerde {
+    frontiers: Vec<FrontierInfo>,
+    frontiers_balance: u128,
+}
+impl From<FrontiersDBSerde> for FrontiersDB {
+    fn from(value: FrontiersDBSerde) -> Self {
+        let mut db = FrontiersDB {
+            frontiers: value.frontiers,
+            frontiers_balance: value.frontiers_balance,
+            accounts: HashMap::new(),
+            hashes: HashMap::new(),
+            confirmed: HashSet::new(),
+            pending_sends: HashMap::new(),
+        };
+        db._reindex(0);
+        db
+    }
+}
+impl From<FrontiersDB> for FrontiersDBSerde {
+    fn from(value: FrontiersDB) -> Self {
+        FrontiersDBSerde {
+            frontiers: value.frontiers.clone(),
+            frontiers_balance: value.frontiers_balance,
+        }
+    }
+}
+
 #[cfg(test)]
+#[allow(clippy::useless_vec)]
 mod tests {
     use super::*;
-    use nanopyrs::Key;
+    use nanopyrs::{block::get_local_work, Key};
+    use std::slice;
 
     fn fake_account_1() -> Account {
         "nano_3t6k35gi95xu6tergt6p69ck76ogmitsa8mnijtpxm9fkcm736xtoncuohr3"
@@ -486,6 +655,27 @@ mod tests {
         assert!(!db.all_accounts().contains(&fake_account_3()));
     }
 
+    #[test]
+    fn take_unrequested() {
+        let mut new_frontiers = NewFrontiers {
+            new: vec![
+                FrontierInfo::new_unopened(fake_account_1()),
+                FrontierInfo::new_unopened(fake_account_2()),
+                FrontierInfo::new_unopened(fake_account_3()),
+            ],
+        };
+        let requested = [fake_account_1(), fake_account_3()];
+
+        let unrequested = new_frontiers.take_unrequested(&requested);
+        assert!(unrequested.len() == 1);
+        assert!(unrequested[0].block.account == fake_account_2());
+        assert!(new_frontiers.new.len() == 2);
+        assert!(new_frontiers
+            .new
+            .iter()
+            .all(|frontier| requested.contains(&frontier.block.account)));
+    }
+
     #[test]
     fn insert() {
         let mut db = fake_db().unwrap();
@@ -510,14 +700,162 @@ mod tests {
         assert!(db.account_balance(&account_2) == Some(50));
     }
 
+    #[test]
+    fn indexes() {
+        let mut db = fake_db().unwrap();
+        let key = Key::from_seed(&[9; 32].into(), 9);
+        let frontier = fake_frontier(&key, fake_account_2(), 100);
+        let hash = frontier.block.hash();
+        db.insert(NewFrontiers::from(vec![frontier])).unwrap();
+
+        // removal shifts the indexes of later frontiers
+        db.remove(&fake_account_1()).unwrap();
+        assert!(db.account_frontier(&fake_account_1()).is_none());
+        assert!(db.account_balance(&fake_account_3()) == Some(10));
+        assert!(db.get_hash(hash).unwrap().block.account == key.to_account());
+
+        // indexes are rebuilt when deserializing
+        let json = serde_json::to_string(&db).unwrap();
+        let db: FrontiersDB = serde_json::from_str(&json).unwrap();
+        assert!(db.account_balance(&fake_account_2()) == Some(5));
+        assert!(db.account_balance(&key.to_account()) == Some(100));
+        assert!(db.get_hash(hash).is_some());
+    }
+
+    #[test]
+    fn confirmed() {
+        let mut db = fake_db().unwrap();
+        let key = Key::from_seed(&[9; 32].into(), 9);
+        let account = key.to_account();
+        let frontier = fake_frontier(&key, fake_account_2(), 100);
+        let hash = frontier.block.hash();
+        db.insert(NewFrontiers::from(vec![frontier])).unwrap();
+
+        assert!(!db.is_confirmed(&account));
+        assert!(db.unconfirmed_hashes(slice::from_ref(&account)) == vec![hash]);
+        db.set_confirmed(&[hash, [1; 32]]);
+        assert!(db.is_confirmed(&account));
+        assert!(db.unconfirmed_hashes(slice::from_ref(&account)).is_empty());
+        assert!(!db.confirmed.contains(&[1; 32]));
+
+        // a new frontier must be confirmed again
+        let frontier = fake_frontier(&key, fake_account_3(), 100);
+        db.insert(NewFrontiers::from(vec![frontier])).unwrap();
+        assert!(!db.is_confirmed(&account));
+        assert!(!db.confirmed.contains(&hash));
+
+        let unopened = Key::from_seed(&[9; 32].into(), 10).to_account();
+        assert!(!db.is_confirmed(&unopened));
+        db.insert(NewFrontiers::from(vec![FrontierInfo::new_unopened(
+            unopened.clone(),
+        )]))
+        .unwrap();
+        assert!(db.is_confirmed(&unopened));
+    }
+
+    #[test]
+    fn pending_send() {
+        let mut db = fake_db().unwrap();
+        let key = Key::from_seed(&[9; 32].into(), 9);
+        let account = key.to_account();
+        let opened = fake_frontier(&key, fake_account_2(), 100);
+        let opened_hash = opened.block.hash();
+        db.insert(NewFrontiers::from(vec![opened])).unwrap();
+        db.set_confirmed(&[opened_hash]);
+        assert!(db.pending_send(&account) == 0);
+
+        // two unconfirmed sends, then an unconfirmed receive
+        let mut previous = opened_hash;
+        for (block_type, balance, pending) in [
+            (BlockType::Send, 70, 30),
+            (BlockType::Send, 60, 40),
+            (BlockType::Receive, 65, 40),
+        ] {
+            let mut frontier = fake_frontier(&key, fake_account_2(), balance);
+            frontier.block.block_type = block_type;
+            frontier.block.previous = previous;
+            previous = frontier.block.hash();
+            db.insert(NewFrontiers::from(vec![frontier])).unwrap();
+            assert!(db.pending_send(&account) == pending);
+        }
+        db.set_confirmed(&[previous]);
+        assert!(db.pending_send(&account) == 0);
+
+        // the amount is unknown if the previous frontier is not the block before
+        let mut frontier = fake_frontier(&key, fake_account_2(), 10);
+        frontier.block.block_type = BlockType::Send;
+        db.insert(NewFrontiers::from(vec![frontier])).unwrap();
+        assert!(db.pending_send(&account) == 0);
+    }
+
     #[test]
     fn set_work() {
         let config = CoreClientConfig::test_default();
         let mut db = fake_db().unwrap();
 
-        db.set_account_work(&config, &fake_account_1(), [7; 8])
-            .unwrap();
+        assert!(db
+            .set_account_work(&config, &fake_account_1(), [7; 8])
+            .unwrap());
         let frontier = db.account_frontier(&fake_account_1()).unwrap();
         assert!(frontier.cached_work == Some([7; 8]));
     }
+
+    #[test]
+    fn set_invalid_work() {
+        let mut config = CoreClientConfig::test_default();
+        config.WORK_DIFFICULTY = u64::MAX;
+        let mut db = fake_db().unwrap();
+
+        assert!(!db
+            .set_account_work(&config, &fake_account_1(), [7; 8])
+            .unwrap());
+        assert!(db.needs_work().len() == 3);
+    }
+
+    #[test]
+    fn receive_work() {
+        let mut config = CoreClientConfig::test_default();
+        config.WORK_DIFFICULTY = u64::MAX;
+        config.WORK_DIFFICULTY_RECEIVE = 0xff00000000000000;
+        let mut db = fake_db().unwrap();
+
+        let frontier = db.account_frontier_mut(&fake_account_2()).unwrap();
+        let work = get_local_work(
+            frontier.work_hash(),
+            config.work_difficulty(&BlockType::Receive),
+        );
+        frontier.cached_work = Some(work);
+        assert!(frontier.has_valid_work_for(&config, &BlockType::Receive));
+        assert!(!frontier.has_valid_work_for(&config, &BlockType::Send));
+        assert!(!frontier.has_valid_work_for(&config, &BlockType::Change));
+        assert!(!frontier.has_valid_work(&config));
+    }
+
+    #[test]
+    fn clear_work() {
+        let mut config = CoreClientConfig::test_default();
+        let mut db = fake_db().unwrap();
+
+        db.set_account_work(&config, &fake_account_1(), [7; 8])
+            .unwrap();
+        db.set_account_work(&config, &fake_account_2(), [8; 8])
+            .unwrap();
+        assert!(db.clear_invalid_work(&config) == 0);
+
+        let work_hash = db.account_frontier(&fake_account_1()).unwrap().work_hash();
+        db.clear_work_hash(work_hash).unwrap();
+        assert!(db
+            .account_frontier(&fake_account_1())
+            .unwrap()
+            .cached_work
+            .is_none());
+
+        config.WORK_DIFFICULTY = u64::MAX;
+        assert!(db.clear_invalid_work(&config) == 1);
+        assert!(db
+            .account_frontier(&fake_account_2())
+            .unwrap()
+            .cached_work
+            .is_none());
+    }
 }
@@ -0,0 +1,135 @@
+use nanopyrs::{Account, Block};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+/// A page of a notification account's history, as returned by `account_history`
+#[derive(Debug, Clone, PartialEq, Eq, Zeroize, Serialize, Deserialize)]
+pub struct CachedHistory {
+    pub account: Account,
+    pub head: Option<[u8; 32]>,
+    pub offset: Option<usize>,
+    /// Hash of the account's frontier when the history was downloaded, if known
+    pub frontier: Option<[u8; 32]>,
+    pub blocks: Vec<Block>,
+}
+impl CachedHistory {
+    fn same_page(&self, account: &Account, head: Option<[u8; 32]>, offset: Option<usize>) -> bool {
+        &self.account == account && self.head == head && self.offset == offset
+    }
+}
+
+/// Account histories downloaded while rescanning, so that repeated rescans do not need to re-download them.
+///
+/// Entries are keyed by `(account, head, offset)`.
+/// If the account's frontier changes, all of its entries are considered stale.
+#[derive(Debug, Clone, Default, Zeroize, Serialize, Deserialize)]
+pub struct HistoryCache {
+    entries: Vec<CachedHistory>,
+}
+impl HistoryCache {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Get a cached page of history, unless the account's frontier has changed since it was cached
+    pub fn get(
+        &self,
+        account: &Account,
+        head: Option<[u8; 32]>,
+        offset: Option<usize>,
+        frontier: Option<[u8; 32]>,
+    ) -> Option<&CachedHistory> {
+        self.entries
+            .iter()
+            .find(|entry| entry.same_page(account, head, offset) && entry.frontier == frontier)
+    }
+
+    /// Insert a page of history, replacing any previous entry for the same page.
+    /// Stale entries for the same account (cached under a different frontier) are removed.
+    pub fn insert(&mut self, history: CachedHistory) {
+        self.entries.retain(|entry| {
+            entry.account != history.account
+                || (entry.frontier == history.frontier
+                    && !entry.same_page(&history.account, history.head, history.offset))
+        });
+        self.entries.push(history);
+    }
+
+    /// Remove all cached history for the given account
+    pub fn remove_account(&mut self, account: &Account) {
+        self.entries.retain(|entry| &entry.account != account)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nanopyrs::{BlockType, Key};
+
+    fn account(n: u8) -> Account {
+        Key::from([n; 32]).to_account()
+    }
+
+    fn history(n: u8, head: u8, frontier: u8) -> CachedHistory {
+        let block = Block {
+            block_type: BlockType::Send,
+            account: account(n),
+            previous: [head; 32],
+            representative: account(n),
+            balance: 0,
+            link: [0; 32],
+            signature: Default::default(),
+            work: [0; 8],
+        };
+        CachedHistory {
+            account: account(n),
+            head: Some([head; 32]),
+            offset: None,
+            frontier: Some([frontier; 32]),
+            blocks: vec![block],
+        }
+    }
+
+    #[test]
+    fn invalidation() {
+        let mut cache = HistoryCache::default();
+        cache.insert(history(1, 1, 1));
+        cache.insert(history(1, 2, 1));
+        cache.insert(history(2, 1, 1));
+        assert_eq!(cache.len(), 3);
+
+        let found = cache.get(&account(1), Some([2; 32]), None, Some([1; 32]));
+        assert_eq!(found, Some(&history(1, 2, 1)));
+        assert!(cache
+            .get(&account(1), Some([2; 32]), Some(1), Some([1; 32]))
+            .is_none());
+        // the frontier changed
+        assert!(cache
+            .get(&account(1), Some([2; 32]), None, Some([3; 32]))
+            .is_none());
+
+        // replaces the same page
+        cache.insert(history(1, 2, 1));
+        assert_eq!(cache.len(), 3);
+        // drops stale entries for the same account only
+        cache.insert(history(1, 1, 3));
+        assert_eq!(cache.len(), 2);
+        assert!(cache
+            .get(&account(1), Some([2; 32]), None, Some([1; 32]))
+            .is_none());
+        assert!(cache
+            .get(&account(2), Some([1; 32]), None, Some([1; 32]))
+            .is_some());
+
+        cache.remove_account(&account(2));
+        assert_eq!(cache.len(), 1);
+    }
+}
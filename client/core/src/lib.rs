@@ -6,11 +6,15 @@ mod error;
 
 pub mod constants;
 pub mod frontiers;
+pub mod history;
+pub mod price;
 pub mod rpc;
 pub mod wallet;
 
-pub use client::{CamoPayment, CoreClient, Payment, RescanData};
-pub use config::CoreClientConfig;
+pub use client::{CamoPayment, CoreClient, Payment, ReceivableData, RescanData};
+pub use config::{
+    AccountSelector, CoreClientConfig, IndexRange, ReceiveOrder, RepresentativeStrategy, RpcPin,
+};
 pub use error::CoreClientError;
 pub use nanopyrs::{
     self,
@@ -0,0 +1,172 @@
+use crate::error::CoreClientError;
+use crate::rpc::get_current_time;
+use crate::CoreClientConfig;
+use log::debug;
+use reqwest::{Client as HttpClient, Proxy};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+
+/// How long to wait for the price API, so that it never holds up a command for long
+const PRICE_REQUEST_TIMEOUT_SECONDS: u64 = 5;
+
+fn price_error(message: impl Into<String>) -> CoreClientError {
+    CoreClientError::PriceError(message.into())
+}
+
+/// A source of the price of one Nano in other currencies
+pub trait PriceSource {
+    /// Get the price of one Nano in `currency` (e.g. `usd`)
+    fn price(&self, currency: &str) -> impl Future<Output = Result<f64, CoreClientError>> + Send;
+}
+
+/// Parse a response in the format of CoinGecko's `simple/price` API, e.g. `{"nano": {"usd": 0.9}}`
+fn parse_price(json: &JsonValue, currency: &str) -> Result<f64, CoreClientError> {
+    json["nano"][currency]
+        .as_f64()
+        .filter(|price| price.is_finite() && *price >= 0.0)
+        .ok_or_else(|| price_error(format!("no valid price for '{currency}' in the response")))
+}
+
+/// Downloads prices from `PRICE_API_URL`, through `GLOBAL_PROXY` if set
+#[derive(Debug, Clone)]
+pub struct HttpPriceSource {
+    url: String,
+    proxy: Option<String>,
+}
+impl HttpPriceSource {
+    pub fn from_config(config: &CoreClientConfig) -> HttpPriceSource {
+        HttpPriceSource {
+            url: config.PRICE_API_URL.clone(),
+            proxy: config.GLOBAL_PROXY.clone(),
+        }
+    }
+
+    fn http_client(&self) -> Result<HttpClient, CoreClientError> {
+        let mut builder =
+            HttpClient::builder().timeout(Duration::from_secs(PRICE_REQUEST_TIMEOUT_SECONDS));
+        if let Some(proxy) = &self.proxy {
+            // never bypass the proxy: if it cannot be used, neither can the price API
+            let proxy = Proxy::all(proxy).map_err(|err| price_error(err.to_string()))?;
+            builder = builder.proxy(proxy);
+        }
+        builder.build().map_err(|err| price_error(err.to_string()))
+    }
+}
+impl PriceSource for HttpPriceSource {
+    async fn price(&self, currency: &str) -> Result<f64, CoreClientError> {
+        if self.url.is_empty() {
+            return Err(price_error("no price API is configured"));
+        }
+        let url = self.url.replace("{currency}", currency);
+        let response = self
+            .http_client()?
+            .get(url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| price_error(err.to_string()))?;
+        let json: JsonValue = response
+            .json()
+            .await
+            .map_err(|err| price_error(err.to_string()))?;
+        parse_price(&json, currency)
+    }
+}
+
+/// Prices downloaded from a `PriceSource`, each kept for `PRICE_CACHE_SECONDS`
+#[derive(Debug, Clone, Default)]
+pub struct PriceCache {
+    /// Currency => (price, when it was downloaded as a UNIX timestamp)
+    prices: HashMap<String, (f64, u64)>,
+}
+impl PriceCache {
+    /// Get the price of one Nano in `currency`, downloading it if it is not cached (or has expired).
+    ///
+    /// Returns `None` if the price could not be downloaded.
+    pub async fn get(
+        &mut self,
+        config: &CoreClientConfig,
+        source: &impl PriceSource,
+        currency: &str,
+    ) -> Option<f64> {
+        let currency = currency.to_lowercase();
+        let now = get_current_time();
+        if let Some((price, downloaded)) = self.prices.get(&currency) {
+            if now < downloaded.saturating_add(config.PRICE_CACHE_SECONDS) {
+                return Some(*price);
+            }
+        }
+
+        match source.price(&currency).await {
+            Ok(price) => {
+                self.prices.insert(currency, (price, now));
+                Some(price)
+            }
+            Err(err) => {
+                debug!("Failed to download the price of Nano in '{currency}': {err}");
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Debug, Default)]
+    struct FakePriceSource {
+        requests: AtomicUsize,
+        fail: bool,
+    }
+    impl PriceSource for FakePriceSource {
+        async fn price(&self, currency: &str) -> Result<f64, CoreClientError> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            match (self.fail, currency) {
+                (false, "usd") => Ok(0.5),
+                _ => Err(price_error("unavailable")),
+            }
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let json = json!({"nano": {"usd": 0.9, "eur": -1}});
+        assert!(parse_price(&json, "usd").unwrap() == 0.9);
+        assert!(parse_price(&json, "eur").is_err());
+        assert!(parse_price(&json, "gbp").is_err());
+        assert!(parse_price(&json!({"error": "rate limited"}), "usd").is_err());
+    }
+
+    #[test]
+    fn cache() {
+        let mut config = CoreClientConfig::test_default();
+        let source = FakePriceSource::default();
+        let mut cache = PriceCache::default();
+
+        assert!(block_on(cache.get(&config, &source, "USD")) == Some(0.5));
+        assert!(block_on(cache.get(&config, &source, "usd")) == Some(0.5));
+        assert!(source.requests.load(Ordering::SeqCst) == 1);
+
+        // expired
+        config.PRICE_CACHE_SECONDS = 0;
+        assert!(block_on(cache.get(&config, &source, "usd")) == Some(0.5));
+        assert!(source.requests.load(Ordering::SeqCst) == 2);
+
+        // failures are not cached
+        assert!(block_on(cache.get(&config, &source, "eur")).is_none());
+        assert!(block_on(cache.get(&config, &source, "eur")).is_none());
+        assert!(source.requests.load(Ordering::SeqCst) == 4);
+
+        let failing = FakePriceSource {
+            fail: true,
+            ..Default::default()
+        };
+        assert!(block_on(cache.get(&config, &failing, "usd")).is_none());
+    }
+}
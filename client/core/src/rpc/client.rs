@@ -3,39 +3,231 @@ use crate::config::CoreClientConfig;
 use crate::error::CoreClientError;
 use crate::frontiers::{FrontierInfo, FrontiersDB, NewFrontiers};
 use crate::rpc::{work::WorkManager, RpcFailures, RpcResult, RpcSuccess};
-use nanopyrs::{Account, Block};
+use log::warn;
+use nanopyrs::{block::check_work, Account, Block, BlockType};
+use std::cmp::Ordering;
 use std::iter::zip;
+use std::slice;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use tokio::task::block_in_place;
+
+const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Derive the `subtype` of a state block from the change in the account's balance
+fn state_subtype(previous: &FrontierInfo, block: &Block) -> BlockType {
+    match block.balance.cmp(&previous.block.balance) {
+        Ordering::Less => BlockType::Send,
+        Ordering::Greater => BlockType::Receive,
+        Ordering::Equal => BlockType::Change,
+    }
+}
+
+/// After a node rejected a block as a fork or an old block, compare the account's frontier on the network (`head`)
+/// with the frontier which the block was built on.
+///
+/// Returns `Ok` if the block was already published, `Err(Fork)` if the cached frontier is out of date,
+/// and `Err(rejection)` otherwise.
+fn check_rejected_block(
+    previous: &FrontierInfo,
+    block: &Block,
+    head: Option<[u8; 32]>,
+    rejection: CoreClientError,
+) -> Result<(), CoreClientError> {
+    if head == Some(block.hash()) {
+        warn!("{} was already published", hex::encode_upper(block.hash()));
+        return Ok(());
+    }
+    let expected = match previous.is_unopened() {
+        true => None,
+        false => Some(previous.block.hash()),
+    };
+    if head != expected {
+        return Err(CoreClientError::Fork {
+            account: block.account.to_string(),
+            head: head.map_or("unopened".into(), hex::encode_upper),
+        });
+    }
+    Err(rejection)
+}
 
 #[derive(Debug)]
 pub struct ClientRpc();
 impl ClientRpc {
-    /// Get cached work for this frontier, either cached locally or from an RPC
+    /// Request work at the given difficulty, which is only passed on if it is not the default,
+    /// so that work already in progress at the default difficulty can be reused
+    fn request_work(
+        config: &CoreClientConfig,
+        work_client: &mut WorkManager,
+        work_hash: [u8; 32],
+        difficulty: [u8; 8],
+    ) {
+        let custom_difficulty =
+            (difficulty != config.WORK_DIFFICULTY.to_be_bytes()).then_some(difficulty);
+        work_client.request_work(config, work_hash, custom_difficulty);
+    }
+
+    /// Get work for the next blocks of several frontiers, as with `get_work()`.
+    ///
+    /// Work is requested for every frontier before waiting on any of them, so that the requests run concurrently.
+    pub fn get_work_many(
+        &self,
+        config: &CoreClientConfig,
+        work_client: &mut WorkManager,
+        frontiers: &[(&FrontierInfo, BlockType)],
+    ) -> RpcResult<Vec<[u8; 8]>> {
+        for (frontier, block_type) in frontiers {
+            let difficulty = config.work_difficulty(block_type);
+            let cached = frontier
+                .cached_work()
+                .is_some_and(|work| check_work(frontier.work_hash(), difficulty, work));
+            if !cached {
+                Self::request_work(config, work_client, frontier.work_hash(), difficulty);
+            }
+        }
+
+        let mut works = vec![];
+        let mut failures = RpcFailures::default();
+        for (frontier, block_type) in frontiers {
+            let (work, work_failures) = self
+                .get_work(config, work_client, frontier, block_type, None)?
+                .into();
+            works.push(work);
+            failures.merge_with(work_failures);
+        }
+        Ok((works, failures).into())
+    }
+
+    /// Get work for the next block of this frontier, either cached locally or from an RPC.
+    /// The work must meet the default difficulty for `block_type` (see `CoreClientConfig::work_difficulty()`).
+    ///
+    /// If `custom_difficulty` is given, it is used instead of the default difficulty.
     pub fn get_work(
         &self,
         config: &CoreClientConfig,
         work_client: &mut WorkManager,
         frontier: &FrontierInfo,
+        block_type: &BlockType,
+        custom_difficulty: Option<[u8; 8]>,
     ) -> RpcResult<[u8; 8]> {
+        let work_hash = frontier.work_hash();
+        let difficulty = custom_difficulty.unwrap_or(config.work_difficulty(block_type));
+        let meets_difficulty = |work: [u8; 8]| check_work(work_hash, difficulty, work);
+
         if let Some(work) = frontier.cached_work() {
-            return Ok((work, RpcFailures::default()).into());
+            if meets_difficulty(work) {
+                return Ok((work, RpcFailures::default()).into());
+            }
         }
 
-        let work_hash = frontier.work_hash();
-        work_client.request_work(config, work_hash);
-        work_client.wait_on(work_hash).rpc_result
+        Self::request_work(config, work_client, work_hash, difficulty);
+        let (work, mut failures) = work_client.wait_on(work_hash).rpc_result?.into();
+        if meets_difficulty(work) {
+            return Ok((work, failures).into());
+        }
+
+        // a request at the default difficulty was already in progress
+        Self::request_work(config, work_client, work_hash, difficulty);
+        let (work, failures_2) = work_client.wait_on(work_hash).rpc_result?.into();
+        failures.merge_with(failures_2);
+        Ok((work, failures).into())
     }
 
-    /// Publish a block to the network
+    /// Publish a block to the network, given the account's previous frontier.
+    ///
+    /// The block's `subtype` is checked against the change in balance, since some nodes reject mismatched subtypes.
     pub async fn publish(
         &self,
         config: &CoreClientConfig,
-        block: Block,
+        previous: &FrontierInfo,
+        mut block: Block,
     ) -> RpcResult<FrontierInfo> {
-        let (_, failures) = RpcManager().process(config, &block).await?.into();
+        if block.block_type.is_state() && !block.block_type.is_epoch() {
+            let subtype = state_subtype(previous, &block);
+            if block.block_type != subtype {
+                let block_hash = hex::encode_upper(block.hash());
+                warn!(
+                    "Correcting subtype of {block_hash} from {} to {subtype}",
+                    block.block_type
+                );
+                // the subtype is not part of the block hash, so the signature remains valid
+                block.block_type = subtype;
+            }
+        }
+
+        let failures = match RpcManager().process(config, &block).await {
+            Ok(success) => success.failures,
+            Err(err @ CoreClientError::BlockRejected(_)) => {
+                let account = slice::from_ref(&block.account);
+                let (heads, failures) = RpcManager()
+                    .accounts_frontiers(config, account)
+                    .await?
+                    .into();
+                let head = heads.into_iter().next().flatten();
+                check_rejected_block(previous, &block, head, err)?;
+                failures
+            }
+            Err(err) => return Err(err),
+        };
         let info = FrontierInfo::new(block, None);
         Ok((info, failures).into())
     }
 
+    /// Return those of the given blocks which the network reports as confirmed
+    pub async fn download_confirmed(
+        &self,
+        config: &CoreClientConfig,
+        hashes: &[[u8; 32]],
+    ) -> RpcResult<Vec<[u8; 32]>> {
+        if hashes.is_empty() {
+            return Ok((vec![], RpcFailures::default()).into());
+        }
+
+        let (infos, failures) = RpcManager().blocks_info(config, hashes).await?.into();
+        let confirmed = infos
+            .into_iter()
+            .flatten()
+            .filter(|info| info.confirmed)
+            .map(|info| info.block.hash())
+            .filter(|hash| hashes.contains(hash))
+            .collect();
+        Ok((confirmed, failures).into())
+    }
+
+    /// Poll `blocks_info` until all of the given blocks are confirmed,
+    /// or until `CONFIRMATION_TIMEOUT_SECONDS` have passed.
+    ///
+    /// Returns the hashes of the blocks which were confirmed.
+    pub async fn wait_for_confirmation(
+        &self,
+        config: &CoreClientConfig,
+        hashes: &[[u8; 32]],
+    ) -> RpcResult<Vec<[u8; 32]>> {
+        let timeout = Duration::from_secs(config.CONFIRMATION_TIMEOUT_SECONDS);
+        let start = Instant::now();
+        let mut confirmed = vec![];
+        let mut failures = RpcFailures::default();
+        loop {
+            let unconfirmed: Vec<[u8; 32]> = hashes
+                .iter()
+                .filter(|hash| !confirmed.contains(*hash))
+                .copied()
+                .collect();
+            if unconfirmed.is_empty() || start.elapsed() >= timeout {
+                return Ok((confirmed, failures).into());
+            }
+
+            let (newly_confirmed, new_failures) =
+                self.download_confirmed(config, &unconfirmed).await?.into();
+            confirmed.extend(newly_confirmed);
+            failures.merge_with(new_failures);
+
+            if !hashes.iter().all(|hash| confirmed.contains(hash)) {
+                block_in_place(|| sleep(CONFIRMATION_POLL_INTERVAL));
+            }
+        }
+    }
+
     /// Download the frontiers of the given accounts.
     pub async fn download_frontiers(
         &self,
@@ -85,6 +277,14 @@ impl ClientRpc {
         };
 
         new_frontiers.merge_with(frontiers.into());
+        // a buggy or malicious node could return frontiers of accounts which were never requested
+        for frontier in new_frontiers.take_unrequested(accounts) {
+            warn!(
+                "Discarding frontier {} of unrequested account {}",
+                hex::encode_upper(frontier.block.hash()),
+                frontier.block.account
+            );
+        }
         frontiers_db.check_new(&new_frontiers)?;
 
         Ok(RpcSuccess {
@@ -103,14 +303,23 @@ impl ClientRpc {
         work_client: &mut WorkManager,
         frontier: &FrontierInfo,
         mut block: Block,
+        custom_difficulty: Option<[u8; 8]>,
     ) -> RpcResult<FrontierInfo> {
         let mut failures = RpcFailures::default();
 
-        let (work, failures_work) = self.get_work(config, work_client, frontier)?.into();
+        let (work, failures_work) = self
+            .get_work(
+                config,
+                work_client,
+                frontier,
+                &block.block_type,
+                custom_difficulty,
+            )?
+            .into();
         block.work = work;
         failures.merge_with(failures_work);
 
-        let (info, failures_publish) = self.publish(config, block).await?.into();
+        let (info, failures_publish) = self.publish(config, frontier, block).await?.into();
         failures.merge_with(failures_publish);
 
         Ok((info, failures).into())
@@ -124,11 +333,12 @@ impl ClientRpc {
         work_client: &mut WorkManager,
         frontiers_db: &FrontiersDB,
         block: Block,
+        custom_difficulty: Option<[u8; 8]>,
     ) -> RpcResult<FrontierInfo> {
         let frontier = frontiers_db
             .account_frontier(&block.account)
             .ok_or(CoreClientError::AccountNotFound)?;
-        self.get_work_and_publish_unsynced(config, work_client, frontier, block)
+        self.get_work_and_publish_unsynced(config, work_client, frontier, block, custom_difficulty)
             .await
     }
 
@@ -142,15 +352,16 @@ impl ClientRpc {
         work_client: &mut WorkManager,
         frontier: &FrontierInfo,
         block: Block,
+        custom_difficulty: Option<[u8; 8]>,
     ) -> RpcResult<FrontierInfo> {
         let block_hash = block.hash();
         let result = self
-            .get_work_and_publish_unsynced(config, work_client, frontier, block)
+            .get_work_and_publish_unsynced(config, work_client, frontier, block, custom_difficulty)
             .await;
 
         // Cache work for next block
         if config.ENABLE_WORK_CACHE {
-            work_client.request_work(config, block_hash);
+            work_client.request_work(config, block_hash, None);
         }
         result
     }
@@ -163,11 +374,12 @@ impl ClientRpc {
         work_client: &mut WorkManager,
         frontiers_db: &FrontiersDB,
         block: Block,
+        custom_difficulty: Option<[u8; 8]>,
     ) -> RpcResult<FrontierInfo> {
         let frontier = frontiers_db
             .account_frontier(&block.account)
             .ok_or(CoreClientError::AccountNotFound)?;
-        self.auto_publish_unsynced(config, work_client, frontier, block)
+        self.auto_publish_unsynced(config, work_client, frontier, block, custom_difficulty)
             .await
     }
 
@@ -176,3 +388,54 @@ impl ClientRpc {
         RpcManager().handle_failures(config, failures)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nanopyrs::{Key, Signature};
+
+    fn block(previous: [u8; 32], balance: u128) -> Block {
+        let account = Key::from([1; 32]).to_account();
+        Block {
+            block_type: BlockType::Send,
+            account: account.clone(),
+            previous,
+            representative: account,
+            balance,
+            link: [0; 32],
+            signature: Signature::default(),
+            work: [0; 8],
+        }
+    }
+
+    #[test]
+    fn fork() {
+        let previous = FrontierInfo::new(block([1; 32], 10), None);
+        let send = block(previous.block.hash(), 5);
+        let rejection = || CoreClientError::BlockRejected("Fork".into());
+
+        // the account was spent elsewhere, so `previous` is no longer its frontier
+        let head = block(previous.block.hash(), 7).hash();
+        let result = check_rejected_block(&previous, &send, Some(head), rejection());
+        assert!(matches!(
+            result,
+            Err(CoreClientError::Fork { head: found, .. }) if found == hex::encode_upper(head)
+        ));
+
+        // already published
+        let result = check_rejected_block(&previous, &send, Some(send.hash()), rejection());
+        assert!(result.is_ok());
+
+        // the frontier is up to date, so the rejection is not explained by a fork
+        let result =
+            check_rejected_block(&previous, &send, Some(previous.block.hash()), rejection());
+        assert!(matches!(result, Err(CoreClientError::BlockRejected(_))));
+
+        // unopened
+        let unopened = FrontierInfo::new_unopened(send.account.clone());
+        let result = check_rejected_block(&unopened, &send, None, rejection());
+        assert!(matches!(result, Err(CoreClientError::BlockRejected(_))));
+        let result = check_rejected_block(&unopened, &send, Some(head), rejection());
+        assert!(matches!(result, Err(CoreClientError::Fork { .. })));
+    }
+}
@@ -0,0 +1,174 @@
+use super::request_log;
+use super::wrapped::{Rpc, RpcCommands};
+use crate::config::CoreClientConfig;
+use futures::future::join_all;
+use nanopyrs::constants::get_genesis_account;
+use nanopyrs::rpc::debug::Response;
+use std::time::{Duration, Instant};
+
+/// Result of a single probe request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeResult {
+    pub command: &'static str,
+    pub latency: Duration,
+    /// `None` if the request was successful
+    pub error: Option<String>,
+}
+impl ProbeResult {
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Results of probing a single RPC
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcHealth {
+    pub url: String,
+    pub probes: Vec<ProbeResult>,
+}
+impl RpcHealth {
+    pub fn is_healthy(&self) -> bool {
+        self.probes.iter().all(|probe| probe.is_success())
+    }
+
+    /// Average latency of the successful probes, if any
+    pub fn average_latency(&self) -> Option<Duration> {
+        let latencies: Vec<Duration> = self
+            .probes
+            .iter()
+            .filter(|probe| probe.is_success())
+            .map(|probe| probe.latency)
+            .collect();
+        let count = u32::try_from(latencies.len())
+            .ok()
+            .filter(|count| *count > 0)?;
+        Some(latencies.iter().sum::<Duration>() / count)
+    }
+
+    /// Returns `commands`, with any commands which failed their probe disabled
+    pub fn corrected_commands(&self, commands: &RpcCommands) -> RpcCommands {
+        let mut commands = commands.clone();
+        for probe in self.probes.iter().filter(|probe| !probe.is_success()) {
+            match probe.command {
+                "account_info" => commands.account_info = false,
+                "account_history" => commands.account_history = false,
+                "block_info" => commands.block_info = false,
+                _ => panic!("broken RPC health code: unknown probe '{}'", probe.command),
+            }
+        }
+        commands
+    }
+}
+
+/// Record the probe in the RPC log, and return its result
+fn probe_result<T>(
+    config: &CoreClientConfig,
+    command: &'static str,
+    url: &str,
+    start: Instant,
+    response: &Response<Option<T>>,
+) -> ProbeResult {
+    let latency = start.elapsed();
+    let error = match &response.result {
+        Ok(Some(_)) => None,
+        Ok(None) => Some("the genesis account could not be found".into()),
+        Err(err) => Some(err.to_string()),
+    };
+    request_log::record(
+        request_log::new_entry(command, url, latency, error.clone()),
+        config.RPC_LOG_SIZE,
+    );
+    ProbeResult {
+        command,
+        latency,
+        error,
+    }
+}
+
+/// Probe the commands which this RPC claims to support, out of
+/// `account_info`, `account_history` and `block_info`, using the genesis account
+async fn probe(config: &CoreClientConfig, rpc: &Rpc) -> RpcHealth {
+    let url = rpc.get_url();
+    let genesis = get_genesis_account();
+    let mut probes = vec![];
+    let mut block_hash = None;
+
+    if rpc.commands.account_info {
+        let start = Instant::now();
+        let response = rpc.rpc.account_info(&genesis).await;
+        probes.push(probe_result(config, "account_info", url, start, &response));
+        if let Ok(Some(info)) = response.result {
+            block_hash = Some(info.frontier);
+        }
+    }
+
+    if rpc.commands.account_history {
+        let start = Instant::now();
+        let response = rpc.rpc.account_history(&genesis, 1, None, None).await;
+        let response = Response {
+            raw_request: response.raw_request,
+            raw_response: response.raw_response,
+            result: response.result.map(|blocks| blocks.into_iter().next()),
+        };
+        probes.push(probe_result(
+            config,
+            "account_history",
+            url,
+            start,
+            &response,
+        ));
+        if let Ok(Some(block)) = response.result {
+            block_hash = block_hash.or(Some(block.hash()));
+        }
+    }
+
+    // a block hash is needed to probe `block_info`
+    if let (true, Some(block_hash)) = (rpc.commands.block_info, block_hash) {
+        let start = Instant::now();
+        let response = rpc.rpc.block_info(block_hash).await;
+        probes.push(probe_result(config, "block_info", url, start, &response));
+    }
+
+    RpcHealth {
+        url: url.to_owned(),
+        probes,
+    }
+}
+
+/// Probe every RPC in `config.RPCS` concurrently, regardless of whether or not it is banned
+pub(super) async fn probe_all(config: &CoreClientConfig) -> Vec<RpcHealth> {
+    join_all(config.RPCS.iter().map(|rpc| probe(config, rpc))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe(command: &'static str, error: Option<&str>) -> ProbeResult {
+        ProbeResult {
+            command,
+            latency: Duration::from_millis(100),
+            error: error.map(|err| err.into()),
+        }
+    }
+
+    #[test]
+    fn corrected_commands() {
+        let health = RpcHealth {
+            url: "https://example.com".into(),
+            probes: vec![
+                probe("account_info", None),
+                probe("account_history", Some("invalid data")),
+                probe("block_info", None),
+            ],
+        };
+        assert!(!health.is_healthy());
+        assert!(health.average_latency() == Some(Duration::from_millis(100)));
+
+        let commands = health.corrected_commands(&RpcCommands::all());
+        assert!(commands.account_info);
+        assert!(!commands.account_history);
+        assert!(commands.block_info);
+        assert!(commands.process);
+    }
+}
@@ -1,11 +1,18 @@
-use super::{get_current_time, wrapped::Rpc, RpcFailure, RpcFailures, RpcResult, RpcSuccess};
+use super::health::{self, RpcHealth};
+use super::request_log::{self, RpcLogEntry};
+use super::retry_budget;
+use super::wrapped::{DebugRpcExt, NetworkDifficulty, Rpc};
+use super::{get_current_time, RpcFailure, RpcFailures, RpcResult, RpcSuccess};
 use crate::config::CoreClientConfig;
 use crate::error::CoreClientError;
 use log::{trace, warn};
-use nanopyrs::rpc::{AccountInfo, BlockInfo, Receivable};
+use nanopyrs::rpc::{AccountInfo, BlockInfo, Receivable, RpcError};
 use nanopyrs::{Account, Block};
 use rand::prelude::{thread_rng, SliceRandom};
+use serde_json::Value as JsonValue;
 use std::fmt::Debug;
+use std::future::Future;
+use std::time::{Duration, Instant};
 
 macro_rules! wrap_rpc_methods {
     ( $($func:ident(&self, config: &ClientConfig, $($arg:ident: $type:ty),*) -> $return: ty)* ) => {
@@ -13,13 +20,24 @@ macro_rules! wrap_rpc_methods {
             #[doc = concat!("See `nanopyrs::rpc::Rpc::", stringify!($func), "()` for documentation")]
             pub async fn $func(&self, config: &CoreClientConfig, $($arg: $type),*) -> $return {
                 let command = stringify!($func);
-                for _ in 0..config.RPC_RETRY_LIMIT {
+                let mut recent_failures = RecentFailures::new(config);
+                for attempt in 0..config.RPC_RETRY_LIMIT {
+                    if attempt > 0 && !retry_budget::take() {
+                        return Err(CoreClientError::RpcRetryBudgetExhausted)
+                    }
                     let mut failures = vec!();
-                    for w_rpc in self.get_usable_rpcs(config, command)? {
+                    let mut source_missing = false;
+                    for w_rpc in recent_failures.deprioritize(self.get_usable_rpcs(config, command)?) {
                         let url = w_rpc.get_url();
                         trace!("Making RPC request ({}) to {}", command, url);
 
+                        let start = Instant::now();
                         let response = w_rpc.rpc.$func($($arg),*).await;
+                        let error = response.result.as_ref().err().map(|err| err.to_string());
+                        request_log::record(
+                            request_log::new_entry(command, url, start.elapsed(), error),
+                            config.RPC_LOG_SIZE
+                        );
 
                         trace!("RPC request ({}) to {}: {:?}", command, url, response.raw_request);
                         trace!("RPC response ({}) from {}: {:?}", command, url, response.raw_response);
@@ -27,6 +45,11 @@ macro_rules! wrap_rpc_methods {
                         if let Err(err) = &response.result {
                             trace!("Error ({command}) from {url}: {err}");
                         }
+                        // the block itself was rejected, so other RPCs would reject it too
+                        if let Some(err) = block_rejection(command, &response.raw_response) {
+                            return Err(err)
+                        }
+                        source_missing |= is_source_missing(command, &response.raw_response);
                         // successful request (break)
                         if let Ok(item) = response.result {
                             trace!("Success ({command}) from {url}");
@@ -35,12 +58,26 @@ macro_rules! wrap_rpc_methods {
                                 failures: RpcFailures(failures)
                             })
                         }
+                        // unsuccessful request (strict mode: return immediately)
+                        if config.RPC_STRICT_MODE {
+                            return Err(CoreClientError::RpcStrictModeFailure {
+                                command: command.to_string(),
+                                url: url.to_string(),
+                                err: response.result.unwrap_err(),
+                            })
+                        }
                         // unsuccessful request (continue)
+                        recent_failures.record(url);
                         failures.push(RpcFailure{
                             err: response.result.unwrap_err(),
-                            url: w_rpc.get_url().to_string()
+                            url: w_rpc.get_url().to_string(),
+                            batch_size: None
                         });
                     }
+                    // retrying will not help if the nodes have pruned (or never had) the source block
+                    if source_missing {
+                        return Err(CoreClientError::SourceBlockMissing(RpcFailures(failures)))
+                    }
                     warn!("Failed to execute RPC command '{command}'. Trying again...")
                 }
                 // unsuccessful request (all RPC's failed)
@@ -50,17 +87,169 @@ macro_rules! wrap_rpc_methods {
     };
 }
 
+/// If `process` failed because the node already has this block, or a different block with the same `previous`,
+/// return the error to surface instead of retrying
+fn block_rejection(command: &str, raw_response: &Option<JsonValue>) -> Option<CoreClientError> {
+    if command != "process" {
+        return None;
+    }
+    let error = raw_response.as_ref()?["error"].as_str()?;
+    matches!(error, "Fork" | "Old block").then(|| CoreClientError::BlockRejected(error.into()))
+}
+
+/// Whether `process` failed because the node does not have the source block of a receive
+fn is_source_missing(command: &str, raw_response: &Option<JsonValue>) -> bool {
+    command == "process"
+        && raw_response
+            .as_ref()
+            .is_some_and(|response| response["error"] == "Gap source block")
+}
+
+/// Describe a failed batch, with its size only if the node rejected it for having too many items
+/// (so that the batch size limit is lowered, rather than the node banned)
+fn batch_failure(
+    err: RpcError,
+    url: &str,
+    raw_response: &Option<JsonValue>,
+    batch_size: usize,
+) -> RpcFailure {
+    let too_large = raw_response
+        .as_ref()
+        .and_then(|response| response["error"].as_str())
+        .is_some_and(|error| error.to_lowercase().contains("too many"));
+    RpcFailure {
+        err,
+        url: url.into(),
+        batch_size: too_large.then_some(batch_size),
+    }
+}
+
+/// RPCs which failed the current command, so that they are tried last when retrying it.
+/// Unlike bans, these are forgotten once the command finishes.
+struct RecentFailures {
+    cooldown: Duration,
+    failed: Vec<(String, Instant)>,
+}
+impl RecentFailures {
+    fn new(config: &CoreClientConfig) -> RecentFailures {
+        RecentFailures {
+            cooldown: Duration::from_secs(config.RPC_RETRY_COOLDOWN_SECONDS),
+            failed: vec![],
+        }
+    }
+
+    fn record(&mut self, url: &str) {
+        self.failed.retain(|(failed_url, _)| failed_url != url);
+        self.failed.push((url.to_string(), Instant::now()));
+    }
+
+    fn is_cooling_down(&self, url: &str) -> bool {
+        self.failed
+            .iter()
+            .any(|(failed_url, time)| failed_url == url && time.elapsed() < self.cooldown)
+    }
+
+    /// Move RPCs which failed less than `RPC_RETRY_COOLDOWN_SECONDS` ago to the end, keeping the order otherwise
+    fn deprioritize(&self, rpcs: Vec<Rpc>) -> Vec<Rpc> {
+        let (cooling_down, ready): (Vec<Rpc>, Vec<Rpc>) = rpcs
+            .into_iter()
+            .partition(|rpc| self.is_cooling_down(rpc.get_url()));
+        ready.into_iter().chain(cooling_down).collect()
+    }
+}
+
+/// Like `wrap_rpc_methods!`, but for commands taking an array of accounts or blocks.
+/// The array is split into batches of at most `Rpc::max_batch_size` items for each RPC.
+/// If any batch fails, the next RPC is tried with all of the batches.
+macro_rules! wrap_batched_rpc_methods {
+    ( $($func:ident(&self, config: &ClientConfig, $items:ident: &[$item:ty] $(, $arg:ident: $type:ty)*) -> $return: ty)* ) => {
+        $(
+            #[doc = concat!("See `nanopyrs::rpc::Rpc::", stringify!($func), "()` for documentation")]
+            pub async fn $func(&self, config: &CoreClientConfig, $items: &[$item] $(, $arg: $type)*) -> $return {
+                let command = stringify!($func);
+                let mut recent_failures = RecentFailures::new(config);
+                for attempt in 0..config.RPC_RETRY_LIMIT {
+                    if attempt > 0 && !retry_budget::take() {
+                        return Err(CoreClientError::RpcRetryBudgetExhausted)
+                    }
+                    let mut failures = vec!();
+                    'rpcs: for w_rpc in recent_failures.deprioritize(self.get_usable_rpcs(config, command)?) {
+                        let url = w_rpc.get_url();
+                        let mut items = Vec::with_capacity($items.len());
+                        for batch in $items.chunks(w_rpc.max_batch_size.max(1)) {
+                            trace!("Making RPC request ({}, {} items) to {}", command, batch.len(), url);
+
+                            let start = Instant::now();
+                            let response = w_rpc.rpc.$func(batch $(, $arg)*).await;
+                            let error = response.result.as_ref().err().map(|err| err.to_string());
+                            request_log::record(
+                                request_log::new_entry(command, url, start.elapsed(), error),
+                                config.RPC_LOG_SIZE
+                            );
+
+                            trace!("RPC request ({}) to {}: {:?}", command, url, response.raw_request);
+                            trace!("RPC response ({}) from {}: {:?}", command, url, response.raw_response);
+
+                            match response.result {
+                                Ok(batch_items) => items.extend(batch_items),
+                                Err(err) => {
+                                    trace!("Error ({command}) from {url}: {err}");
+                                    let failure = batch_failure(err, url, &response.raw_response, batch.len());
+                                    // unsuccessful request (strict mode: return immediately)
+                                    if config.RPC_STRICT_MODE {
+                                        return Err(CoreClientError::RpcStrictModeFailure {
+                                            command: command.to_string(),
+                                            url: url.to_string(),
+                                            err: failure.err,
+                                        })
+                                    }
+                                    // unsuccessful request (continue)
+                                    recent_failures.record(url);
+                                    failures.push(failure);
+                                    continue 'rpcs;
+                                }
+                            }
+                        }
+                        // successful request (every batch)
+                        trace!("Success ({command}) from {url}");
+                        return Ok(RpcSuccess{
+                            item: items,
+                            failures: RpcFailures(failures)
+                        })
+                    }
+                    warn!("Failed to execute RPC command '{command}'. Trying again...")
+                }
+                // unsuccessful request (all RPC's failed)
+                Err(CoreClientError::RpcCommandFailed)
+            }
+        )*
+    };
+}
+
+/// Requests are sent directly to each node, so refuse to make any while a proxy is configured,
+/// rather than silently bypassing it
+pub(super) fn refuse_proxies(config: &CoreClientConfig) -> Result<(), CoreClientError> {
+    let proxied =
+        config.GLOBAL_PROXY.is_some() || config.RPCS.iter().any(|rpc| rpc.get_proxy().is_some());
+    match proxied {
+        true => Err(CoreClientError::ProxyUnsupported),
+        false => Ok(()),
+    }
+}
+
 #[derive(Debug)]
 pub struct RpcManager();
 impl RpcManager {
+    /// Returns `CoreClientError::ProxyUnsupported` if `GLOBAL_PROXY` or any RPC's own proxy is set
     pub fn get_usable_rpcs(
         &self,
         config: &CoreClientConfig,
         command: &str,
     ) -> Result<Vec<Rpc>, CoreClientError> {
+        refuse_proxies(config)?;
         let current_time = get_current_time();
 
-        let mut rpcs = config.RPCS.clone();
+        let mut rpcs: Vec<Rpc> = config.RPCS.clone();
         rpcs.shuffle(&mut thread_rng());
         rpcs.sort_by_key(|rpc| {
             if rpc.is_banned(current_time) {
@@ -73,23 +262,59 @@ impl RpcManager {
         let rpcs = rpcs
             .into_iter()
             .filter(|rpc| rpc.commands.supports(command));
-        let rpcs = match config.RPC_USE_BANNED_NODES_AS_BACKUP {
+        let mut rpcs: Vec<Rpc> = match config.RPC_USE_BANNED_NODES_AS_BACKUP {
             true => rpcs.collect(),
             false => rpcs.filter(|rpc| !rpc.is_banned(current_time)).collect(),
         };
 
+        // pinned nodes are used first, or exclusively
+        if let Some(pin) = config.RPC_PINNED.iter().find(|pin| pin.command == command) {
+            match rpcs.iter().position(|rpc| rpc.get_url() == pin.url) {
+                Some(index) => {
+                    let pinned = rpcs.remove(index);
+                    rpcs.insert(0, pinned);
+                }
+                None => warn!("Pinned RPC {} is not usable for '{command}'", pin.url),
+            }
+            if pin.exclusive {
+                rpcs.retain(|rpc| rpc.get_url() == pin.url);
+            }
+        }
+
         Ok(rpcs)
     }
 
+    /// Run a command, limiting the total number of retries of all of its RPC calls
+    /// to `budget` (`RPC_COMMAND_RETRY_BUDGET`), where `0` means unlimited
+    pub async fn with_retry_budget<F: Future>(&self, budget: usize, command: F) -> F::Output {
+        retry_budget::scope(budget, command).await
+    }
+
+    /// Get the most recent RPC requests (up to `config::RPC_LOG_SIZE`), oldest first
+    pub fn log(&self) -> Vec<RpcLogEntry> {
+        request_log::entries()
+    }
+
+    /// Probe every RPC with lightweight requests, to check which commands it actually supports.
+    ///
+    /// Returns `CoreClientError::ProxyUnsupported` if `GLOBAL_PROXY` or any RPC's own proxy is set.
+    pub async fn health(
+        &self,
+        config: &CoreClientConfig,
+    ) -> Result<Vec<RpcHealth>, CoreClientError> {
+        refuse_proxies(config)?;
+        Ok(health::probe_all(config).await)
+    }
+
     pub fn handle_failures(&self, config: &mut CoreClientConfig, failures: RpcFailures) {
         let _config = config.clone();
         for failure in failures.0 {
+            // the RPC may have been removed in the meantime, or (if edited in) be duplicated
             config
                 .RPCS
                 .iter_mut()
-                .find(|w_rpc| w_rpc.get_url() == failure.url)
-                .expect("broken RpcManager code: unknown RPC URL")
-                .handle_err(&_config, &failure.err);
+                .filter(|w_rpc| w_rpc.get_url() == failure.url)
+                .for_each(|w_rpc| w_rpc.handle_err(&_config, &failure.err, failure.batch_size));
         }
     }
 
@@ -98,23 +323,28 @@ impl RpcManager {
         account_history(&self, config: &ClientConfig, account: &Account, count: usize, head: Option<[u8; 32]>, offset: Option<usize>) -> RpcResult<Vec<Block>>
         account_info(&self, config: &ClientConfig, account: &Account) -> RpcResult<Option<AccountInfo>>
         account_representative(&self, config: &ClientConfig, account: &Account) -> RpcResult<Option<Account>>
+        account_weight(&self, config: &ClientConfig, account: &Account) -> RpcResult<u128>
+        active_difficulty(&self, config: &ClientConfig, ) -> RpcResult<NetworkDifficulty>
+        block_info(&self, config: &ClientConfig, hash: [u8; 32]) -> RpcResult<Option<BlockInfo>>
+        process(&self, config: &ClientConfig, block: &Block) -> RpcResult<[u8; 32]>
+        work_generate(&self, config: &ClientConfig, hash: [u8; 32], custom_difficulty: Option<[u8; 8]>) -> RpcResult<[u8; 8]>
+    );
+
+    wrap_batched_rpc_methods!(
         accounts_balances(&self, config: &ClientConfig, accounts: &[Account]) -> RpcResult<Vec<u128>>
         accounts_frontiers(&self, config: &ClientConfig, accounts: &[Account]) -> RpcResult<Vec<Option<[u8; 32]>>>
         accounts_receivable(&self, config: &ClientConfig, accounts: &[Account], count: usize, threshold: u128) -> RpcResult<Vec<Vec<Receivable>>>
         accounts_representatives(&self, config: &ClientConfig, accounts: &[Account]) -> RpcResult<Vec<Option<Account>>>
-        block_info(&self, config: &ClientConfig, hash: [u8; 32]) -> RpcResult<Option<BlockInfo>>
         blocks_info(&self, config: &ClientConfig, hashes: &[[u8; 32]]) -> RpcResult<Vec<Option<BlockInfo>>>
-        process(&self, config: &ClientConfig, block: &Block) -> RpcResult<[u8; 32]>
-        work_generate(&self, config: &ClientConfig, hash: [u8; 32], custom_difficulty: Option<[u8; 8]>) -> RpcResult<[u8; 8]>
     );
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::CoreClientConfig;
+    use crate::config::{CoreClientConfig, RpcPin};
+    use crate::rpc::wrapped::DEFAULT_MAX_BATCH_SIZE;
     use crate::rpc::{get_current_time, Rpc, RpcCommands};
-    use nanopyrs::rpc::RpcError;
 
     fn fake_rpc(url: &str) -> Rpc {
         let commands = RpcCommands {
@@ -122,10 +352,12 @@ mod tests {
             account_history: true,
             account_info: true,
             account_representative: true,
+            account_weight: true,
             accounts_balances: true,
             accounts_frontiers: true,
             accounts_receivable: true,
             accounts_representatives: true,
+            active_difficulty: true,
             block_info: true,
             blocks_info: true,
             process: true,
@@ -138,6 +370,7 @@ mod tests {
         RpcFailures(vec![RpcFailure {
             err: RpcError::InvalidData,
             url: url.into(),
+            batch_size: None,
         }])
     }
 
@@ -151,7 +384,11 @@ mod tests {
         assert!(rpc_1.is_banned(get_current_time()));
 
         assert!(!rpc_2.is_banned(get_current_time()));
-        rpc_2.handle_err(&CoreClientConfig::test_default(), &RpcError::InvalidData);
+        rpc_2.handle_err(
+            &CoreClientConfig::test_default(),
+            &RpcError::InvalidData,
+            None,
+        );
         assert!(rpc_2.is_banned(get_current_time()));
     }
 
@@ -221,4 +458,176 @@ mod tests {
         let usable = rpcs.get_usable_rpcs(&config, "account_info").unwrap();
         assert!(usable.is_empty());
     }
+
+    #[test]
+    fn get_usable_rpcs_pinned() {
+        let mut config = CoreClientConfig::test_default();
+        config.RPCS = vec![
+            fake_rpc("https://example7.com"),
+            fake_rpc("https://example8.com"),
+            fake_rpc("https://example9.com"),
+        ];
+        config.RPC_PINNED = vec![RpcPin {
+            command: "process".into(),
+            url: "https://example8.com".into(),
+            exclusive: false,
+        }];
+        let rpcs = RpcManager();
+
+        let usable = rpcs.get_usable_rpcs(&config, "process").unwrap();
+        assert!(usable.len() == 3);
+        assert!(usable[0].get_url() == "https://example8.com");
+
+        config.RPC_PINNED[0].exclusive = true;
+        let usable = rpcs.get_usable_rpcs(&config, "process").unwrap();
+        assert!(usable.len() == 1);
+        assert!(usable[0].get_url() == "https://example8.com");
+
+        // other commands are unaffected
+        let usable = rpcs.get_usable_rpcs(&config, "account_info").unwrap();
+        assert!(usable.len() == 3);
+
+        // unknown pinned node
+        config.RPC_PINNED[0].url = "https://example10.com".into();
+        let usable = rpcs.get_usable_rpcs(&config, "process").unwrap();
+        assert!(usable.is_empty());
+    }
+
+    #[test]
+    fn block_rejections() {
+        let response = |error: &str| Some(serde_json::json!({ "error": error }));
+        assert!(matches!(
+            block_rejection("process", &response("Fork")),
+            Some(CoreClientError::BlockRejected(err)) if err == "Fork"
+        ));
+        assert!(block_rejection("process", &response("Old block")).is_some());
+        assert!(block_rejection("process", &response("Gap previous block")).is_none());
+        assert!(block_rejection("process", &None).is_none());
+        assert!(block_rejection("block_info", &response("Fork")).is_none());
+    }
+
+    #[test]
+    fn source_missing() {
+        let response = |error: &str| Some(serde_json::json!({ "error": error }));
+        assert!(is_source_missing("process", &response("Gap source block")));
+        assert!(!is_source_missing(
+            "process",
+            &response("Gap previous block")
+        ));
+        assert!(!is_source_missing("process", &None));
+        assert!(!is_source_missing(
+            "block_info",
+            &response("Gap source block")
+        ));
+    }
+
+    #[test]
+    fn get_usable_rpcs_refuses_proxies() {
+        let mut config = CoreClientConfig::test_default();
+        config.RPCS = vec![fake_rpc("https://example15.com")];
+        let rpcs = RpcManager();
+        assert!(rpcs.get_usable_rpcs(&config, "process").unwrap().len() == 1);
+
+        config.GLOBAL_PROXY = Some("socks5h://localhost:9050".into());
+        assert!(matches!(
+            rpcs.get_usable_rpcs(&config, "process"),
+            Err(CoreClientError::ProxyUnsupported)
+        ));
+
+        config.GLOBAL_PROXY = None;
+        config.RPCS.push(
+            Rpc::new(
+                RpcCommands::all(),
+                "https://example16.com",
+                Some("socks5h://localhost:1080".into()),
+            )
+            .unwrap(),
+        );
+        assert!(matches!(
+            rpcs.get_usable_rpcs(&config, "process"),
+            Err(CoreClientError::ProxyUnsupported)
+        ));
+    }
+
+    #[test]
+    fn handle_failures_duplicate_url() {
+        let mut config = CoreClientConfig::test_default();
+        config.RPCS = vec![
+            fake_rpc("https://example11.com"),
+            fake_rpc("https://example12.com"),
+            fake_rpc("https://example11.com"),
+        ];
+
+        RpcManager().handle_failures(&mut config, fake_failures("https://example11.com"));
+        let time = get_current_time();
+        assert!(config.RPCS[0].is_banned(time));
+        assert!(!config.RPCS[1].is_banned(time));
+        assert!(config.RPCS[2].is_banned(time));
+
+        // unknown URLs are ignored
+        RpcManager().handle_failures(&mut config, fake_failures("https://example13.com"));
+    }
+
+    #[test]
+    fn handle_failures_batch_size() {
+        let mut config = CoreClientConfig::test_default();
+        config.RPCS = vec![fake_rpc("https://example14.com")];
+        let too_many = Some(serde_json::json!({ "error": "Too many accounts" }));
+        let failure = batch_failure(
+            RpcError::InvalidData,
+            "https://example14.com",
+            &too_many,
+            40,
+        );
+
+        // a rejected batch lowers the limit instead of banning
+        RpcManager().handle_failures(&mut config, RpcFailures(vec![failure]));
+        assert!(config.RPCS[0].max_batch_size == 20);
+        assert!(!config.RPCS[0].is_banned(get_current_time()));
+
+        // a single item cannot be split further
+        let failure = batch_failure(RpcError::InvalidData, "https://example14.com", &too_many, 1);
+        RpcManager().handle_failures(&mut config, RpcFailures(vec![failure]));
+        assert!(config.RPCS[0].max_batch_size == 20);
+        assert!(config.RPCS[0].is_banned(get_current_time()));
+    }
+
+    #[test]
+    fn handle_failures_invalid_batch() {
+        let mut config = CoreClientConfig::test_default();
+        config.RPCS = vec![fake_rpc("https://example18.com")];
+        let invalid = Some(serde_json::json!({ "blocks": "invalid" }));
+        let failure = batch_failure(RpcError::InvalidData, "https://example18.com", &invalid, 40);
+        assert!(failure.batch_size.is_none());
+
+        // invalid data is not a sign of a batch which is too large
+        RpcManager().handle_failures(&mut config, RpcFailures(vec![failure]));
+        assert!(config.RPCS[0].max_batch_size == DEFAULT_MAX_BATCH_SIZE);
+        assert!(config.RPCS[0].is_banned(get_current_time()));
+    }
+
+    #[test]
+    fn recent_failures() {
+        let mut config = CoreClientConfig::test_default();
+        config.RPC_RETRY_COOLDOWN_SECONDS = 1000;
+        let rpcs = vec![
+            fake_rpc("https://example15.com"),
+            fake_rpc("https://example16.com"),
+            fake_rpc("https://example17.com"),
+        ];
+
+        let mut recent = RecentFailures::new(&config);
+        recent.record("https://example15.com");
+        let rpcs = recent.deprioritize(rpcs);
+        assert!(rpcs[0].get_url() == "https://example16.com");
+        assert!(rpcs[1].get_url() == "https://example17.com");
+        assert!(rpcs[2].get_url() == "https://example15.com");
+
+        // no cooldown
+        config.RPC_RETRY_COOLDOWN_SECONDS = 0;
+        let mut recent = RecentFailures::new(&config);
+        recent.record("https://example16.com");
+        let rpcs = recent.deprioritize(rpcs);
+        assert!(rpcs[0].get_url() == "https://example16.com");
+    }
 }
@@ -1,18 +1,25 @@
 mod client;
+mod health;
 mod manager;
+mod request_log;
 mod result;
+mod retry_budget;
+mod websocket;
 mod work;
 mod wrapped;
 
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub use client::ClientRpc;
+pub use health::{ProbeResult, RpcHealth};
 pub use manager::RpcManager;
+pub use request_log::RpcLogEntry;
 pub use result::{RpcFailure, RpcFailures, RpcResult, RpcSuccess};
+pub use websocket::{WebsocketClient, WebsocketManager};
 pub use work::{WorkHandle, WorkManager, WorkResult};
-pub use wrapped::{Rpc, RpcCommands};
+pub use wrapped::{dedup_rpcs, NetworkDifficulty, Rpc, RpcCommands};
 
-pub(super) fn get_current_time() -> u64 {
+pub fn get_current_time() -> u64 {
     SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap_or(Duration::from_secs(0))
@@ -0,0 +1,53 @@
+use super::get_current_time;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+static RPC_LOG: Mutex<VecDeque<RpcLogEntry>> = Mutex::new(VecDeque::new());
+
+/// A single RPC request, as recorded by `RpcManager`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RpcLogEntry {
+    /// Time at which the response was received (UNIX timestamp, in seconds)
+    pub timestamp: u64,
+    pub command: String,
+    pub url: String,
+    pub latency: Duration,
+    /// `None` if the request was successful
+    pub error: Option<String>,
+}
+impl RpcLogEntry {
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Record an RPC request, discarding the oldest entries if the log holds more than `limit` entries
+pub(super) fn record(entry: RpcLogEntry, limit: usize) {
+    let mut log = RPC_LOG.lock().unwrap_or_else(|err| err.into_inner());
+    log.push_back(entry);
+    while log.len() > limit {
+        log.pop_front();
+    }
+}
+
+pub(super) fn new_entry(
+    command: &str,
+    url: &str,
+    latency: Duration,
+    error: Option<String>,
+) -> RpcLogEntry {
+    RpcLogEntry {
+        timestamp: get_current_time(),
+        command: command.to_owned(),
+        url: url.to_owned(),
+        latency,
+        error,
+    }
+}
+
+/// Get the recorded RPC requests, oldest first
+pub(super) fn entries() -> Vec<RpcLogEntry> {
+    let log = RPC_LOG.lock().unwrap_or_else(|err| err.into_inner());
+    log.iter().cloned().collect()
+}
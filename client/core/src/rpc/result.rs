@@ -6,6 +6,8 @@ use std::fmt::Debug;
 pub struct RpcFailure {
     pub err: RpcError,
     pub url: String,
+    /// Number of accounts or blocks in the failed request, if the node rejected it for having too many
+    pub batch_size: Option<usize>,
 }
 
 #[derive(Debug, Default)]
@@ -59,10 +61,12 @@ mod tests {
         let failure_1 = RpcFailures(vec![RpcFailure {
             err: RpcError::InvalidData,
             url: "https://example.com".into(),
+            batch_size: None,
         }]);
         let failure_2 = RpcFailures(vec![RpcFailure {
             err: RpcError::InvalidAccount,
             url: "https://example2.com".into(),
+            batch_size: None,
         }]);
         let failures = RpcFailures::merge_all(vec![failure_1, failure_2]);
 
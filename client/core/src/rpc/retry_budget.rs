@@ -0,0 +1,71 @@
+use std::cell::Cell;
+use std::future::Future;
+
+tokio::task_local! {
+    /// Retries remaining for the current command, shared by every RPC call which it makes.
+    /// Tasks spawned by the command (e.g. work generation) are not limited by it.
+    static RETRY_BUDGET: RetryBudget;
+}
+
+/// Number of retries remaining, where `None` means unlimited
+struct RetryBudget(Cell<Option<usize>>);
+impl RetryBudget {
+    /// `0` means unlimited
+    fn new(budget: usize) -> RetryBudget {
+        RetryBudget(Cell::new(Some(budget).filter(|budget| *budget != 0)))
+    }
+
+    fn take(&self) -> bool {
+        match self.0.get() {
+            None => true,
+            Some(0) => false,
+            Some(remaining) => {
+                self.0.set(Some(remaining - 1));
+                true
+            }
+        }
+    }
+}
+
+/// Run `future` with a retry budget of `budget` shared by all of its RPC calls, where `0` means unlimited
+pub(super) async fn scope<F: Future>(budget: usize, future: F) -> F::Output {
+    RETRY_BUDGET.scope(RetryBudget::new(budget), future).await
+}
+
+/// Use up one retry, returning `false` if the budget has been exhausted.
+/// Always succeeds outside of `scope`.
+pub(super) fn take() -> bool {
+    RETRY_BUDGET
+        .try_with(|budget| budget.take())
+        .unwrap_or(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    #[test]
+    fn retry_budget() {
+        let budget = RetryBudget::new(2);
+        assert!(budget.take());
+        assert!(budget.take());
+        assert!(!budget.take());
+        assert!(!budget.take());
+
+        let budget = RetryBudget::new(0);
+        for _ in 0..100 {
+            assert!(budget.take());
+        }
+    }
+
+    #[test]
+    fn retry_budget_scope() {
+        assert!(take());
+        block_on(scope(1, async {
+            assert!(take());
+            assert!(!take());
+        }));
+        assert!(take());
+    }
+}
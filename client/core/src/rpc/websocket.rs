@@ -0,0 +1,482 @@
+use crate::error::CoreClientError;
+use crate::rpc::get_current_time;
+use crate::CoreClientConfig;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use bitcoin_hashes::{sha1, Hash};
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use log::{debug, warn};
+use nanopyrs::rpc::{
+    util::{bytes_from_json, u128_from_json},
+    Receivable,
+};
+use nanopyrs::Account;
+use rand::{thread_rng, Rng};
+use serde_json::{json, Value as JsonValue};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::task::{spawn, JoinHandle};
+
+/// Minimum time between connection attempts, so that an unreachable node is not spammed
+const RECONNECT_INTERVAL_SECONDS: u64 = 30;
+/// Largest message which will be accepted from the node
+const MAX_MESSAGE_SIZE: usize = 1 << 20;
+
+/// Appended to `Sec-WebSocket-Key` to compute `Sec-WebSocket-Accept` (RFC 6455)
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+fn websocket_error(message: impl Into<String>) -> CoreClientError {
+    CoreClientError::WebsocketError(message.into())
+}
+
+/// Split a `ws://` URL into the address to connect to, the `Host` header, and the path
+fn parse_url(url: &str) -> Result<(String, String, String), CoreClientError> {
+    let rest = url
+        .strip_prefix("ws://")
+        .ok_or_else(|| websocket_error(format!("only ws:// URLs are supported: {url}")))?;
+    let (host, path) = match rest.find('/') {
+        Some(index) => (&rest[..index], &rest[index..]),
+        None => (rest, "/"),
+    };
+    if host.is_empty() {
+        return Err(websocket_error(format!("invalid URL: {url}")));
+    }
+    let has_port = host
+        .rsplit_once(':')
+        .is_some_and(|(_, port)| port.parse::<u16>().is_ok());
+    let address = match has_port {
+        true => host.to_owned(),
+        false => format!("{host}:80"),
+    };
+    Ok((address, host.to_owned(), path.to_owned()))
+}
+
+/// The `Sec-WebSocket-Accept` value expected in response to the (base64-encoded) `Sec-WebSocket-Key`
+fn accept_key(key: &str) -> String {
+    let hash = sha1::Hash::hash(format!("{key}{HANDSHAKE_GUID}").as_bytes());
+    BASE64.encode(hash.to_byte_array())
+}
+
+/// Check the node's response to the opening handshake:
+/// the status must be `101`, and `Sec-WebSocket-Accept` must match the key that was sent
+fn check_handshake(response: &str, key: &str) -> Result<(), CoreClientError> {
+    let mut lines = response.lines();
+    let status = lines.next().unwrap_or_default();
+    if status.split_whitespace().nth(1) != Some("101") {
+        return Err(websocket_error(format!("handshake failed: {status}")));
+    }
+    let accept = lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("sec-websocket-accept"))
+        .map(|(_, value)| value.trim());
+    if accept != Some(accept_key(key).as_str()) {
+        return Err(websocket_error(
+            "handshake failed: missing or invalid Sec-WebSocket-Accept",
+        ));
+    }
+    Ok(())
+}
+
+/// Encode a single (final) frame. Frames sent by a client must be masked.
+fn encode_frame(opcode: u8, payload: &[u8], mask: [u8; 4]) -> Vec<u8> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len @ 0..=125 => frame.push(0x80 | len as u8),
+        len @ 126..=0xffff => {
+            frame.push(0x80 | 126);
+            frame.extend((len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(0x80 | 127);
+            frame.extend((len as u64).to_be_bytes());
+        }
+    }
+    frame.extend(mask);
+    frame.extend(
+        payload
+            .iter()
+            .enumerate()
+            .map(|(i, byte)| byte ^ mask[i % 4]),
+    );
+    frame
+}
+
+/// Read a single frame, returning `(fin, opcode, payload)`
+async fn read_frame(
+    stream: &mut (impl AsyncRead + Unpin),
+) -> Result<(bool, u8, Vec<u8>), CoreClientError> {
+    let mut header = [0; 2];
+    stream.read_exact(&mut header).await?;
+    let fin = header[0] & 0x80 != 0;
+    let opcode = header[0] & 0x0f;
+    let masked = header[1] & 0x80 != 0;
+
+    let len = match header[1] & 0x7f {
+        126 => stream.read_u16().await? as u64,
+        127 => stream.read_u64().await?,
+        len => len as u64,
+    };
+    if len > MAX_MESSAGE_SIZE as u64 {
+        return Err(websocket_error("message too large"));
+    }
+    let mut mask = [0; 4];
+    if masked {
+        stream.read_exact(&mut mask).await?;
+    }
+    let mut payload = vec![0; len as usize];
+    stream.read_exact(&mut payload).await?;
+    if masked {
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+    }
+    Ok((fin, opcode, payload))
+}
+
+/// Parse a message from the `confirmation` topic,
+/// returning it as a `Receivable` if it is a send to one of `accounts`
+fn parse_confirmation(json: &JsonValue, accounts: &[Account]) -> Option<Receivable> {
+    if json["topic"] != "confirmation" {
+        return None;
+    }
+    let message = &json["message"];
+    let block = &message["block"];
+    if block["subtype"] != "send" {
+        return None;
+    }
+    let recipient = Account::try_from(block["link_as_account"].as_str()?).ok()?;
+    if !accounts.contains(&recipient) {
+        return None;
+    }
+    Some(Receivable {
+        recipient,
+        block_hash: bytes_from_json(&message["hash"]).ok()?,
+        amount: u128_from_json(&message["amount"]).ok()?,
+    })
+}
+
+/// A minimal websocket client for a node's confirmation stream.
+///
+/// Only `ws://` URLs are supported, and connections are made directly: never through a proxy.
+#[derive(Debug)]
+pub struct WebsocketClient {
+    stream: TcpStream,
+}
+impl WebsocketClient {
+    pub async fn connect(url: &str) -> Result<WebsocketClient, CoreClientError> {
+        let (address, host, path) = parse_url(url)?;
+        let mut stream = TcpStream::connect(address).await?;
+
+        let key = BASE64.encode(thread_rng().gen::<[u8; 16]>());
+        let request = format!(
+            "GET {path} HTTP/1.1\r\n\
+            Host: {host}\r\n\
+            Upgrade: websocket\r\n\
+            Connection: Upgrade\r\n\
+            Sec-WebSocket-Key: {key}\r\n\
+            Sec-WebSocket-Version: 13\r\n\r\n"
+        );
+        stream.write_all(request.as_bytes()).await?;
+
+        // read the response headers (the node sends nothing else until we subscribe)
+        let mut response = vec![];
+        while !response.ends_with(b"\r\n\r\n") {
+            if response.len() > MAX_MESSAGE_SIZE {
+                return Err(websocket_error("handshake response too large"));
+            }
+            response.push(stream.read_u8().await?);
+        }
+        check_handshake(&String::from_utf8_lossy(&response), &key)?;
+        Ok(WebsocketClient { stream })
+    }
+
+    async fn send(&mut self, opcode: u8, payload: &[u8]) -> Result<(), CoreClientError> {
+        let frame = encode_frame(opcode, payload, thread_rng().gen());
+        self.stream.write_all(&frame).await?;
+        Ok(())
+    }
+
+    /// Subscribe to the confirmations of blocks involving these accounts
+    pub async fn subscribe(&mut self, accounts: &[Account]) -> Result<(), CoreClientError> {
+        let accounts: Vec<String> = accounts.iter().map(|account| account.to_string()).collect();
+        let message = json!({
+            "action": "subscribe",
+            "topic": "confirmation",
+            "options": {
+                "accounts": accounts
+            }
+        });
+        self.send(OPCODE_TEXT, message.to_string().as_bytes()).await
+    }
+
+    /// Wait for the next text message, answering pings in the meantime
+    async fn next_message(&mut self) -> Result<String, CoreClientError> {
+        let mut message = vec![];
+        loop {
+            let (fin, opcode, payload) = read_frame(&mut self.stream).await?;
+            match opcode {
+                OPCODE_PING => self.send(OPCODE_PONG, &payload).await?,
+                OPCODE_PONG => (),
+                OPCODE_CLOSE => return Err(websocket_error("connection closed by the node")),
+                OPCODE_TEXT | OPCODE_BINARY | OPCODE_CONTINUATION => {
+                    message.extend(payload);
+                    if message.len() > MAX_MESSAGE_SIZE {
+                        return Err(websocket_error("message too large"));
+                    }
+                    if fin {
+                        return Ok(String::from_utf8_lossy(&message).into_owned());
+                    }
+                }
+                _ => return Err(websocket_error(format!("unknown opcode {opcode}"))),
+            }
+        }
+    }
+
+    /// Wait for the next confirmed send to one of `accounts`
+    pub async fn next_receivable(
+        &mut self,
+        accounts: &[Account],
+    ) -> Result<Receivable, CoreClientError> {
+        loop {
+            let message = self.next_message().await?;
+            let Ok(json) = serde_json::from_str::<JsonValue>(&message) else {
+                debug!("Ignoring invalid websocket message: {message}");
+                continue;
+            };
+            if let Some(receivable) = parse_confirmation(&json, accounts) {
+                return Ok(receivable);
+            }
+        }
+    }
+}
+
+async fn listen(
+    url: String,
+    accounts: Vec<Account>,
+    sender: UnboundedSender<Receivable>,
+) -> Result<(), CoreClientError> {
+    let mut client = WebsocketClient::connect(&url).await?;
+    client.subscribe(&accounts).await?;
+    debug!("Listening for receivable transactions on {url}");
+    loop {
+        let receivable = client.next_receivable(&accounts).await?;
+        // the manager was dropped
+        if sender.unbounded_send(receivable).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Listener {
+    url: String,
+    accounts: Vec<Account>,
+    handle: JoinHandle<Result<(), CoreClientError>>,
+    receiver: UnboundedReceiver<Receivable>,
+}
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.handle.abort()
+    }
+}
+
+/// Keeps a websocket connection open in the background (if `WEBSOCKET_ENABLED`),
+/// collecting sends to the wallet's accounts as they are confirmed.
+///
+/// If the connection drops, the next URL in `WEBSOCKET_URLS` is tried after a short delay.
+/// Until then, receivable transactions can still be found with `refresh`.
+#[derive(Debug, Default)]
+pub struct WebsocketManager {
+    listener: Option<Listener>,
+    /// Index into `WEBSOCKET_URLS` of the next URL to connect to
+    next_url: usize,
+    /// When the last connection attempt was made (UNIX timestamp, in seconds)
+    last_attempt: u64,
+    /// Whether the user was already warned that a proxy prevents connecting
+    warned_proxy: bool,
+}
+impl WebsocketManager {
+    /// Whether or not a connection is currently open (or being opened)
+    pub fn is_connected(&self) -> bool {
+        self.listener
+            .as_ref()
+            .is_some_and(|listener| !listener.handle.is_finished())
+    }
+
+    /// Return the receivable transactions collected since the last call, and (re)connect if necessary.
+    /// Does not wait for new transactions.
+    ///
+    /// Reconnects if `accounts` changed since the connection was opened.
+    pub async fn update(
+        &mut self,
+        config: &CoreClientConfig,
+        accounts: &[Account],
+    ) -> Vec<Receivable> {
+        let mut received = vec![];
+        if let Some(listener) = &mut self.listener {
+            while let Ok(receivable) = listener.receiver.try_recv() {
+                received.push(receivable);
+            }
+        }
+
+        // the connection cannot go through a proxy, so never open one when a proxy is configured
+        let proxied = config.GLOBAL_PROXY.is_some()
+            || config.RPCS.iter().any(|rpc| rpc.get_proxy().is_some());
+        if proxied && config.WEBSOCKET_ENABLED && !self.warned_proxy {
+            warn!("Websockets cannot be used through a proxy: 'WEBSOCKET_ENABLED' is ignored");
+            self.warned_proxy = true;
+        }
+        let enabled = config.WEBSOCKET_ENABLED && !proxied;
+
+        // stop listening if disabled, or if the accounts or URLs changed
+        let stale = self.listener.as_ref().is_some_and(|listener| {
+            !enabled
+                || listener.accounts != accounts
+                || !config.WEBSOCKET_URLS.contains(&listener.url)
+        });
+        if stale {
+            self.listener = None;
+            self.last_attempt = 0;
+        }
+
+        if let Some(listener) = &mut self.listener {
+            if listener.handle.is_finished() {
+                let err = match (&mut listener.handle).await {
+                    Ok(Ok(())) => "connection closed".into(),
+                    Ok(Err(err)) => err.to_string(),
+                    Err(err) => err.to_string(),
+                };
+                warn!(
+                    "Lost websocket connection to {}: {err}. Use 'refresh' to check for receivable transactions until it reconnects",
+                    listener.url
+                );
+                self.listener = None;
+            }
+        }
+
+        let now = get_current_time();
+        let urls = &config.WEBSOCKET_URLS;
+        let due = now >= self.last_attempt.saturating_add(RECONNECT_INTERVAL_SECONDS);
+        if self.listener.is_none() && enabled && !urls.is_empty() && !accounts.is_empty() && due {
+            self.last_attempt = now;
+            let url = urls[self.next_url % urls.len()].clone();
+            self.next_url = (self.next_url + 1) % urls.len();
+
+            let (sender, receiver) = unbounded();
+            let handle = spawn(listen(url.clone(), accounts.to_vec(), sender));
+            self.listener = Some(Listener {
+                url,
+                accounts: accounts.to_vec(),
+                handle,
+                receiver,
+            });
+        }
+        received
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use nanopyrs::Key;
+
+    #[test]
+    fn url() {
+        let parsed = parse_url("ws://localhost:7078").unwrap();
+        assert!(parsed == ("localhost:7078".into(), "localhost:7078".into(), "/".into()));
+        let parsed = parse_url("ws://example.com/websocket").unwrap();
+        assert!(
+            parsed
+                == (
+                    "example.com:80".into(),
+                    "example.com".into(),
+                    "/websocket".into()
+                )
+        );
+        assert!(parse_url("wss://example.com").is_err());
+        assert!(parse_url("ws://").is_err());
+    }
+
+    #[test]
+    fn handshake() {
+        // example from RFC 6455
+        let key = "dGhlIHNhbXBsZSBub25jZQ==";
+        assert!(accept_key(key) == "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+
+        let response = |status: &str, accept: &str| {
+            format!(
+                "HTTP/1.1 {status}\r\nUpgrade: websocket\r\nsec-websocket-accept: {accept}\r\n\r\n"
+            )
+        };
+        assert!(
+            check_handshake(&response("101 Switching Protocols", &accept_key(key)), key).is_ok()
+        );
+        assert!(check_handshake(&response("200 OK", &accept_key(key)), key).is_err());
+        assert!(check_handshake(
+            &response("101 Switching Protocols", &accept_key("other")),
+            key
+        )
+        .is_err());
+        assert!(check_handshake("HTTP/1.1 101 Switching Protocols\r\n\r\n", key).is_err());
+    }
+
+    #[test]
+    fn frames() {
+        for len in [0, 5, 125, 126, 0xffff, 0x10000] {
+            let payload: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            let frame = encode_frame(OPCODE_TEXT, &payload, [1, 2, 3, 4]);
+            let decoded = block_on(read_frame(&mut frame.as_slice())).unwrap();
+            assert!(decoded == (true, OPCODE_TEXT, payload));
+        }
+
+        // unmasked frame, as sent by the node
+        let frame = [0x09, 0x02, b'h', b'i'];
+        let decoded = block_on(read_frame(&mut frame.as_slice())).unwrap();
+        assert!(decoded == (false, OPCODE_PING, b"hi".to_vec()));
+
+        // truncated
+        assert!(block_on(read_frame(&mut [0x81, 0x05, b'h'].as_slice())).is_err());
+    }
+
+    #[test]
+    fn confirmation() {
+        let recipient = Key::from([1; 32]).to_account();
+        let accounts = [recipient.clone()];
+        let other = Key::from([2; 32]).to_account();
+        let json = |subtype: &str, account: &Account| {
+            json!({
+                "topic": "confirmation",
+                "message": {
+                    "account": other.to_string(),
+                    "amount": "1000000",
+                    "hash": hex::encode_upper([7; 32]),
+                    "block": {
+                        "type": "state",
+                        "subtype": subtype,
+                        "link_as_account": account.to_string(),
+                    }
+                }
+            })
+        };
+
+        let receivable = parse_confirmation(&json("send", &recipient), &accounts);
+        assert!(
+            receivable
+                == Some(Receivable {
+                    recipient: recipient.clone(),
+                    block_hash: [7; 32],
+                    amount: 1000000
+                })
+        );
+        assert!(parse_confirmation(&json("receive", &recipient), &accounts).is_none());
+        assert!(parse_confirmation(&json("send", &other), &accounts).is_none());
+        assert!(parse_confirmation(&json!({"ack": "subscribe"}), &accounts).is_none());
+    }
+}
@@ -1,8 +1,14 @@
-use crate::rpc::{RpcManager, RpcResult};
+use crate::error::CoreClientError;
+use crate::rpc::{RpcFailures, RpcManager, RpcResult, RpcSuccess};
 use crate::CoreClientConfig;
-use log::{debug, info};
+use futures::channel::oneshot;
+use log::{debug, info, warn};
+use nanopyrs::hashes::blake2b_work;
+use rand::{thread_rng, Rng};
 use std::collections::HashMap;
-use std::thread::sleep;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, sleep};
 use std::time::{Duration, SystemTime};
 use tokio::runtime::Handle as TokioHandle;
 use tokio::task::{block_in_place, spawn, JoinHandle};
@@ -26,31 +32,144 @@ fn resolve_handle(handle: WorkHandle, work_hash: [u8; 32]) -> WorkResult {
     }
 }
 
+/// Sets its flag when dropped, cancelling any local work which checks it
+#[derive(Debug)]
+struct CancelOnDrop(Arc<AtomicBool>);
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Search for work on `threads` threads, returning once any of them finds valid work.
+///
+/// Returns `None` if `cancelled` is set before then.
+fn solve_work(
+    work_hash: [u8; 32],
+    difficulty: [u8; 8],
+    threads: usize,
+    cancelled: &AtomicBool,
+) -> Option<[u8; 8]> {
+    let threads = threads.max(1) as u64;
+    let start: u64 = thread_rng().gen();
+    let found = AtomicBool::new(false);
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|i| {
+                let found = &found;
+                scope.spawn(move || {
+                    let mut data = [0; 40];
+                    data[8..].copy_from_slice(&work_hash);
+                    let mut nonce = start.wrapping_add(i);
+                    while !found.load(Ordering::Relaxed) && !cancelled.load(Ordering::Relaxed) {
+                        data[..8].copy_from_slice(&nonce.to_le_bytes());
+                        let mut bytes = blake2b_work(&data);
+                        bytes.reverse();
+                        if bytes >= difficulty {
+                            found.store(true, Ordering::Relaxed);
+                            return Some(nonce.to_be_bytes());
+                        }
+                        nonce = nonce.wrapping_add(threads);
+                    }
+                    None
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .filter_map(|handle| handle.join().expect("local work thread panicked"))
+            .next()
+    })
+}
+
+/// Generate work with the local CPU, without blocking the async runtime.
+///
+/// The work is generated on a detached thread, so that it does not delay shutdown.
+/// That thread stops once `cancelled` is set, or this future is dropped.
+async fn local_work(
+    config: &CoreClientConfig,
+    work_hash: [u8; 32],
+    difficulty: [u8; 8],
+    cancelled: Arc<AtomicBool>,
+) -> Result<[u8; 8], CoreClientError> {
+    let threads = config.LOCAL_WORK_THREADS;
+    let (sender, receiver) = oneshot::channel();
+    let _cancel_on_drop = CancelOnDrop(cancelled.clone());
+    thread::spawn(move || {
+        if let Some(work) = solve_work(work_hash, difficulty, threads, &cancelled) {
+            let _ = sender.send(work);
+        }
+    });
+    receiver.await.map_err(|_| CoreClientError::LocalWorkFailed)
+}
+
+/// Request work from the RPCs, falling back to local work (if `WORK_LOCAL_ENABLED`)
+/// when no RPC which supports `work_generate` is usable
+async fn generate_work(
+    config: &CoreClientConfig,
+    work_hash: [u8; 32],
+    custom_difficulty: Option<[u8; 8]>,
+    cancelled: Arc<AtomicBool>,
+) -> RpcResult<[u8; 8]> {
+    let has_rpcs = RpcManager()
+        .get_usable_rpcs(config, "work_generate")
+        .is_ok_and(|rpcs| !rpcs.is_empty());
+    if has_rpcs || !config.WORK_LOCAL_ENABLED {
+        let result = RpcManager()
+            .work_generate(config, work_hash, custom_difficulty)
+            .await;
+        if result.is_ok() || !config.WORK_LOCAL_ENABLED {
+            return result;
+        }
+        warn!("Failed to get work from any RPC: falling back to local work");
+    }
+
+    let difficulty = custom_difficulty.unwrap_or(config.WORK_DIFFICULTY.to_be_bytes());
+    let item = local_work(config, work_hash, difficulty, cancelled).await?;
+    Ok(RpcSuccess {
+        item,
+        failures: RpcFailures(vec![]),
+    })
+}
+
 #[derive(Debug, Default)]
 pub struct WorkManager {
-    handles: HashMap<[u8; 32], WorkHandle>,
+    /// Dropping a request's `CancelOnDrop` (with the `WorkManager`, or once it is removed) stops any local work for it
+    handles: HashMap<[u8; 32], (WorkHandle, CancelOnDrop)>,
+    /// Number of times invalid work was returned for each work hash
+    rejected: HashMap<[u8; 32], usize>,
 }
 impl WorkManager {
     /// Returns immediately.
     ///
     /// If already requested and in progress, the request is ignored.
-    pub fn request_work(&mut self, config: &CoreClientConfig, work_hash: [u8; 32]) {
+    ///
+    /// `custom_difficulty` overrides the default `WORK_DIFFICULTY`, if given.
+    pub fn request_work(
+        &mut self,
+        config: &CoreClientConfig,
+        work_hash: [u8; 32],
+        custom_difficulty: Option<[u8; 8]>,
+    ) {
         if self.handles.contains_key(&work_hash) {
             return;
         }
 
         let config = config.clone();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let cancel_on_drop = CancelOnDrop(cancelled.clone());
         let worker = spawn(async move {
             let as_hex = hex::encode(work_hash).to_uppercase();
             debug!("WorkManager: getting work for {as_hex}");
-            let rpc_result = RpcManager().work_generate(&config, work_hash, None).await;
+            let rpc_result = generate_work(&config, work_hash, custom_difficulty, cancelled).await;
             debug!("WorkManager: got work for {as_hex}");
             WorkResult {
                 work_hash,
                 rpc_result,
             }
         });
-        self.handles.insert(work_hash, worker);
+        self.handles.insert(work_hash, (worker, cancel_on_drop));
     }
 
     /// Wait for a work request to resolve.
@@ -60,7 +179,7 @@ impl WorkManager {
         let time = SystemTime::now();
         let mut last_log_time = 0;
 
-        let handle = self
+        let (handle, _cancel_on_drop) = self
             .handles
             .remove(&work_hash)
             .expect("Attempted to wait on work which hasn't been requested");
@@ -86,14 +205,14 @@ impl WorkManager {
     /// Return all finished requests.
     pub async fn get_results(&mut self) -> Vec<WorkResult> {
         let mut to_remove = vec![];
-        for (work_hash, handle) in self.handles.iter() {
+        for (work_hash, (handle, _)) in self.handles.iter() {
             if handle.is_finished() {
                 to_remove.push(*work_hash)
             }
         }
         let mut removed = vec![];
         for work_hash in to_remove {
-            let handle = self
+            let (handle, _) = self
                 .handles
                 .remove(&work_hash)
                 .expect("broken WorkManager::get_results() code: failed to remove handle");
@@ -102,8 +221,62 @@ impl WorkManager {
         removed
     }
 
+    /// Record that invalid work was returned for this hash.
+    /// Returns `true` if work should be requested again, as per `WORK_REGENERATION_LIMIT`.
+    pub fn reject_work(&mut self, config: &CoreClientConfig, work_hash: [u8; 32]) -> bool {
+        let rejected = self.rejected.entry(work_hash).or_default();
+        *rejected += 1;
+        *rejected <= config.WORK_REGENERATION_LIMIT
+    }
+
+    /// Whether or not work has been rejected for this hash too many times to be requested again.
+    pub fn is_exhausted(&self, config: &CoreClientConfig, work_hash: [u8; 32]) -> bool {
+        self.rejected
+            .get(&work_hash)
+            .is_some_and(|rejected| *rejected > config.WORK_REGENERATION_LIMIT)
+    }
+
     /// Returns how many requests are currently running.
     pub fn n_requests(&self) -> usize {
         self.handles.len()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nanopyrs::block::check_work;
+
+    #[test]
+    fn solve_work() {
+        let difficulty = 0xff00000000000000_u64.to_be_bytes();
+        for threads in [1, 4] {
+            let work = super::solve_work([7; 32], difficulty, threads, &AtomicBool::new(false));
+            assert!(check_work([7; 32], difficulty, work.unwrap()));
+        }
+    }
+
+    #[test]
+    fn solve_work_cancelled() {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let guard = CancelOnDrop(cancelled.clone());
+        let solver = thread::spawn(move || super::solve_work([7; 32], [0xff; 8], 2, &cancelled));
+        drop(guard);
+        assert!(solver.join().unwrap().is_none());
+    }
+
+    #[test]
+    fn reject_work() {
+        let mut config = CoreClientConfig::test_default();
+        config.WORK_REGENERATION_LIMIT = 2;
+        let mut work_client = WorkManager::default();
+
+        assert!(!work_client.is_exhausted(&config, [1; 32]));
+        assert!(work_client.reject_work(&config, [1; 32]));
+        assert!(work_client.reject_work(&config, [1; 32]));
+        assert!(!work_client.is_exhausted(&config, [1; 32]));
+        assert!(!work_client.reject_work(&config, [1; 32]));
+        assert!(work_client.is_exhausted(&config, [1; 32]));
+        assert!(!work_client.is_exhausted(&config, [2; 32]));
+    }
+}
@@ -2,28 +2,115 @@ use crate::config::CoreClientConfig;
 use crate::error::CoreClientError;
 use crate::rpc::get_ban_expiration;
 use log::debug;
-use nanopyrs::rpc::{debug::DebugRpc, RpcError};
+use nanopyrs::rpc::{
+    debug::{DebugRpc, Response},
+    util::{bytes_from_json, u128_from_json},
+    RpcError,
+};
+use nanopyrs::Account;
 use serde::{Deserialize, Serialize};
-use std::cmp::max;
+use serde_json::{Map, Value as JsonValue};
+use std::cmp::{max, min};
 use std::fmt::Debug;
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
+/// Default limit on the number of accounts or blocks in a single request, for each RPC.
+/// Conservative, since nodes cap array sizes differently.
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 100;
+
+fn default_max_batch_size() -> usize {
+    DEFAULT_MAX_BATCH_SIZE
+}
+
+/// All RPC commands used by the wallet
+const COMMANDS: [&str; 14] = [
+    "account_balance",
+    "account_history",
+    "account_info",
+    "account_representative",
+    "account_weight",
+    "accounts_balances",
+    "accounts_frontiers",
+    "accounts_receivable",
+    "accounts_representatives",
+    "active_difficulty",
+    "block_info",
+    "blocks_info",
+    "process",
+    "work_generate",
+];
+
 #[derive(Debug, Clone, PartialEq, Eq, Zeroize, ZeroizeOnDrop, Serialize, Deserialize)]
 pub struct RpcCommands {
     pub account_balance: bool,
     pub account_history: bool,
     pub account_info: bool,
     pub account_representative: bool,
+    /// Added in v0.1.2
+    #[serde(default)]
+    pub account_weight: bool,
     pub accounts_balances: bool,
     pub accounts_frontiers: bool,
     pub accounts_receivable: bool,
     pub accounts_representatives: bool,
+    /// Added in v0.1.2
+    #[serde(default)]
+    pub active_difficulty: bool,
     pub block_info: bool,
     pub blocks_info: bool,
     pub process: bool,
     pub work_generate: bool,
 }
 impl RpcCommands {
+    /// Every command enabled
+    pub fn all() -> RpcCommands {
+        RpcCommands {
+            account_balance: true,
+            account_history: true,
+            account_info: true,
+            account_representative: true,
+            account_weight: true,
+            accounts_balances: true,
+            accounts_frontiers: true,
+            accounts_receivable: true,
+            accounts_representatives: true,
+            active_difficulty: true,
+            block_info: true,
+            blocks_info: true,
+            process: true,
+            work_generate: true,
+        }
+    }
+
+    /// Names of the enabled commands
+    pub fn supported(&self) -> Vec<&'static str> {
+        COMMANDS
+            .into_iter()
+            .filter(|command| self.supports(command))
+            .collect()
+    }
+
+    /// Commands enabled in either `self` or `other`
+    pub fn union(&self, other: &RpcCommands) -> RpcCommands {
+        RpcCommands {
+            account_balance: self.account_balance || other.account_balance,
+            account_history: self.account_history || other.account_history,
+            account_info: self.account_info || other.account_info,
+            account_representative: self.account_representative || other.account_representative,
+            account_weight: self.account_weight || other.account_weight,
+            accounts_balances: self.accounts_balances || other.accounts_balances,
+            accounts_frontiers: self.accounts_frontiers || other.accounts_frontiers,
+            accounts_receivable: self.accounts_receivable || other.accounts_receivable,
+            accounts_representatives: self.accounts_representatives
+                || other.accounts_representatives,
+            active_difficulty: self.active_difficulty || other.active_difficulty,
+            block_info: self.block_info || other.block_info,
+            blocks_info: self.blocks_info || other.blocks_info,
+            process: self.process || other.process,
+            work_generate: self.work_generate || other.work_generate,
+        }
+    }
+
     /// Will panic if given an invalid command
     pub fn supports(&self, command: &str) -> bool {
         match command {
@@ -31,10 +118,12 @@ impl RpcCommands {
             "account_history" => self.account_history,
             "account_info" => self.account_info,
             "account_representative" => self.account_representative,
+            "account_weight" => self.account_weight,
             "accounts_balances" => self.accounts_balances,
             "accounts_frontiers" => self.accounts_frontiers,
             "accounts_receivable" => self.accounts_receivable,
             "accounts_representatives" => self.accounts_representatives,
+            "active_difficulty" => self.active_difficulty,
             "block_info" => self.block_info,
             "blocks_info" => self.blocks_info,
             "process" => self.process,
@@ -44,13 +133,91 @@ impl RpcCommands {
     }
 }
 
-#[derive(Debug, Clone, Zeroize, ZeroizeOnDrop)]
+/// The network's current work difficulty thresholds, as reported by `active_difficulty`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NetworkDifficulty {
+    /// Difficulty of send and change blocks
+    pub send: u64,
+    /// Difficulty of receive blocks
+    pub receive: u64,
+}
+
+fn network_difficulty_from_json(json: &JsonValue) -> Result<NetworkDifficulty, RpcError> {
+    Ok(NetworkDifficulty {
+        send: u64::from_be_bytes(bytes_from_json(&json["network_current"])?),
+        receive: u64::from_be_bytes(bytes_from_json(&json["network_receive_current"])?),
+    })
+}
+
+/// RPC methods which are not provided by `nanopyrs`
+pub(super) trait DebugRpcExt {
+    /// Get the voting weight delegated to a representative
+    async fn account_weight(&self, account: &Account) -> Response<u128>;
+    /// Get the network's current work difficulty
+    async fn active_difficulty(&self) -> Response<NetworkDifficulty>;
+}
+impl DebugRpcExt for DebugRpc {
+    async fn account_weight(&self, account: &Account) -> Response<u128> {
+        let mut arguments = Map::new();
+        arguments.insert("account".into(), account.to_string().into());
+
+        let Response {
+            raw_request,
+            raw_response,
+            result,
+        } = self.command("account_weight", arguments).await;
+        Response {
+            raw_request,
+            raw_response,
+            result: result.and_then(|json| u128_from_json(&json["weight"])),
+        }
+    }
+
+    async fn active_difficulty(&self) -> Response<NetworkDifficulty> {
+        let Response {
+            raw_request,
+            raw_response,
+            result,
+        } = self.command("active_difficulty", Map::new()).await;
+        Response {
+            raw_request,
+            raw_response,
+            result: result.and_then(|json| network_difficulty_from_json(&json)),
+        }
+    }
+}
+
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
 pub struct Rpc {
     pub commands: RpcCommands,
     pub banned_until: u64,
+    /// Maximum number of accounts or blocks in a single request (lowered if the node rejects a batch)
+    pub max_batch_size: usize,
+    /// Header name and value (e.g. an API key) to authenticate with the RPC.
+    ///
+    /// Note that `DebugRpc` does not yet allow setting request headers, so it is stored but not sent,
+    /// and the CLI does not offer to set it until it is.
+    pub auth_header: Option<(String, String)>,
     #[zeroize(skip)]
     pub rpc: DebugRpc,
 }
+impl Debug for Rpc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Rpc")
+            .field("commands", &self.commands)
+            .field("banned_until", &self.banned_until)
+            .field("max_batch_size", &self.max_batch_size)
+            .field(
+                "auth_header",
+                &self
+                    .auth_header
+                    .as_ref()
+                    .map(|(name, _)| (name, "[redacted]")),
+            )
+            .field("rpc", &self.rpc)
+            .finish()
+    }
+}
 impl Rpc {
     fn _new(
         commands: RpcCommands,
@@ -62,6 +229,8 @@ impl Rpc {
             commands,
             rpc: DebugRpc::new(url, proxy)?,
             banned_until,
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+            auth_header: None,
         })
     }
 
@@ -93,7 +262,26 @@ impl Rpc {
         &self.rpc
     }
 
-    pub(super) fn handle_err(&mut self, config: &CoreClientConfig, err: &RpcError) {
+    /// `batch_size` is the number of accounts or blocks in a batch which the node rejected for having too many.
+    /// If there was more than one, the node caps array sizes below `max_batch_size`,
+    /// so the limit is halved instead of banning it.
+    pub(super) fn handle_err(
+        &mut self,
+        config: &CoreClientConfig,
+        err: &RpcError,
+        batch_size: Option<usize>,
+    ) {
+        if let Some(batch_size) = batch_size.filter(|size| *size > 1) {
+            self.max_batch_size = max(min(self.max_batch_size, batch_size) / 2, 1);
+            debug!(
+                "Lowering the batch size limit of {} to {}: {}",
+                self.get_url(),
+                self.max_batch_size,
+                err
+            );
+            return;
+        }
+
         let seconds = match err {
             RpcError::InvalidData => config.RPC_INVALID_DATA_BAN_TIME,
             _ => config.RPC_FAILURE_BAN_TIME,
@@ -107,6 +295,29 @@ impl Rpc {
         self.ban_for_seconds(seconds);
     }
 }
+
+/// Collapse RPCs with the same URL into one, keeping the first one's position, proxy and auth header.
+/// The merged RPC supports the commands of all of them, and is banned for as long as any of them.
+/// It uses the lowest of their batch size limits.
+pub fn dedup_rpcs(rpcs: Vec<Rpc>) -> Vec<Rpc> {
+    let mut deduped: Vec<Rpc> = vec![];
+    for rpc in rpcs {
+        match deduped
+            .iter_mut()
+            .find(|existing| existing.get_url() == rpc.get_url())
+        {
+            Some(existing) => {
+                debug!("Merging duplicate RPC {}", rpc.get_url());
+                existing.commands = existing.commands.union(&rpc.commands);
+                existing.banned_until = max(existing.banned_until, rpc.banned_until);
+                existing.max_batch_size = min(existing.max_batch_size, rpc.max_batch_size);
+            }
+            None => deduped.push(rpc),
+        }
+    }
+    deduped
+}
+
 impl Serialize for Rpc {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -117,6 +328,8 @@ impl Serialize for Rpc {
             url: self.get_url().to_owned(),
             proxy: self.get_proxy().map(|proxy| proxy.to_owned()),
             banned_until: self.banned_until,
+            max_batch_size: self.max_batch_size,
+            auth_header: self.auth_header.clone(),
         }
         .serialize(serializer)
     }
@@ -126,16 +339,94 @@ impl<'de> Deserialize<'de> for Rpc {
     where
         D: serde::Deserializer<'de>,
     {
-        let rpc = WrappedRpcSerde::deserialize(deserializer)?;
-        let rpc = Rpc::_new(rpc.commands, &rpc.url, rpc.proxy, rpc.banned_until);
-        Ok(rpc.expect("could not deserialize WrappedRpcSerde"))
+        let mut rpc = WrappedRpcSerde::deserialize(deserializer)?;
+        let mut w_rpc = Rpc::_new(
+            rpc.commands.clone(),
+            &rpc.url,
+            rpc.proxy.clone(),
+            rpc.banned_until,
+        )
+        .expect("could not deserialize WrappedRpcSerde");
+        w_rpc.max_batch_size = max(rpc.max_batch_size, 1);
+        w_rpc.auth_header = rpc.auth_header.take();
+        Ok(w_rpc)
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Zeroize, ZeroizeOnDrop, Serialize, Deserialize)]
 struct WrappedRpcSerde {
     commands: RpcCommands,
     url: String,
     proxy: Option<String>,
     banned_until: u64,
+    /// Added in v0.1.2
+    #[serde(default = "default_max_batch_size")]
+    max_batch_size: usize,
+    /// Added in v0.1.2
+    #[serde(default)]
+    auth_header: Option<(String, String)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn supported_commands() {
+        assert!(RpcCommands::all().supported() == COMMANDS);
+
+        let mut commands = RpcCommands::all();
+        commands.process = false;
+        commands.work_generate = false;
+        assert!(commands.supported() == COMMANDS[..12]);
+    }
+
+    #[test]
+    fn network_difficulty() {
+        let json = serde_json::json!({
+            "network_minimum": "fffffff800000000",
+            "network_receive_minimum": "fffffe0000000000",
+            "network_current": "fffffff84c2e6a2b",
+            "network_receive_current": "fffffe0a2b1c3d4e",
+            "multiplier": "1.000000000000000"
+        });
+        let difficulty = network_difficulty_from_json(&json).unwrap();
+        assert!(difficulty.send == 0xfffffff84c2e6a2b);
+        assert!(difficulty.receive == 0xfffffe0a2b1c3d4e);
+        assert!(network_difficulty_from_json(&serde_json::json!({})).is_err());
+    }
+
+    #[test]
+    fn dedup() {
+        let mut commands = RpcCommands::all();
+        commands.work_generate = false;
+        let rpc_1 = Rpc::_new(commands.clone(), "https://example.com", None, 0).unwrap();
+        let rpc_2 = Rpc::new(RpcCommands::all(), "https://example2.com", None).unwrap();
+        commands.work_generate = true;
+        commands.process = false;
+        let rpc_3 = Rpc::_new(commands, "https://example.com", None, 1000).unwrap();
+
+        let rpcs = dedup_rpcs(vec![rpc_1, rpc_2, rpc_3]);
+        assert!(rpcs.len() == 2);
+        assert!(rpcs[0].get_url() == "https://example.com");
+        assert!(rpcs[0].commands == RpcCommands::all());
+        assert!(rpcs[0].banned_until == 1000);
+        assert!(rpcs[1].get_url() == "https://example2.com");
+    }
+
+    #[test]
+    fn auth_header() {
+        let mut rpc = Rpc::new(RpcCommands::all(), "https://example.com", None).unwrap();
+        rpc.auth_header = Some(("X-Api-Key".into(), "secret-token".into()));
+        assert!(!format!("{rpc:?}").contains("secret-token"));
+
+        let json = serde_json::to_string(&rpc).unwrap();
+        let deserialized: Rpc = serde_json::from_str(&json).unwrap();
+        assert!(deserialized.auth_header == rpc.auth_header);
+
+        // RPCs saved before auth headers were added
+        let json = json.replace(r#","auth_header":["X-Api-Key","secret-token"]"#, "");
+        let deserialized: Rpc = serde_json::from_str(&json).unwrap();
+        assert!(deserialized.auth_header.is_none());
+    }
 }
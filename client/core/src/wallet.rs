@@ -1,10 +1,13 @@
-use super::config::CoreClientConfig;
+use super::config::{CoreClientConfig, IndexRange};
 use super::error::CoreClientError;
+use bip39::{Language, Mnemonic};
 use log::debug;
 use nanopyrs::{camo::*, Account, Block, Key, SecretBytes};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::convert::From;
-use std::fmt::Display;
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
 #[derive(Clone, Debug, PartialEq, Eq, Zeroize, ZeroizeOnDrop, Serialize, Deserialize)]
@@ -36,28 +39,12 @@ macro_rules! _search_db {
             .find(|item| &item.account.signer_account() == $account)
     }};
 
-    ($db: expr, CamoAccount, $account: expr, $iter: ident) => {{
-        _search_db!($db, Account, $account, $iter)
-    }};
-
-    ($db: expr, Account, $value: expr, $iter: ident) => {{
-        $db.info.$iter().find(|item| item.account == $value)
-    }};
-
     ($db: expr, Index, $value: expr, $iter: ident) => {{
         $db.info.$iter().find(|item| item.index == $value)
     }};
 }
 
 macro_rules! search_db {
-    (mut $db: expr, Remove, $value: expr) => {{
-        $db.info
-            .iter()
-            .position(|info| &info.account == $value)
-            .ok_or(CoreClientError::AccountNotFound)
-            .map(|index| $db.info.remove(index))
-    }};
-
     ($db: expr, $type: ident, $account: expr) => {{
         _search_db!($db, $type, $account, iter)
     }};
@@ -67,15 +54,28 @@ macro_rules! search_db {
 }
 
 #[derive(Debug, Clone, Zeroize, ZeroizeOnDrop, Serialize, Deserialize)]
-pub struct GenericInfoDB<T: Clone + Eq + Zeroize + Display> {
+#[serde(from = "GenericInfoDBSerde<T>", into = "GenericInfoDBSerde<T>")]
+pub struct GenericInfoDB<T: Clone + Eq + Hash + Zeroize + Display> {
     /// **Unordered!** The index of accounts in this does not necessarily represent their actual wallet index
-    pub info: Vec<GenericInfo<T>>,
+    info: Vec<GenericInfo<T>>,
+    /// Position of each account in `info`
+    #[zeroize(skip)]
+    accounts: HashMap<T, usize>,
 }
-impl<T: Clone + Eq + Zeroize + Display> GenericInfoDB<T> {
+impl<T: Clone + Eq + Hash + Zeroize + Display> GenericInfoDB<T> {
     pub fn new() -> GenericInfoDB<T> {
         Self::default()
     }
 
+    fn _reindex(&mut self) {
+        self.accounts = self
+            .info
+            .iter()
+            .enumerate()
+            .map(|(position, info)| (info.account.clone(), position))
+            .collect();
+    }
+
     pub fn all_infos(&self) -> &[GenericInfo<T>] {
         &self.info
     }
@@ -92,6 +92,7 @@ impl<T: Clone + Eq + Zeroize + Display> GenericInfoDB<T> {
             return true;
         }
         debug!("Adding {} to wallet DB", info.account);
+        self.accounts.insert(info.account.clone(), self.info.len());
         self.info.push(info);
         false
     }
@@ -115,27 +116,39 @@ impl<T: Clone + Eq + Zeroize + Display> GenericInfoDB<T> {
 
     /// Remove an account from the DB, returning the account info if successful.
     pub fn remove(&mut self, account: &T) -> Result<GenericInfo<T>, CoreClientError> {
-        search_db!(mut self, Remove, account)
+        let position = self
+            .accounts
+            .get(account)
+            .ok_or(CoreClientError::AccountNotFound)?;
+        let removed = self.info.remove(*position);
+        self._reindex();
+        Ok(removed)
     }
 
     pub fn get_info(&self, account: &T) -> Option<&GenericInfo<T>> {
-        search_db!(self, Account, *account)
+        self.accounts
+            .get(account)
+            .map(|position| &self.info[*position])
     }
 
     pub fn get_info_from_index(&self, index: u32) -> Option<&GenericInfo<T>> {
         search_db!(self, Index, index)
     }
 
+    /// The account must not be modified, since the DB is indexed by account.
     pub fn get_mut_info(&mut self, account: &T) -> Option<&mut GenericInfo<T>> {
-        search_db!(mut self, Account, *account)
+        self.accounts
+            .get(account)
+            .map(|position| &mut self.info[*position])
     }
 
+    /// The account must not be modified, since the DB is indexed by account.
     pub fn get_mut_info_from_index(&mut self, index: u32) -> Option<&mut GenericInfo<T>> {
         search_db!(mut self, Index, index)
     }
 
     pub fn contains(&self, account: &T) -> bool {
-        self.get_info(account).is_some()
+        self.accounts.contains_key(account)
     }
 
     pub fn contains_index(&self, index: u32) -> bool {
@@ -161,9 +174,35 @@ impl GenericInfoDB<CamoAccount> {
         self.get_info_from_notification_account(account).is_some()
     }
 }
-impl<T: Clone + Eq + Zeroize + Display> Default for GenericInfoDB<T> {
+impl<T: Clone + Eq + Hash + Zeroize + Display> Default for GenericInfoDB<T> {
     fn default() -> Self {
-        GenericInfoDB { info: vec![] }
+        GenericInfoDB {
+            info: vec![],
+            accounts: HashMap::new(),
+        }
+    }
+}
+
+/// `GenericInfoDB` as it is serialized, without its index
+#[derive(Serialize, Deserialize)]
+struct GenericInfoDBSerde<T: Clone + Eq + Zeroize> {
+    info: Vec<GenericInfo<T>>,
+}
+impl<T: Clone + Eq + Hash + Zeroize + Display> From<GenericInfoDBSerde<T>> for GenericInfoDB<T> {
+    fn from(value: GenericInfoDBSerde<T>) -> Self {
+        let mut db = GenericInfoDB {
+            info: value.info,
+            accounts: HashMap::new(),
+        };
+        db._reindex();
+        db
+    }
+}
+impl<T: Clone + Eq + Hash + Zeroize + Display> From<GenericInfoDB<T>> for GenericInfoDBSerde<T> {
+    fn from(value: GenericInfoDB<T>) -> Self {
+        GenericInfoDBSerde {
+            info: value.info.clone(),
+        }
     }
 }
 
@@ -171,9 +210,16 @@ pub type AccountDB = GenericInfoDB<Account>;
 pub type CamoAccountDB = GenericInfoDB<CamoAccount>;
 
 #[derive(Debug, Clone, Zeroize, ZeroizeOnDrop, Default, Serialize, Deserialize)]
+#[serde(from = "DerivedAccountDBSerde", into = "DerivedAccountDBSerde")]
 pub struct DerivedAccountDB {
     /// **Unordered!** The index of accounts in this does not necessarily represent their actual wallet index
-    pub info: Vec<DerivedAccountInfo>,
+    info: Vec<DerivedAccountInfo>,
+    /// Position of each account in `info`
+    #[zeroize(skip)]
+    accounts: HashMap<Account, usize>,
+    /// Positions in `info` of the accounts derived from each master Camo account
+    #[zeroize(skip)]
+    masters: HashMap<u32, Vec<usize>>,
 }
 
 impl DerivedAccountDB {
@@ -181,6 +227,18 @@ impl DerivedAccountDB {
         Self::default()
     }
 
+    fn _reindex(&mut self) {
+        self.accounts.clear();
+        self.masters.clear();
+        for (position, info) in self.info.iter().enumerate() {
+            self.accounts.insert(info.account.clone(), position);
+            self.masters
+                .entry(info.master_index)
+                .or_default()
+                .push(position);
+        }
+    }
+
     pub fn all_infos(&self) -> &[DerivedAccountInfo] {
         &self.info
     }
@@ -197,6 +255,12 @@ impl DerivedAccountDB {
             return true;
         }
         debug!("Adding {} to wallet DB", info.account);
+        let position = self.info.len();
+        self.accounts.insert(info.account.clone(), position);
+        self.masters
+            .entry(info.master_index)
+            .or_default()
+            .push(position);
         self.info.push(info);
         false
     }
@@ -210,11 +274,19 @@ impl DerivedAccountDB {
 
     /// Remove an account from the DB, returning the account info if successful.
     pub fn remove(&mut self, account: &Account) -> Result<DerivedAccountInfo, CoreClientError> {
-        search_db!(mut self, Remove, account)
+        let position = self
+            .accounts
+            .get(account)
+            .ok_or(CoreClientError::AccountNotFound)?;
+        let removed = self.info.remove(*position);
+        self._reindex();
+        Ok(removed)
     }
 
     pub fn get_info(&self, account: &Account) -> Option<&DerivedAccountInfo> {
-        search_db!(self, Account, *account)
+        self.accounts
+            .get(account)
+            .map(|position| &self.info[*position])
     }
 
     pub fn get_info_from_index(&self, index: u32) -> Option<&DerivedAccountInfo> {
@@ -231,22 +303,29 @@ impl DerivedAccountDB {
             None => return vec![],
         };
 
-        self.info
-            .iter()
-            .filter(|item| item.master_index == index)
-            .collect::<Vec<&DerivedAccountInfo>>()
+        match self.masters.get(&index) {
+            Some(positions) => positions
+                .iter()
+                .map(|position| &self.info[*position])
+                .collect(),
+            None => vec![],
+        }
     }
 
+    /// The account and master index must not be modified, since the DB is indexed by them.
     pub fn get_mut_info(&mut self, account: &Account) -> Option<&mut DerivedAccountInfo> {
-        search_db!(mut self, Account, *account)
+        self.accounts
+            .get(account)
+            .map(|position| &mut self.info[*position])
     }
 
+    /// The account and master index must not be modified, since the DB is indexed by them.
     pub fn get_mut_info_from_index(&mut self, index: u32) -> Option<&mut DerivedAccountInfo> {
         search_db!(mut self, Index, index)
     }
 
     pub fn contains(&self, account: &Account) -> bool {
-        self.get_info(account).is_some()
+        self.accounts.contains_key(account)
     }
 
     pub fn contains_index(&self, index: u32) -> bool {
@@ -254,6 +333,66 @@ impl DerivedAccountDB {
     }
 }
 
+/// `DerivedAccountDB` as it is serialized, without its indexes
+#[derive(Serialize, Deserialize)]
+struct DerivedAccountDBSerde {
+    info: Vec<DerivedAccountInfo>,
+}
+impl From<DerivedAccountDBSerde> for DerivedAccountDB {
+    fn from(value: DerivedAccountDBSerde) -> Self {
+        let mut db = DerivedAccountDB {
+            info: value.info,
+            accounts: HashMap::new(),
+            masters: HashMap::new(),
+        };
+        db._reindex();
+        db
+    }
+}
+impl From<DerivedAccountDB> for DerivedAccountDBSerde {
+    fn from(value: DerivedAccountDB) -> Self {
+        DerivedAccountDBSerde {
+            info: value.info.clone(),
+        }
+    }
+}
+
+/// Signs blocks on behalf of the wallet.
+///
+/// The default implementation, `SeedSigner`, derives keys from the wallet's seed.
+/// Other implementations (e.g. hardware wallets) may sign blocks without the seed being in memory.
+pub trait Signer: Debug + Send + Sync {
+    /// Sign the given block, returning it with a signature attached
+    fn sign_block(&self, wallet_db: &WalletDB, block: Block) -> Result<Block, CoreClientError>;
+    /// Clone this signer into a new `Box`
+    fn clone_box(&self) -> Box<dyn Signer>;
+}
+impl Clone for Box<dyn Signer> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Signs blocks with keys derived from the wallet's seed
+#[derive(Debug, Clone, Zeroize, ZeroizeOnDrop)]
+pub struct SeedSigner {
+    seed: WalletSeed,
+}
+impl SeedSigner {
+    pub fn new(seed: WalletSeed) -> SeedSigner {
+        SeedSigner { seed }
+    }
+}
+impl Signer for SeedSigner {
+    fn sign_block(&self, wallet_db: &WalletDB, block: Block) -> Result<Block, CoreClientError> {
+        wallet_db.sign_block(&self.seed, block)
+    }
+
+    fn clone_box(&self) -> Box<dyn Signer> {
+        Box::new(self.clone())
+    }
+}
+
 #[derive(Debug, Clone, Zeroize, ZeroizeOnDrop, Serialize, Deserialize)]
 pub struct WalletSeed {
     bytes: SecretBytes<32>,
@@ -269,10 +408,34 @@ impl WalletSeed {
         result
     }
 
+    /// Import a seed from a 24-word BIP39 mnemonic, where the mnemonic's entropy is the seed
+    /// (the convention used by other Nano wallets)
+    pub fn from_mnemonic(phrase: &str) -> Result<WalletSeed, CoreClientError> {
+        let mnemonic = Mnemonic::parse_in(Language::English, phrase)
+            .map_err(|err| CoreClientError::InvalidMnemonic(err.to_string()))?;
+        let mut entropy = mnemonic.to_entropy();
+        let seed_bytes: Result<[u8; 32], _> = entropy.as_slice().try_into();
+        entropy.zeroize();
+        let seed_bytes = seed_bytes.map_err(|_| {
+            CoreClientError::InvalidMnemonic(format!(
+                "expected 24 words, got {}",
+                mnemonic.word_count()
+            ))
+        })?;
+        Ok(WalletSeed::from(seed_bytes))
+    }
+
     pub fn as_hex(&self) -> String {
         hex::encode(self.bytes.as_ref())
     }
 
+    /// Returns the seed as a 24-word BIP39 mnemonic, the inverse of `from_mnemonic()`
+    pub fn as_mnemonic(&self) -> String {
+        Mnemonic::from_entropy_in(Language::English, self.bytes.as_ref())
+            .expect("32 bytes is a valid entropy length")
+            .to_string()
+    }
+
     pub fn get_key(&self, index: u32) -> (Key, AccountInfo) {
         let key = Key::from_seed(&self.bytes, index);
         let account = key.to_account();
@@ -338,6 +501,8 @@ pub struct WalletDB {
     pub account_db: AccountDB,
     pub camo_account_db: CamoAccountDB,
     pub derived_account_db: DerivedAccountDB,
+    /// Accounts tracked for their balance, whose keys are not known (added in v0.1.2)
+    watch_only: Vec<Account>,
 }
 impl WalletDB {
     /// Find the key of the given `nano_` account in this wallet, regardless of where it is located.
@@ -422,8 +587,9 @@ impl WalletDB {
         .concat()
     }
 
-    /// Returns all on-chain accounts controlled by this wallet, regardless of where they are located
-    pub fn all_nano_accounts(&self) -> Vec<Account> {
+    /// Returns all on-chain accounts controlled by this wallet, regardless of where they are located.
+    /// Does not include watch-only accounts.
+    pub fn spendable_nano_accounts(&self) -> Vec<Account> {
         [
             self.account_db.all_accounts(),
             self.camo_account_db.all_notification_accounts(),
@@ -432,6 +598,86 @@ impl WalletDB {
         .concat()
     }
 
+    /// Returns all on-chain accounts tracked by this wallet, including watch-only accounts
+    pub fn all_nano_accounts(&self) -> Vec<Account> {
+        [self.spendable_nano_accounts(), self.watch_only.clone()].concat()
+    }
+
+    /// Returns all on-chain accounts tracked by this wallet whose index is within `range`, including watch-only accounts.
+    /// Derived accounts use the index of their camo account, and watch-only accounts (which have no index) are always included.
+    ///
+    /// If `range` is `None`, this is the same as `all_nano_accounts()`.
+    pub fn nano_accounts_in_range(&self, range: Option<&IndexRange>) -> Vec<Account> {
+        let Some(range) = range else {
+            return self.all_nano_accounts();
+        };
+        let accounts = self
+            .account_db
+            .all_infos()
+            .iter()
+            .filter(|info| range.contains(info.index))
+            .map(|info| info.account.clone());
+        let notification_accounts = self
+            .camo_account_db
+            .all_infos()
+            .iter()
+            .filter(|info| range.contains(info.index))
+            .map(|info| info.account.signer_account());
+        let derived_accounts = self
+            .derived_account_db
+            .all_infos()
+            .iter()
+            .filter(|info| range.contains(info.master_index))
+            .map(|info| info.account.clone());
+        accounts
+            .chain(notification_accounts)
+            .chain(derived_accounts)
+            .chain(self.watch_only.iter().cloned())
+            .collect()
+    }
+
+    /// Returns all watch-only accounts
+    pub fn watch_only_accounts(&self) -> &[Account] {
+        &self.watch_only
+    }
+
+    pub fn is_watch_only(&self, account: &Account) -> bool {
+        self.watch_only.contains(account)
+    }
+
+    /// Track an account for its balance, without knowing its key.
+    ///
+    /// Returns `Err` if the limit has been reached on how many accounts can be tracked at one time.
+    /// See the `DB_NUMBER_OF_ACCOUNTS_LIMIT` configuration option.
+    ///
+    /// Otherwise, returns whether or not the wallet already contained the account.
+    pub fn add_watch_only(
+        &mut self,
+        config: &CoreClientConfig,
+        account: Account,
+    ) -> Result<bool, CoreClientError> {
+        if self.is_watch_only(&account) || self.contains_account(&account) {
+            return Ok(true);
+        }
+        if self.watch_only.len() >= config.DB_NUMBER_OF_ACCOUNTS_LIMIT {
+            return Err(CoreClientError::DBAccountLimitReached);
+        }
+        debug!("Adding watch-only account {account} to wallet DB");
+        self.watch_only.push(account);
+        Ok(false)
+    }
+
+    /// Stop tracking a watch-only account
+    pub fn remove_watch_only(&mut self, account: &Account) -> Result<(), CoreClientError> {
+        let position = self
+            .watch_only
+            .iter()
+            .position(|watched| watched == account)
+            .ok_or(CoreClientError::AccountNotFound)?;
+        self.watch_only.remove(position);
+        Ok(())
+    }
+
     /// sign the given block, returning it with a signature attached
     pub fn sign_block(
         &self,
@@ -446,6 +692,24 @@ impl WalletDB {
     }
 }
 
+/// Wallet DB as stored before watch-only accounts were added, used to migrate older wallets
+#[derive(Debug, Zeroize, Deserialize)]
+pub struct LegacyWalletDB {
+    account_db: AccountDB,
+    camo_account_db: CamoAccountDB,
+    derived_account_db: DerivedAccountDB,
+}
+impl From<LegacyWalletDB> for WalletDB {
+    fn from(value: LegacyWalletDB) -> Self {
+        WalletDB {
+            account_db: value.account_db,
+            camo_account_db: value.camo_account_db,
+            derived_account_db: value.derived_account_db,
+            watch_only: vec![],
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -504,6 +768,31 @@ mod tests {
         assert!(seed.as_hex() == seed_hex);
     }
 
+    #[test]
+    fn seed_from_mnemonic() {
+        let phrase = format!("{}art", "abandon ".repeat(23));
+        let seed = WalletSeed::from_mnemonic(&phrase).unwrap();
+        assert!(seed.as_hex() == hex::encode([0; 32]));
+
+        // invalid checksum
+        let phrase = format!("{}abandon", "abandon ".repeat(23));
+        assert!(WalletSeed::from_mnemonic(&phrase).is_err());
+        // valid, but only 12 words
+        let phrase = format!("{}about", "abandon ".repeat(11));
+        assert!(WalletSeed::from_mnemonic(&phrase).is_err());
+    }
+
+    #[test]
+    fn seed_mnemonic_roundtrip() {
+        let seed = fake_seed().unwrap();
+        let phrase = seed.as_mnemonic();
+        assert!(phrase.split(' ').count() == 24);
+        assert!(WalletSeed::from_mnemonic(&phrase).unwrap().as_hex() == seed.as_hex());
+
+        let phrase = WalletSeed::from([0; 32]).as_mnemonic();
+        assert!(phrase == format!("{}art", "abandon ".repeat(23)));
+    }
+
     #[test]
     fn seed_get_account() {
         let account = WalletSeed::from([0; 32]).get_key(0).0.to_account();
@@ -520,6 +809,24 @@ mod tests {
         assert!(account == fake_camo_account());
     }
 
+    #[test]
+    fn db_index_range() {
+        let seed = fake_seed().unwrap();
+        let db = fake_db().unwrap();
+
+        assert!(db.nano_accounts_in_range(None).len() == 4);
+        let range = IndexRange { min: 92, max: 99 };
+        let accounts = db.nano_accounts_in_range(Some(&range));
+        // 92, and the camo account 99 with its derived account
+        assert!(accounts.len() == 3);
+        assert!(!accounts.contains(&seed.get_key(91).0.to_account()));
+        assert!(accounts.contains(&seed.get_key(92).0.to_account()));
+
+        let range = IndexRange { min: 0, max: 91 };
+        let accounts = db.nano_accounts_in_range(Some(&range));
+        assert!(accounts == vec![seed.get_key(91).0.to_account()]);
+    }
+
     #[test]
     fn db_insert() {
         let seed = fake_seed().unwrap();
@@ -541,6 +848,42 @@ mod tests {
         assert!(db.all_nano_accounts().contains(&account));
     }
 
+    #[test]
+    fn db_remove() {
+        let seed = fake_seed().unwrap();
+        let mut db = fake_db().unwrap();
+        let account_91 = seed.get_key(91).0.to_account();
+        let account_92 = seed.get_key(92).0.to_account();
+        let camo_account = seed
+            .get_camo_key(99, camo_versions())
+            .unwrap()
+            .0
+            .to_camo_account();
+        let derived = db.derived_account_db.all_accounts()[0].clone();
+
+        // the remaining accounts can still be found after their positions shift
+        db.account_db.remove(&account_91).unwrap();
+        assert!(!db.account_db.contains(&account_91));
+        assert!(db.account_db.get_info(&account_92).unwrap().index == 92);
+        assert!(db.account_db.remove(&account_91).is_err());
+
+        // indexes are rebuilt when deserializing
+        let json = serde_json::to_string(&db).unwrap();
+        let mut db: WalletDB = serde_json::from_str(&json).unwrap();
+        assert!(db.account_db.contains(&account_92));
+        assert!(db.derived_account_db.contains(&derived));
+        let from_master = db
+            .derived_account_db
+            .get_info_from_master(&db.camo_account_db, &camo_account);
+        assert!(from_master.len() == 1);
+
+        db.derived_account_db.remove(&derived).unwrap();
+        assert!(db
+            .derived_account_db
+            .get_info_from_master(&db.camo_account_db, &camo_account)
+            .is_empty());
+    }
+
     #[test]
     fn db_find_key() {
         let seed = fake_seed().unwrap();
@@ -594,6 +937,50 @@ mod tests {
             work: [0; 8],
         };
         block = db.sign_block(&seed, block).unwrap();
+        assert!(block.has_valid_signature());
+
+        block.signature = Signature::default();
+        block = SeedSigner::new(seed).sign_block(&db, block).unwrap();
         assert!(block.has_valid_signature())
     }
+
+    #[test]
+    fn db_watch_only() {
+        let seed = fake_seed().unwrap();
+        let config = CoreClientConfig::test_default();
+        let mut db = fake_db().unwrap();
+        let account = fake_account();
+
+        assert!(!db.add_watch_only(&config, account.clone()).unwrap());
+        assert!(db.add_watch_only(&config, account.clone()).unwrap());
+        assert!(db.is_watch_only(&account));
+        assert!(db.all_nano_accounts().contains(&account));
+        assert!(!db.spendable_nano_accounts().contains(&account));
+        assert!(!db.contains_account(&account));
+        assert!(db.find_key(&seed, &account).is_none());
+
+        let block = Block {
+            block_type: BlockType::Send,
+            account: account.clone(),
+            previous: [22; 32],
+            representative: fake_account(),
+            balance: 999,
+            link: [201; 32],
+            signature: Signature::default(),
+            work: [0; 8],
+        };
+        assert!(matches!(
+            db.sign_block(&seed, block),
+            Err(CoreClientError::AccountNotFound)
+        ));
+
+        // accounts with known keys are never watch-only
+        let owned = seed.get_key(91).0.to_account();
+        assert!(db.add_watch_only(&config, owned.clone()).unwrap());
+        assert!(!db.is_watch_only(&owned));
+
+        db.remove_watch_only(&account).unwrap();
+        assert!(!db.is_watch_only(&account));
+        assert!(db.remove_watch_only(&account).is_err());
+    }
 }
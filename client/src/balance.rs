@@ -1,18 +1,42 @@
 use super::error::ClientError;
-use super::types::Amount;
-use super::WalletFrontend;
-use core_client::{Account, CamoAccount, CoreClient, Receivable};
+use super::types::{Amount, MASKED_AMOUNT};
+use super::{Client, WalletFrontend};
+use core_client::constants::{ONE_DAY, ONE_HOUR, ONE_MINUTE, ONE_NANO};
+use core_client::{rpc::get_current_time, Account, CamoAccount, CoreClient, Receivable};
+use serde_json::{json, Value as JsonValue};
+use std::cmp::Reverse;
 
-fn get_display_balance(client: &CoreClient, account: &Account) -> String {
+/// `fiat` is the currency and the price of one Nano in it, if known
+fn display_fiat(amount: u128, fiat: Option<(&str, f64)>, mask: bool) -> String {
+    let Some((currency, price)) = fiat else {
+        return String::new();
+    };
+    let currency = currency.to_uppercase();
+    match mask {
+        true => format!(" (~{MASKED_AMOUNT} {currency})"),
+        false => format!(
+            " (~{:.2} {currency})",
+            amount as f64 / ONE_NANO as f64 * price
+        ),
+    }
+}
+
+fn get_display_balance(
+    client: &CoreClient,
+    account: &Account,
+    fiat: Option<(&str, f64)>,
+    mask: bool,
+) -> String {
     let amount: Amount = client
         .frontiers_db
         .account_balance(account)
         .unwrap_or(0)
         .into();
-    amount.to_string()
+    let fiat = display_fiat(amount.value, fiat, mask);
+    format!("{} Nano{fiat}", amount.display(mask))
 }
 
-/// Returns `Vec<(index, account)>`, sorted
+/// Returns `Vec<(index, account)>`, sorted, with `PRIMARY_ACCOUNT` (if any) first
 fn get_normal_accounts(client: &CoreClient) -> Vec<(u32, Account)> {
     let mut accounts: Vec<(u32, Account)> = client
         .wallet_db
@@ -22,9 +46,35 @@ fn get_normal_accounts(client: &CoreClient) -> Vec<(u32, Account)> {
         .map(|info| (info.index, info.account.clone()))
         .collect();
     accounts.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+
+    if let Some(primary) = &client.config.PRIMARY_ACCOUNT {
+        if let Some(position) = accounts
+            .iter()
+            .position(|(index, account)| primary.matches(*index, account))
+        {
+            let primary = accounts.remove(position);
+            accounts.insert(0, primary);
+        }
+    }
     accounts
 }
 
+/// Warn if `PRIMARY_ACCOUNT` is set, but is not one of this wallet's normal accounts
+fn check_primary_account<Frontend: WalletFrontend>(client: &CoreClient) {
+    let Some(primary) = &client.config.PRIMARY_ACCOUNT else {
+        return;
+    };
+    let tracked = client
+        .wallet_db
+        .account_db
+        .all_infos()
+        .iter()
+        .any(|info| primary.matches(info.index, &info.account));
+    if !tracked {
+        Frontend::println("Warning: 'PRIMARY_ACCOUNT' is not tracked by this wallet");
+    }
+}
+
 /// Returns `Vec<(index, account)>`, sorted
 fn get_camo_accounts(client: &CoreClient) -> Vec<(u32, CamoAccount)> {
     let mut accounts: Vec<(u32, CamoAccount)> = client
@@ -57,34 +107,110 @@ fn filter_receivable(receivables: &[&Receivable], account: &Account) -> Amount {
         .into()
 }
 
-pub fn execute<Frontend: WalletFrontend>(frontend: &Frontend) -> Result<(), ClientError> {
+/// Balance and receivable amount of an account, in raw
+fn account_json(client: &CoreClient, receivables: &[&Receivable], account: &Account) -> JsonValue {
+    json!({
+        "account": account.to_string(),
+        "balance": client.frontiers_db.account_balance(account).unwrap_or(0).to_string(),
+        "receivable": filter_receivable(receivables, account).value.to_string(),
+    })
+}
+
+fn execute_json<Frontend: WalletFrontend>(frontend: &Frontend, fiat: Option<(&str, f64)>) {
     let client = frontend.client();
-    fn print_balance<Frontend: WalletFrontend>(receivable: Amount, s: String) {
-        match receivable.value > 0 {
-            true => Frontend::println(&format!("{s} (+ {receivable} Nano receivable)")),
-            false => Frontend::println(&s),
-        }
+    let core_client = &client.core;
+    let receivables: Vec<&Receivable> = client.receivable.values().collect();
+
+    let accounts: Vec<JsonValue> = get_normal_accounts(core_client)
+        .into_iter()
+        .map(|(index, account)| {
+            let mut json = account_json(core_client, &receivables, &account);
+            json["index"] = index.into();
+            json
+        })
+        .collect();
+    let camo_accounts: Vec<JsonValue> = get_camo_accounts(core_client)
+        .into_iter()
+        .map(|(index, camo_account)| {
+            let derived: Vec<JsonValue> = get_derived_accounts(core_client, &camo_account)
+                .iter()
+                .map(|account| account_json(core_client, &receivables, account))
+                .collect();
+            json!({
+                "camo_account": camo_account.to_string(),
+                "index": index,
+                "main": account_json(core_client, &receivables, &camo_account.signer_account()),
+                "derived": derived,
+            })
+        })
+        .collect();
+    let watch_only: Vec<JsonValue> = core_client
+        .wallet_db
+        .watch_only_accounts()
+        .iter()
+        .map(|account| account_json(core_client, &receivables, account))
+        .collect();
+
+    let fiat = fiat.map(|(currency, price)| {
+        json!({
+            "currency": currency.to_lowercase(),
+            "price": price,
+        })
+    });
+    Frontend::println_json(&json!({
+        "balance": core_client.wallet_balance().to_string(),
+        "receivable": client.receivable_total().to_string(),
+        "accounts": accounts,
+        "camo_accounts": camo_accounts,
+        "watch_only": watch_only,
+        "fiat": fiat,
+    }));
+}
+
+/// `fiat` is the currency and the price of one Nano in it, if balances should also be shown in that currency
+pub fn execute<Frontend: WalletFrontend>(
+    frontend: &Frontend,
+    json: bool,
+    mask: bool,
+    fiat: Option<(&str, f64)>,
+) -> Result<(), ClientError> {
+    if json {
+        execute_json(frontend, fiat);
+        return Ok(());
     }
 
+    let client = frontend.client();
+    let print_balance = |receivable: Amount, s: String| match receivable.value > 0 {
+        true => Frontend::println(&format!(
+            "{s} (+ {} Nano receivable)",
+            receivable.display(mask)
+        )),
+        false => Frontend::println(&s),
+    };
+
     let core_client = &client.core;
     let receivables: Vec<&Receivable> = client.receivable.values().collect();
+    check_primary_account::<Frontend>(core_client);
 
     // total balance
     let total: Amount = core_client.wallet_balance().into();
-    let total_receivable: Amount = receivables
-        .iter()
-        .map(|receivable| receivable.amount)
-        .sum::<u128>()
-        .into();
-    print_balance::<Frontend>(total_receivable, format!("total: {total} Nano"));
+    let total_receivable: Amount = client.receivable_total().into();
+    print_balance(
+        total_receivable,
+        format!(
+            "total: {} Nano{}",
+            total.display(mask),
+            display_fiat(total.value, fiat, mask)
+        ),
+    );
 
     // normal accounts
     for (index, account) in get_normal_accounts(core_client) {
-        let balance = get_display_balance(core_client, &account);
+        let balance = get_display_balance(core_client, &account, fiat, mask);
         let account_receivable = filter_receivable(&receivables, &account);
-        print_balance::<Frontend>(
+        print_balance(
             account_receivable,
-            format!("{account} (#{index}): {balance} Nano"),
+            format!("{account} (#{index}): {balance}"),
         );
     }
 
@@ -94,18 +220,219 @@ pub fn execute<Frontend: WalletFrontend>(frontend: &Frontend) -> Result<(), Clie
 
         // main account
         let main_account = camo_account.signer_account();
-        let balance = get_display_balance(core_client, &main_account);
+        let balance = get_display_balance(core_client, &main_account, fiat, mask);
         let account_receivable = filter_receivable(&receivables, &main_account);
-        print_balance::<Frontend>(
+        print_balance(
             account_receivable,
-            format!("\t{main_account} (main): {balance} Nano"),
+            format!("\t{main_account} (main): {balance}"),
         );
 
         // derived accounts
         for account in get_derived_accounts(core_client, &camo_account) {
-            let balance = get_display_balance(core_client, &account);
+            let balance = get_display_balance(core_client, &account, fiat, mask);
             let account_receivable = filter_receivable(&receivables, &account);
-            print_balance::<Frontend>(account_receivable, format!("\t{account}: {balance} Nano"));
+            print_balance(account_receivable, format!("\t{account}: {balance}"));
+        }
+    }
+
+    // watch-only accounts, which are not included in the total
+    for account in core_client.wallet_db.watch_only_accounts() {
+        let balance = get_display_balance(core_client, account, fiat, mask);
+        let account_receivable = filter_receivable(&receivables, account);
+        print_balance(
+            account_receivable,
+            format!("{account} (watch-only): {balance}"),
+        );
+    }
+    Ok(())
+}
+
+fn format_elapsed(seconds: u64) -> String {
+    match seconds {
+        s if s >= ONE_DAY => format!("{} days", s / ONE_DAY),
+        s if s >= ONE_HOUR => format!("{} hours", s / ONE_HOUR),
+        s if s >= ONE_MINUTE => format!("{} minutes", s / ONE_MINUTE),
+        s => format!("{s} seconds"),
+    }
+}
+
+fn get_display_activity(client: &Client, account: &Account) -> String {
+    match client.account_activity(account) {
+        Some(activity) if activity.last_activity > 0 => {
+            let elapsed = get_current_time().saturating_sub(activity.last_activity);
+            format!(
+                "{} sends, {} receives, last active {} ago",
+                activity.sends,
+                activity.receives,
+                format_elapsed(elapsed)
+            )
+        }
+        _ => "no activity".into(),
+    }
+}
+
+/// List the wallet's accounts, along with their local activity counters
+pub fn execute_accounts<Frontend: WalletFrontend>(frontend: &Frontend) -> Result<(), ClientError> {
+    let client = frontend.client();
+    let core_client = &client.core;
+    check_primary_account::<Frontend>(core_client);
+
+    // normal accounts
+    for (index, account) in get_normal_accounts(core_client) {
+        let activity = get_display_activity(client, &account);
+        Frontend::println(&format!("{account} (#{index}): {activity}"));
+    }
+
+    // camo accounts
+    for (index, camo_account) in get_camo_accounts(core_client) {
+        Frontend::println(&format!("{camo_account} (#{index}):"));
+
+        let main_account = camo_account.signer_account();
+        let activity = get_display_activity(client, &main_account);
+        Frontend::println(&format!("\t{main_account} (main): {activity}"));
+
+        for account in get_derived_accounts(core_client, &camo_account) {
+            let activity = get_display_activity(client, &account);
+            Frontend::println(&format!("\t{account}: {activity}"));
+        }
+    }
+    Ok(())
+}
+
+/// Display the wallet's balance, the amount sent by unconfirmed blocks, the balance available without them,
+/// the cached receivable total, and the sum of the available balance and receivable total
+pub fn execute_summary<Frontend: WalletFrontend>(
+    frontend: &Frontend,
+    json: bool,
+    mask: bool,
+) -> Result<(), ClientError> {
+    let client = frontend.client();
+    // the frontiers' balances already exclude unconfirmed sends
+    let available = client.core.wallet_balance();
+    let pending_send = client.core.wallet_pending_send();
+    let balance = available.saturating_add(pending_send);
+    let receivable = client.receivable_total();
+    let total = available.saturating_add(receivable);
+
+    if json {
+        Frontend::println_json(&json!({
+            "balance": balance.to_string(),
+            "pending_send": pending_send.to_string(),
+            "available": available.to_string(),
+            "receivable": receivable.to_string(),
+            "total": total.to_string(),
+        }));
+        return Ok(());
+    }
+    for (name, amount) in [
+        ("balance", balance),
+        ("pending send", pending_send),
+        ("available", available),
+        ("receivable", receivable),
+        ("total", total),
+    ] {
+        Frontend::println(&format!(
+            "{name}: {} Nano",
+            Amount::from(amount).display(mask)
+        ));
+    }
+    Ok(())
+}
+
+/// Group the balances of the wallet's opened accounts by representative,
+/// returning `(representative, number of accounts, delegated balance)`, largest first.
+pub fn delegated_weights(client: &CoreClient) -> Vec<(Account, usize, u128)> {
+    let mut representatives: Vec<(Account, usize, u128)> = vec![];
+    for account in client.wallet_db.spendable_nano_accounts() {
+        let frontier = match client.frontiers_db.account_frontier(&account) {
+            Some(frontier) if !frontier.is_unopened() => frontier,
+            _ => continue,
+        };
+        let block = &frontier.block;
+        match representatives
+            .iter_mut()
+            .find(|(representative, _, _)| representative == &block.representative)
+        {
+            Some((_, count, delegated)) => {
+                *count += 1;
+                *delegated += block.balance;
+            }
+            None => representatives.push((block.representative.clone(), 1, block.balance)),
+        }
+    }
+    representatives.sort_by_key(|(_, _, delegated)| Reverse(*delegated));
+    representatives
+}
+
+fn percent_of(value: u128, total: u128) -> u128 {
+    match total {
+        0 => 0,
+        total => value * 100 / total,
+    }
+}
+
+/// List the representatives used by the wallet's accounts, along with the balance delegated to each.
+/// Representatives with at least `threshold` delegated are flagged.
+pub fn execute_representatives<Frontend: WalletFrontend>(
+    frontend: &Frontend,
+    threshold: Option<u128>,
+) -> Result<(), ClientError> {
+    let representatives = delegated_weights(&frontend.client().core);
+    if representatives.is_empty() {
+        Frontend::println("No opened accounts");
+        return Ok(());
+    }
+
+    let total: u128 = representatives
+        .iter()
+        .map(|(_, _, delegated)| delegated)
+        .sum();
+    for (representative, count, delegated) in representatives {
+        let percent = percent_of(delegated, total);
+        Frontend::println(&format!(
+            "{representative}: {count} accounts, {} Nano ({percent}%)",
+            Amount::from(delegated)
+        ));
+        if threshold.is_some_and(|threshold| delegated >= threshold) {
+            Frontend::println(
+                "\tWarning: consider spreading this weight across other representatives",
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Display the vote weight that the wallet delegates to each representative.
+/// `live_weights` are the total weights of the representatives, in the same order as `delegated_weights()`.
+pub fn execute_weight<Frontend: WalletFrontend>(
+    frontend: &Frontend,
+    live_weights: Option<Vec<Option<u128>>>,
+) -> Result<(), ClientError> {
+    let representatives = delegated_weights(&frontend.client().core);
+    if representatives.is_empty() {
+        Frontend::println("No opened accounts");
+        return Ok(());
+    }
+
+    let total: u128 = representatives
+        .iter()
+        .map(|(_, _, delegated)| delegated)
+        .sum();
+    Frontend::println(&format!("Total weight: {} Nano", Amount::from(total)));
+    for (i, (representative, _, delegated)) in representatives.into_iter().enumerate() {
+        Frontend::println(&format!(
+            "{representative}: {} Nano ({}% of wallet)",
+            Amount::from(delegated),
+            percent_of(delegated, total)
+        ));
+        match live_weights.as_ref().map(|weights| weights[i]) {
+            Some(Some(weight)) => Frontend::println(&format!(
+                "\tRepresentative weight: {} Nano ({}% from this wallet)",
+                Amount::from(weight),
+                percent_of(delegated, weight)
+            )),
+            Some(None) => Frontend::println("\tRepresentative weight: unknown"),
+            None => (),
         }
     }
     Ok(())
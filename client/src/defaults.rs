@@ -100,10 +100,12 @@ pub fn default_rpcs() -> Vec<Rpc> {
                 account_history: true,
                 account_info: true,
                 account_representative: true,
+                account_weight: true,
                 accounts_balances: true,
                 accounts_frontiers: true,
                 accounts_receivable: true,
                 accounts_representatives: true,
+                active_difficulty: true,
                 block_info: true,
                 blocks_info: true,
                 process: false,
@@ -117,10 +119,12 @@ pub fn default_rpcs() -> Vec<Rpc> {
                 account_history: true,
                 account_info: true,
                 account_representative: true,
+                account_weight: true,
                 accounts_balances: true,
                 accounts_frontiers: true,
                 accounts_receivable: false,
                 accounts_representatives: true,
+                active_difficulty: true,
                 block_info: true,
                 blocks_info: true,
                 process: true,
@@ -134,10 +138,12 @@ pub fn default_rpcs() -> Vec<Rpc> {
                 account_history: true,
                 account_info: true,
                 account_representative: true,
+                account_weight: true,
                 accounts_balances: true,
                 accounts_frontiers: true,
                 accounts_receivable: false,
                 accounts_representatives: true,
+                active_difficulty: true,
                 block_info: true,
                 blocks_info: true,
                 process: true,
@@ -151,10 +157,12 @@ pub fn default_rpcs() -> Vec<Rpc> {
                 account_history: true,
                 account_info: true,
                 account_representative: true,
+                account_weight: true,
                 accounts_balances: true,
                 accounts_frontiers: true,
                 accounts_receivable: false,
                 accounts_representatives: true,
+                active_difficulty: true,
                 block_info: true,
                 blocks_info: true,
                 process: true,
@@ -168,10 +176,12 @@ pub fn default_rpcs() -> Vec<Rpc> {
                 account_history: true,
                 account_info: true,
                 account_representative: true,
+                account_weight: true,
                 accounts_balances: true,
                 accounts_frontiers: true,
                 accounts_receivable: false,
                 accounts_representatives: true,
+                active_difficulty: true,
                 block_info: true,
                 blocks_info: true,
                 process: true,
@@ -186,10 +196,12 @@ pub fn default_rpcs() -> Vec<Rpc> {
                 account_history: false,
                 account_info: true,
                 account_representative: true,
+                account_weight: true,
                 accounts_balances: true,
                 accounts_frontiers: true,
                 accounts_receivable: true,
                 accounts_representatives: true,
+                active_difficulty: true,
                 block_info: true,
                 blocks_info: true,
                 process: true,
@@ -204,10 +216,12 @@ pub fn default_rpcs() -> Vec<Rpc> {
                 account_history: false,
                 account_info: true,
                 account_representative: true,
+                account_weight: true,
                 accounts_balances: true,
                 accounts_frontiers: true,
                 accounts_receivable: true,
                 accounts_representatives: true,
+                active_difficulty: true,
                 block_info: true,
                 blocks_info: true,
                 process: true,
@@ -223,10 +237,12 @@ pub fn default_rpcs() -> Vec<Rpc> {
                 account_history: false,
                 account_info: true,
                 account_representative: true,
+                account_weight: true,
                 accounts_balances: true,
                 accounts_frontiers: true,
                 accounts_receivable: true,
                 accounts_representatives: true,
+                active_difficulty: true,
                 block_info: true,
                 blocks_info: true,
                 process: true,
@@ -242,10 +258,12 @@ pub fn default_rpcs() -> Vec<Rpc> {
                 account_history: false,
                 account_info: true,
                 account_representative: true,
+                account_weight: true,
                 accounts_balances: true,
                 accounts_frontiers: true,
                 accounts_receivable: true,
                 accounts_representatives: true,
+                active_difficulty: true,
                 block_info: true,
                 blocks_info: true,
                 process: true,
@@ -259,10 +277,12 @@ pub fn default_rpcs() -> Vec<Rpc> {
                 account_history: true,
                 account_info: true,
                 account_representative: true,
+                account_weight: true,
                 accounts_balances: true,
                 accounts_frontiers: true,
                 accounts_receivable: false,
                 accounts_representatives: true,
+                active_difficulty: true,
                 block_info: true,
                 blocks_info: true,
                 process: true,
@@ -276,10 +296,12 @@ pub fn default_rpcs() -> Vec<Rpc> {
                 account_history: true,
                 account_info: true,
                 account_representative: true,
+                account_weight: true,
                 accounts_balances: true,
                 accounts_frontiers: true,
                 accounts_receivable: true,
                 accounts_representatives: true,
+                active_difficulty: true,
                 block_info: true,
                 blocks_info: true,
                 process: true,
@@ -293,10 +315,12 @@ pub fn default_rpcs() -> Vec<Rpc> {
                 account_history: true,
                 account_info: true,
                 account_representative: true,
+                account_weight: true,
                 accounts_balances: true,
                 accounts_frontiers: true,
                 accounts_receivable: false,
                 accounts_representatives: true,
+                active_difficulty: true,
                 block_info: true,
                 blocks_info: true,
                 process: true,
@@ -310,10 +334,12 @@ pub fn default_rpcs() -> Vec<Rpc> {
                 account_history: true,
                 account_info: true,
                 account_representative: true,
+                account_weight: true,
                 accounts_balances: true,
                 accounts_frontiers: true,
                 accounts_receivable: true,
                 accounts_representatives: true,
+                active_difficulty: true,
                 block_info: true,
                 blocks_info: true,
                 process: true,
@@ -327,10 +353,12 @@ pub fn default_rpcs() -> Vec<Rpc> {
                 account_history: true,
                 account_info: true,
                 account_representative: true,
+                account_weight: true,
                 accounts_balances: true,
                 accounts_frontiers: true,
                 accounts_receivable: false,
                 accounts_representatives: true,
+                active_difficulty: true,
                 block_info: true,
                 blocks_info: true,
                 process: true,
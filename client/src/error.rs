@@ -6,6 +6,7 @@ use aes_gcm::Error as AESError;
 use argon2::Error as Argon2Error;
 use bincode::Error as BincodeError;
 use hex::FromHexError;
+use serde_json::Error as JsonError;
 
 #[derive(Debug, Error)]
 pub enum ClientError {
@@ -17,10 +18,36 @@ pub enum ClientError {
     InvalidArguments,
     #[error("Invalid amount")]
     AmountBelowDustThreshold,
+    #[error("No matching receivable transactions found for {0} (try 'refresh' first)")]
+    NoMatchingReceivable(String),
+    #[error("Account frontier may be out of date (try 'refresh' first)")]
+    NeedsRefresh,
+    #[error("No unopened account found among the first {0} unused indexes (see 'DB_NUMBER_OF_ACCOUNTS_LIMIT')")]
+    NoFreshAccount(usize),
+    #[error("Notification does not match any camo payment in this wallet's history")]
+    UnknownNotification,
+    #[error("No account other than the sender can be the notifier (see 'STRICT_AUTO_NOTIFIER')")]
+    NoSeparateNotifier,
+    #[error("Contact labels must be alphanumeric, and cannot be account addresses")]
+    InvalidContactLabel,
+    #[error("A contact with the same label already exists")]
+    ContactAlreadyExists,
+    #[error("No matching contact found for '{0}'")]
+    ContactNotFound(String),
+    #[error("Invalid recipient or amount on line {0}")]
+    InvalidBatchLine(usize),
+    #[error("Failed to read or write file: {0}")]
+    FileError(#[from] std::io::Error),
     #[error("Invalid hex value: {0}")]
     InvalidHex(#[from] FromHexError),
     #[error("Error while serializing/deserializing data: {0}")]
     SerializationError(#[from] BincodeError),
+    #[error("Error while serializing/deserializing JSON: {0}")]
+    JsonError(#[from] JsonError),
+    #[error("Unsupported key derivation function '{0}' (try a newer version)")]
+    UnsupportedKeyDerivation(String),
+    #[error("Unsupported wallet data version {0} (try a newer version)")]
+    UnsupportedWalletVersion(u32),
     #[error("Error while deriving encryption key from password: {0}")]
     Argon2Error(Argon2Error),
     #[error("Error while encrypting/decrypting data: {0}")]
@@ -1,14 +1,93 @@
 use super::balance;
 use super::error::ClientError;
-use super::types::{Amount, CamoTxSummary};
-use super::types::{Hex32Bytes, ParsedAccount, ParsedCamoVersion};
-use super::WalletFrontend;
+use super::status;
+use super::storage::{self, KdfParams};
+use super::types::{
+    AccountOrContact, Amount, AmountSpec, BatchPayment, CamoTxSummary, MASKED_AMOUNT,
+};
+use super::types::{Hex32Bytes, ParsedAccount, ParsedCamoVersion, RescanStatus, SenderAccount};
+use super::{Client, WalletFrontend};
 use clap::{Args, Parser, Subcommand};
 use core_client::{
-    constants::CAMO_SENDER_DUST_THRESHOLD, rpc::RpcManager, Account, CamoAccount, CamoPayment,
-    CamoVersion, CamoVersions, CoreClientError, Notification, NotificationV1, Payment, Receivable,
+    constants::CAMO_SENDER_DUST_THRESHOLD,
+    frontiers::{FrontierInfo, NewFrontiers},
+    price::HttpPriceSource,
+    rpc::{get_current_time, Rpc, RpcCommands, RpcHealth, RpcLogEntry, RpcManager},
+    Account, Block, BlockType, CamoAccount, CamoPayment, CamoVersion, CamoVersions,
+    CoreClientError, Notification, NotificationV1, Payment, Receivable, RescanData,
 };
+use log::info;
+use serde_json::{json, Value as JsonValue};
 use std::cmp::{max, min};
+use std::fs;
+use std::path::Path;
+use std::slice;
+
+/// Refuse to send from accounts whose frontiers may be stale, unless `force` is set
+fn check_needs_refresh<Frontend: WalletFrontend>(
+    needs_refresh: &[Account],
+    accounts: &[&Account],
+    force: bool,
+) -> Result<(), ClientError> {
+    let stale: Vec<&&Account> = accounts
+        .iter()
+        .filter(|account| needs_refresh.contains(account))
+        .collect();
+    if stale.is_empty() {
+        return Ok(());
+    }
+
+    for account in stale {
+        Frontend::println(&format!(
+            "Warning: the frontier of {account} has not been downloaded since it was added"
+        ));
+    }
+    if force {
+        return Ok(());
+    }
+    Frontend::println("Use 'refresh' to update it, or --force to send anyway");
+    Err(ClientError::NeedsRefresh)
+}
+
+/// Wait for the frontiers of these accounts to be confirmed, printing whether or not they were
+async fn wait_for_confirmation<Frontend: WalletFrontend>(
+    client: &mut Client,
+    accounts: &[Account],
+) -> Result<(), ClientError> {
+    let hashes: Vec<[u8; 32]> = accounts
+        .iter()
+        .filter_map(|account| client.core.frontiers_db.account_frontier(account))
+        .map(|frontier| frontier.block.hash())
+        .collect();
+
+    Frontend::println("Waiting for confirmation...");
+    if client.core.wait_for_confirmation(&hashes).await? {
+        Frontend::println("Confirmed");
+    } else {
+        Frontend::println(&format!(
+            "Still pending after {}s",
+            client.core.config.CONFIRMATION_TIMEOUT_SECONDS
+        ));
+    }
+    Ok(())
+}
+
+/// Send a camo payment, updating the frontiers DB and activity counters
+async fn publish_camo_payment(
+    client: &mut Client,
+    payment: CamoPayment,
+) -> Result<(), ClientError> {
+    let (sender, notifier) = (payment.sender.clone(), payment.notifier.clone());
+    let success = client.core.send_camo(&mut client.work, payment).await?;
+
+    let frontiers = client.core.handle_rpc_success(success);
+    client.core.set_new_frontiers(frontiers);
+    client.record_send(&sender);
+    if notifier != sender {
+        client.record_send(&notifier);
+    }
+    Ok(())
+}
 
 fn notification_payload_bytes(notification: Notification) -> [u8; 32] {
     let Notification::V1(notification) = &notification;
@@ -21,12 +100,28 @@ fn notification_payload_bytes(notification: Notification) -> [u8; 32] {
 pub struct Command {
     #[clap(subcommand)]
     command: CommandType,
+    /// Output as machine-readable JSON, where supported
+    #[arg(short, long, global = true, default_value_t = false)]
+    json: bool,
+    /// Show balances and amounts, even in private mode
+    #[arg(long, global = true, default_value_t = false)]
+    reveal: bool,
 }
 impl Command {
     /// `Ok(true)` means continue looping, `Ok(false)` means exit.
     pub async fn execute<Frontend: WalletFrontend>(
         frontend: &mut Frontend,
         command: &str,
+    ) -> Result<bool, ClientError> {
+        let budget = frontend.client().core.config.RPC_COMMAND_RETRY_BUDGET;
+        RpcManager()
+            .with_retry_budget(budget, Command::_execute(frontend, command))
+            .await
+    }
+
+    async fn _execute<Frontend: WalletFrontend>(
+        frontend: &mut Frontend,
+        command: &str,
     ) -> Result<bool, ClientError> {
         frontend.client_mut().update_work_cache().await?;
 
@@ -39,22 +134,47 @@ impl Command {
             }
         };
 
+        let json = command.json;
+        let mask = frontend.private_mode() && !command.reveal;
         let result = match command.command {
             CommandType::RecoverNotification(args) => args.execute(frontend),
-            CommandType::AckNotification(args) => args.execute(frontend),
+            CommandType::AckNotification(args) => args.execute(frontend).await,
             CommandType::Account(args) => args.execute(frontend).await,
-            CommandType::Balance(args) => args.execute(frontend),
-            CommandType::CamoHistory(args) => args.execute(frontend),
+            CommandType::AccountDiff(args) => args.execute(frontend, json, mask).await,
+            CommandType::Accounts(args) => args.execute(frontend),
+            CommandType::Balance(args) => args.execute(frontend, json, mask).await,
+            CommandType::BatchSend(args) => args.execute(frontend).await,
+            CommandType::CamoHistory(args) => args.execute(frontend, json, mask),
+            CommandType::CamoRetry(args) => args.execute(frontend).await,
+            CommandType::CamoSweep(args) => args.execute(frontend).await,
             CommandType::Clear(args) => args.execute::<Frontend>(),
             CommandType::ClearCache(args) => args.execute(frontend).await,
+            CommandType::Consolidate(args) => args.execute(frontend).await,
+            CommandType::Contact(args) => args.execute(frontend),
+            CommandType::DeriveCamo(args) => args.execute(frontend),
+            CommandType::Export(args) => args.execute(frontend).await,
+            CommandType::Frontier(args) => args.execute(frontend, json, mask),
+            CommandType::History(args) => args.execute(frontend, json, mask).await,
             CommandType::Notify(args) => args.execute(frontend).await,
-            CommandType::Receive(args) => args.execute(frontend).await,
+            CommandType::PrivateKey(args) => args.execute(frontend),
+            CommandType::Receive(args) => args.execute(frontend, json).await,
             CommandType::Refresh(args) => args.execute(frontend).await,
             CommandType::Remove(args) => args.execute(frontend).await,
+            CommandType::Rep(args) => args.execute(frontend, json).await,
+            CommandType::Representative(args) => args.execute(frontend).await,
+            CommandType::Reps(args) => args.execute(frontend),
             CommandType::Rescan(args) => args.execute(frontend).await,
+            CommandType::Rpc(args) => args.execute(frontend).await,
             CommandType::Seed(args) => args.execute(frontend),
+            CommandType::Status(args) => args.execute(frontend, json, mask),
+            CommandType::Summary(args) => args.execute(frontend, json, mask),
             CommandType::Send(args) => args.execute(frontend).await,
             CommandType::SendCamo(args) => args.execute(frontend).await,
+            CommandType::Sweep(args) => args.execute(frontend).await,
+            CommandType::VerifyBackup(args) => args.execute(frontend),
+            CommandType::Watch(args) => args.execute(frontend).await,
+            CommandType::Weight(args) => args.execute(frontend).await,
+            CommandType::Work(args) => args.execute(frontend),
             CommandType::Quit(args) => args.execute(),
         }?;
 
@@ -73,33 +193,86 @@ enum CommandType {
     AckNotification(AckNotificationArgs),
     /// Get account at the specified index
     Account(AccountArgs),
+    /// Compare the locally stored frontier of a nano_ account with the network's, for debugging
+    #[clap(name = "account_diff", alias = "account-diff")]
+    AccountDiff(AccountDiffArgs),
+    /// List the accounts in this wallet, along with their activity
+    Accounts(AccountsArgs),
     /// Display wallet balance
     Balance(BalanceArgs),
+    /// Send coins to many normal Nano accounts, read from a file of `account,amount` lines
+    #[clap(name = "batch_send", alias = "batch-send")]
+    BatchSend(BatchSendArgs),
     /// Display send history of Camo transactions
     #[clap(name = "camo_history")]
     CamoHistory(CamoHistoryArgs),
+    /// Retry a Camo payment from the Camo history, e.g. after a network failure
+    #[clap(name = "camo_retry")]
+    CamoRetry(CamoRetryArgs),
+    /// Receive and send all funds of a Camo account's derived accounts to a normal Nano account
+    #[clap(name = "camo_sweep")]
+    CamoSweep(CamoSweepArgs),
     /// Clear the terminal
     Clear(ClearArgs),
     /// Clear the work cache
     #[clap(name = "clear_cache")]
     ClearCache(ClearCacheArgs),
+    /// Send the balances of a Camo account's derived accounts (minus 'RESERVE_RAW') to a normal Nano account
+    Consolidate(ConsolidateArgs),
+    /// Manage the address book
+    Contact(ContactArgs),
+    /// Display the camo_ account at an index without adding it to the wallet
+    #[clap(name = "derive_camo", alias = "derive-camo")]
+    DeriveCamo(DeriveCamoArgs),
+    /// Export the full transaction history of this wallet's nano_ accounts, e.g. for accounting
+    Export(ExportArgs),
+    /// Display the locally stored frontier block of a nano_ account, for debugging
+    Frontier(FrontierArgs),
+    /// Display the transaction history of nano_ accounts
+    History(HistoryArgs),
     /// Send a notification to a Camo account for a Camo payment
     Notify(NotifyArgs),
+    /// Show the private key of a single account
+    #[clap(name = "private_key", alias = "private-key")]
+    PrivateKey(PrivateKeyArgs),
     /// Receive transactions
     Receive(ReceiveArgs),
     /// Refresh the wallet
     Refresh(RefreshArgs),
     /// Stop tracking a Nano or Camo account
     Remove(RemoveArgs),
+    /// Download the current representative of any nano_ account
+    Rep(RepArgs),
+    /// Change the representative of this wallet's accounts
+    Representative(RepresentativeArgs),
+    /// List the representatives used by this wallet's accounts
+    Reps(RepsArgs),
     /// Rescan a Camo account for Camo payments
     Rescan(RescanArgs),
+    /// Manage and inspect the wallet's RPCs
+    Rpc(RpcArgs),
     /// Show the seed of this wallet
     Seed(SeedArgs),
+    /// Display a summary of the wallet's status
+    Status(StatusArgs),
+    /// Display the wallet's balance, unconfirmed sends, and receivable totals
+    Summary(SummaryArgs),
     /// Send coins to a normal Nano account
     Send(SendArgs),
     /// Send coins to a Camo account
     #[clap(name = "send_camo")]
     SendCamo(SendCamoArgs),
+    /// Send the full balance of one or more accounts to a normal Nano account
+    Sweep(SweepArgs),
+    /// Manage watch-only accounts, whose balances are tracked without their private keys
+    Watch(WatchArgs),
+    /// Check that the wallet can be encrypted and decrypted with its password, without writing to disk
+    #[clap(name = "verify_backup", alias = "verify-backup")]
+    VerifyBackup(VerifyBackupArgs),
+    /// Display the vote weight delegated by this wallet to each representative
+    Weight(WeightArgs),
+    /// Inspect and manage cached proof-of-work
+    Work(WorkArgs),
     /// Exit the program
     #[clap(alias = "exit")]
     Quit(QuitArgs),
@@ -139,31 +312,74 @@ struct AckNotificationArgs {
     recipient: CamoAccount,
     /// Camo transaction notification
     notification: Hex32Bytes,
+    /// Immediately download the derived account's frontier and receivable transactions
+    #[arg(short, long, default_value_t = false)]
+    download: bool,
 }
 impl AckNotificationArgs {
-    fn execute<Frontend: WalletFrontend>(
+    async fn execute<Frontend: WalletFrontend>(
         self,
         frontend: &mut Frontend,
     ) -> Result<bool, ClientError> {
-        let client = &mut frontend.client_mut().core;
-        let seed = &client.seed;
-        if let Some(info) = client.wallet_db.camo_account_db.get_info(&self.recipient) {
-            let notification = NotificationV1 {
-                recipient: self.recipient.signer_account(),
-                representative_payload: Account::from_bytes(self.notification.0)?,
-            };
-            let (_, info) = seed.derive_key(info, &Notification::V1(notification));
-            client.wallet_db.derived_account_db.insert(info);
+        let client = frontend.client_mut();
+        let core_client = &mut client.core;
+        let seed = &core_client.seed;
+        let info = match core_client
+            .wallet_db
+            .camo_account_db
+            .get_info(&self.recipient)
+        {
+            Some(info) => info,
+            None => {
+                Frontend::println(&format!(
+                    "We must know the private key for {}",
+                    self.recipient
+                ));
+                return Err(CoreClientError::AccountNotFound.into());
+            }
+        };
 
-            Frontend::println("Done");
-            Ok(true)
-        } else {
+        let notification = NotificationV1 {
+            recipient: self.recipient.signer_account(),
+            representative_payload: Account::from_bytes(self.notification.0)?,
+        };
+        let (key, info) = seed.derive_key(info, &Notification::V1(notification));
+        core_client.wallet_db.derived_account_db.insert(info);
+
+        if self.download {
+            let account = key.to_account();
+            let accounts = [account.clone()];
+            Frontend::println(&format!("Downloading {account}..."));
+
+            let frontiers = core_client.download_frontiers(&accounts).await?;
+            let frontiers = core_client.handle_rpc_success(frontiers);
+            core_client.set_new_frontiers(frontiers);
+
+            let receivables = core_client.download_receivable(&accounts).await?;
+            let receivables = core_client.handle_rpc_success(receivables).receivable;
+            client.remove_receivable(&account);
+            client.insert_receivable(receivables);
+
+            let balance = client
+                .core
+                .frontiers_db
+                .account_balance(&account)
+                .unwrap_or(0);
+            let receivable: u128 = client
+                .receivable
+                .values()
+                .filter(|receivable| receivable.recipient == account)
+                .map(|receivable| receivable.amount)
+                .sum();
             Frontend::println(&format!(
-                "We must know the private key for {}",
-                self.recipient
+                "{account}: {} Nano (+ {} Nano receivable)",
+                Amount::from(balance),
+                Amount::from(receivable)
             ));
-            Err(CoreClientError::AccountNotFound.into())
         }
+
+        Frontend::println("Done");
+        Ok(true)
     }
 }
 
@@ -200,6 +416,7 @@ impl AccountArgs {
                 .wallet_db
                 .camo_account_db
                 .insert(&core_client.config, info)?;
+            client.mark_needs_refresh(key.to_camo_account().signer_account());
             key.to_camo_account().to_string()
         } else {
             if self.versions.is_some() {
@@ -211,12 +428,20 @@ impl AccountArgs {
                 .wallet_db
                 .account_db
                 .insert(&core_client.config, info)?;
+            client.mark_needs_refresh(key.to_account());
             key.to_account().to_string()
         };
 
+        let core_client = &mut client.core;
         let downloaded = core_client.download_unknown_frontiers().await?;
         let downloaded = core_client.handle_rpc_success(downloaded);
+        let accounts: Vec<Account> = downloaded
+            .new
+            .iter()
+            .map(|frontier| frontier.block.account.clone())
+            .collect();
         core_client.set_new_frontiers(downloaded);
+        client.clear_needs_refresh(&accounts);
 
         Frontend::println(&string);
         Ok(true)
@@ -224,10 +449,122 @@ impl AccountArgs {
 }
 
 #[derive(Debug, Args)]
-struct BalanceArgs {}
-impl BalanceArgs {
+struct AccountDiffArgs {
+    /// nano_ account to compare
+    account: Account,
+}
+impl AccountDiffArgs {
+    /// The compared fields of a frontier: hash, balance and representative
+    fn fields(frontier: Option<&FrontierInfo>, mask: bool) -> [String; 3] {
+        match frontier {
+            None => ["unknown".into(), "unknown".into(), "unknown".into()],
+            Some(frontier) if frontier.is_unopened() => [
+                "unopened".into(),
+                Amount::from(0).display(mask),
+                "none".into(),
+            ],
+            Some(frontier) => [
+                hex::encode_upper(frontier.block.hash()),
+                Amount::from(frontier.block.balance).display(mask),
+                frontier.block.representative.to_string(),
+            ],
+        }
+    }
+
+    async fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+        json: bool,
+        mask: bool,
+    ) -> Result<bool, ClientError> {
+        let core_client = &mut frontend.client_mut().core;
+        let downloaded = core_client
+            .download_frontiers(slice::from_ref(&self.account))
+            .await?;
+        let downloaded = core_client.handle_rpc_success(downloaded);
+        let local = core_client.frontiers_db.account_frontier(&self.account);
+        // the block is only downloaded if it differs from the local frontier
+        let network = match downloaded.new.first() {
+            Some(frontier) => Some(frontier),
+            None => local,
+        };
+
+        let local = Self::fields(local, mask);
+        let network = Self::fields(network, mask);
+        let names = ["hash", "balance", "representative"];
+
+        if json {
+            let fields: Vec<JsonValue> = names
+                .iter()
+                .zip(local.iter().zip(&network))
+                .map(|(name, (local, network))| {
+                    json!({
+                        "field": name,
+                        "local": local,
+                        "network": network,
+                        "differs": local != network,
+                    })
+                })
+                .collect();
+            Frontend::println_json(&json!({
+                "account": self.account.to_string(),
+                "fields": fields,
+            }));
+            return Ok(true);
+        }
+
+        Frontend::println(&format!("  {:<16}{:<67}network", "", "local"));
+        for (name, (local, network)) in names.iter().zip(local.iter().zip(&network)) {
+            let marker = if local != network { '*' } else { ' ' };
+            Frontend::println(&format!("{marker} {name:<16}{local:<67}{network}"));
+        }
+        if local == network {
+            Frontend::println("The local frontier matches the network");
+        } else {
+            Frontend::println(
+                "Fields marked with * differ: use 'refresh' to update the local frontier",
+            );
+        }
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct AccountsArgs {}
+impl AccountsArgs {
     fn execute<Frontend: WalletFrontend>(self, frontend: &Frontend) -> Result<bool, ClientError> {
-        balance::execute(frontend)?;
+        balance::execute_accounts(frontend)?;
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct BalanceArgs {
+    /// Also show the approximate value of each balance in this currency, e.g. 'usd' (see 'PRICE_API_URL')
+    #[arg(long)]
+    fiat: Option<String>,
+}
+impl BalanceArgs {
+    async fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+        json: bool,
+        mask: bool,
+    ) -> Result<bool, ClientError> {
+        // if the price can't be downloaded, only Nano balances are shown
+        let price = match &self.fiat {
+            Some(currency) => {
+                let client = frontend.client_mut();
+                let source = HttpPriceSource::from_config(&client.core.config);
+                client
+                    .prices
+                    .get(&client.core.config, &source, currency)
+                    .await
+            }
+            None => None,
+        };
+        let fiat = self.fiat.as_deref().zip(price);
+        balance::execute(frontend, json, mask, fiat)?;
         Ok(true)
     }
 }
@@ -245,6 +582,8 @@ impl CamoHistoryArgs {
     fn execute<Frontend: WalletFrontend>(
         self,
         frontend: &mut Frontend,
+        json: bool,
+        mask: bool,
     ) -> Result<bool, ClientError> {
         let client = frontend.client_mut();
         if self.clear {
@@ -252,13 +591,268 @@ impl CamoHistoryArgs {
             return Ok(true);
         }
 
+        if json {
+            let history: Vec<JsonValue> = client
+                .camo_history
+                .iter()
+                .take(self.count)
+                .enumerate()
+                .map(|(index, payment)| {
+                    json!({
+                        "index": index,
+                        "recipient": payment.recipient.to_string(),
+                        "camo_amount": payment.camo_amount.to_string(),
+                        "total_amount": payment.total_amount.to_string(),
+                        "notification": hex::encode(payment.notification),
+                        "sender": payment.sender.as_ref().map(|sender| sender.to_string()),
+                        "notifier": payment.notifier.as_ref().map(|notifier| notifier.to_string()),
+                    })
+                })
+                .collect();
+            Frontend::println_json(&history.into());
+            return Ok(true);
+        }
+
         for (i, payment) in client.camo_history.iter().enumerate() {
             if i == self.count {
                 break;
             }
-            Frontend::println(&payment.to_string());
+            Frontend::println(&format!("#{i}: {}", payment.display(mask)));
+        }
+
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct CamoRetryArgs {
+    /// Index of the payment in the Camo history (see 'camo_history')
+    index: usize,
+    /// Retry even if the sender's frontier has changed since the payment was recorded,
+    /// which means that the original payment may have been (partially) sent
+    #[arg(short, long, default_value_t = false)]
+    force: bool,
+}
+impl CamoRetryArgs {
+    async fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+    ) -> Result<bool, ClientError> {
+        let client = frontend.client_mut();
+        let summary = client
+            .camo_history
+            .get(self.index)
+            .ok_or(ClientError::InvalidArguments)?;
+        let (Some(sender), Some(notifier)) = (summary.sender.clone(), summary.notifier.clone())
+        else {
+            Frontend::println(
+                "This payment was recorded by an older version, and can't be retried",
+            );
+            return Err(ClientError::InvalidArguments);
+        };
+        let payment = CamoPayment {
+            sender,
+            sender_amount: summary.camo_amount,
+            notifier,
+            notification_amount: summary.total_amount - summary.camo_amount,
+            recipient: summary.recipient.clone(),
+        };
+        Frontend::println(&summary.to_string());
+
+        // the notification depends on the sender's frontier,
+        // so it only changes if a block was published by the sender
+        let (_, notification) = client.core.camo_transaction_memo(&payment)?;
+        let notification = notification_payload_bytes(notification);
+        if notification != summary.notification {
+            Frontend::println(
+                "Warning: the sender's frontier has changed, so this payment may have already been sent",
+            );
+            if !self.force {
+                Frontend::println("Check the sender's history, then use --force to send anyway");
+                return Err(ClientError::InvalidArguments);
+            }
+        }
+        check_needs_refresh::<Frontend>(
+            &client.needs_refresh,
+            &[&payment.sender, &payment.notifier],
+            self.force,
+        )?;
+        client.camo_history[self.index].notification = notification;
+
+        Frontend::println("Sending...");
+        publish_camo_payment(client, payment).await?;
+        Frontend::println("Done");
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct CamoSweepArgs {
+    /// The camo_ account to sweep
+    camo_account: CamoAccount,
+    /// Destination nano_ account
+    destination: Account,
+}
+impl CamoSweepArgs {
+    async fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+    ) -> Result<bool, ClientError> {
+        let client = frontend.client_mut();
+        if !client
+            .core
+            .wallet_db
+            .contains_camo_account(&self.camo_account)
+        {
+            Frontend::println(&format!(
+                "We must know the private key for {}",
+                self.camo_account
+            ));
+            return Err(CoreClientError::AccountNotFound.into());
+        }
+
+        let derived = client
+            .core
+            .get_derived_accounts_from_master(&self.camo_account);
+        if derived.contains(&self.destination) {
+            Frontend::println("The destination cannot be one of the swept accounts");
+            return Err(ClientError::InvalidArguments);
+        }
+
+        let receivables = client.take_receivable(&derived);
+        if !receivables.is_empty() {
+            Frontend::println(&format!("Receiving {} transactions...", receivables.len()));
+            client.receive_transactions(receivables).await?;
+        }
+        sweep_accounts::<Frontend>(client, &derived, &self.destination, 0).await?;
+        Ok(true)
+    }
+}
+
+/// Send the balance of each account, minus `RESERVE_RAW`, to `destination`, one block per account,
+/// and print the total moved.
+///
+/// Accounts with nothing to send (see `CoreClient::sweepable_balance()`),
+/// or with less than `min_balance` to send, are skipped.
+/// Blocks are sent in batches of `RPC_RECEIVE_TRANSACTIONS_BATCH_SIZE`,
+/// and accounts which fail to send are reported without stopping the sweep.
+async fn sweep_accounts<Frontend: WalletFrontend>(
+    client: &mut Client,
+    accounts: &[Account],
+    destination: &Account,
+    min_balance: u128,
+) -> Result<(), ClientError> {
+    client.core.update_confirmed().await?;
+    let accounts: Vec<&Account> = accounts
+        .iter()
+        .filter(|account| *account != destination)
+        .filter(|account| {
+            client
+                .core
+                .sweepable_balance(account)
+                .is_some_and(|balance| balance >= min_balance)
+        })
+        .collect();
+
+    let mut total: u128 = 0;
+    let mut n_swept: usize = 0;
+    let mut n_failed: usize = 0;
+    let batch_size = client
+        .core
+        .config
+        .RPC_RECEIVE_TRANSACTIONS_BATCH_SIZE
+        .max(1);
+    for batch in accounts.chunks(batch_size) {
+        // request work for the whole batch at once, so that it is generated concurrently
+        for account in batch {
+            if let Some(frontier) = client.core.frontiers_db.account_frontier(account) {
+                if !frontier.has_valid_work(&client.core.config) {
+                    client
+                        .work
+                        .request_work(&client.core.config, frontier.work_hash(), None);
+                }
+            }
+        }
+
+        for &account in batch {
+            let Some(balance) = client.core.sweepable_balance(account) else {
+                continue;
+            };
+            Frontend::println(&format!(
+                "Sending {} Nano from {account}...",
+                Amount::from(balance)
+            ));
+            let payment = Payment {
+                sender: account.clone(),
+                amount: balance,
+                recipient: destination.clone(),
+                new_representative: None,
+            };
+            match client.core.send(&mut client.work, payment, None).await {
+                Ok(success) => {
+                    let frontiers = client.core.handle_rpc_success(success);
+                    client.core.set_new_frontiers(frontiers);
+                    client.record_send(account);
+                    total += balance;
+                    n_swept += 1;
+                }
+                Err(err) => {
+                    Frontend::println(&format!("Failed to send from {account}: {err}"));
+                    n_failed += 1;
+                }
+            }
         }
+    }
+
+    Frontend::println(&format!(
+        "Swept {} Nano ({total} raw) from {n_swept} accounts",
+        Amount::from(total)
+    ));
+    if n_failed > 0 {
+        Frontend::println(&format!(
+            "{n_failed} accounts failed: try 'refresh', then run this command again"
+        ));
+    }
+    Ok(())
+}
+
+/// Sweep the derived accounts of `camo_account` into `destination`, as with `sweep_accounts()`
+async fn consolidate_camo_account<Frontend: WalletFrontend>(
+    client: &mut Client,
+    camo_account: &CamoAccount,
+    destination: &Account,
+    min_balance: u128,
+) -> Result<(), ClientError> {
+    let derived = client.core.get_derived_accounts_from_master(camo_account);
+    sweep_accounts::<Frontend>(client, &derived, destination, min_balance).await
+}
 
+#[derive(Debug, Args)]
+struct ConsolidateArgs {
+    /// The camo_ account whose derived accounts to consolidate
+    camo_account: CamoAccount,
+    /// Destination nano_ account, or the label of a contact
+    destination: AccountOrContact<Account>,
+}
+impl ConsolidateArgs {
+    async fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+    ) -> Result<bool, ClientError> {
+        let client = frontend.client_mut();
+        let destination = self.destination.resolve(&client.address_book)?;
+        if !client
+            .core
+            .wallet_db
+            .contains_camo_account(&self.camo_account)
+        {
+            Frontend::println(&format!(
+                "We must know the private key for {}",
+                self.camo_account
+            ));
+            return Err(CoreClientError::AccountNotFound.into());
+        }
+        consolidate_camo_account::<Frontend>(client, &self.camo_account, &destination, 0).await?;
         Ok(true)
     }
 }
@@ -309,120 +903,799 @@ impl ClearCacheArgs {
 }
 
 #[derive(Debug, Args)]
-struct NotifyArgs {
-    /// Notifier nano_ account
-    notifier: Account,
-    /// Recipient camo_ account
-    recipient: CamoAccount,
-    /// The notification to send, encoded as a 64-character hex string (see 'camo_history')
-    notification: Hex32Bytes,
-    /// Amount of Nano that the notifier account should send
-    #[arg(short, long, default_value_t = Amount::from(CAMO_SENDER_DUST_THRESHOLD))]
-    amount: Amount,
+struct ContactArgs {
+    #[command(subcommand)]
+    command: ContactCommandType,
 }
-impl NotifyArgs {
-    async fn execute<Frontend: WalletFrontend>(
+impl ContactArgs {
+    fn execute<Frontend: WalletFrontend>(
         self,
         frontend: &mut Frontend,
     ) -> Result<bool, ClientError> {
-        let client = frontend.client_mut();
-        let work_client = &mut client.work;
-        let core_client = &mut client.core;
-
-        if self.amount.value < CAMO_SENDER_DUST_THRESHOLD {
-            return Err(ClientError::AmountBelowDustThreshold);
+        match self.command {
+            ContactCommandType::Add(args) => args.execute(frontend),
+            ContactCommandType::Remove(args) => args.execute(frontend),
+            ContactCommandType::List(args) => args.execute(frontend),
         }
-
-        let payment = Payment {
-            sender: self.notifier,
-            amount: self.amount.into(),
-            recipient: self.recipient.signer_account(),
-            new_representative: Some(Account::from_bytes(self.notification.0)?),
-        };
-        Frontend::println("Sending...");
-        let success = core_client.send(work_client, payment).await?;
-
-        let frontiers = core_client.handle_rpc_success(success);
-        core_client.set_new_frontiers(frontiers);
-        Frontend::println("Done");
-        Ok(true)
     }
 }
 
+#[derive(Debug, Subcommand)]
+enum ContactCommandType {
+    /// Add a labelled nano_ or camo_ account to the address book
+    Add(Box<ContactAddArgs>),
+    /// Remove a contact from the address book
+    Remove(ContactRemoveArgs),
+    /// List the contacts in the address book
+    List(ContactListArgs),
+}
+
 #[derive(Debug, Args)]
-struct ReceiveArgs {
-    /// List receivable transactions (default behavior)
-    #[arg(short, long, conflicts_with = "blocks", conflicts_with = "accounts")]
-    list: bool,
-    /// The block hashes to receive
-    #[arg(short, long, conflicts_with = "accounts", conflicts_with = "list")]
-    blocks: Vec<Hex32Bytes>,
-    /// The accounts to receive transactions on
-    #[arg(short, long, conflicts_with = "blocks", conflicts_with = "list")]
-    accounts: Vec<Account>,
+struct ContactAddArgs {
+    /// Alphanumeric label, which can be used in place of the account
+    label: String,
+    /// nano_ or camo_ account
+    account: ParsedAccount,
 }
-impl ReceiveArgs {
-    async fn execute<Frontend: WalletFrontend>(
+impl ContactAddArgs {
+    fn execute<Frontend: WalletFrontend>(
         self,
         frontend: &mut Frontend,
     ) -> Result<bool, ClientError> {
-        let client = frontend.client_mut();
-        let work_client = &mut client.work;
-        let core_client = &mut client.core;
-        let cached_receivable = &mut client.receivable;
-
-        let receivables: Vec<Receivable> = if !self.blocks.is_empty() {
-            self.blocks
-                .into_iter()
-                .map(|receivable| cached_receivable.remove(&receivable.0))
-                .collect::<Option<Vec<Receivable>>>()
-                .ok_or(CoreClientError::AccountNotFound)?
-        } else if !self.accounts.is_empty() {
-            cached_receivable
-                .iter()
-                .filter(|receivable| self.accounts.contains(&receivable.1.recipient))
-                .map(|receivable| receivable.0)
-                .cloned()
-                .collect::<Vec<[u8; 32]>>()
-                .into_iter()
-                .map(|receivable| cached_receivable.remove(&receivable))
-                .collect::<Option<Vec<Receivable>>>()
-                .ok_or(CoreClientError::AccountNotFound)?
-        } else {
-            let mut receivables: Vec<&Receivable> = cached_receivable.values().collect();
-            receivables.sort_by(|a, b| b.amount.cmp(&a.amount));
-            if receivables.is_empty() {
-                Frontend::println("No transactions to receive.");
-            } else {
-                Frontend::println(
-                    "Specify which transactions to receive by account (-a) or block (-b):",
-                );
-            }
-            for receivable in receivables {
-                Frontend::println(&format!(
-                    "{}: {} ({} Nano)",
-                    receivable.recipient,
-                    hex::encode_upper(receivable.block_hash),
-                    Amount::from(receivable.amount)
-                ));
-            }
-            return Ok(true);
-        };
-
-        Frontend::println("Receiving...");
-        let result = core_client.receive(work_client, receivables).await;
-        let frontiers = core_client.handle_rpc_success(result.successes);
-        core_client.set_new_frontiers(frontiers);
-
-        let (return_value, unreceived) = if let Err(err) = result.failures {
-            (Err(err.err.into()), err.unreceived)
-        } else {
-            (Ok(true), vec![])
-        };
-
-        client.insert_receivable(unreceived);
+        let address_book = &mut frontend.client_mut().address_book;
+        address_book.add(self.label, self.account)?;
         Frontend::println("Done");
-        return_value
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct ContactRemoveArgs {
+    /// Label of the contact
+    label: String,
+}
+impl ContactRemoveArgs {
+    fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+    ) -> Result<bool, ClientError> {
+        let address_book = &mut frontend.client_mut().address_book;
+        address_book.remove(&self.label)?;
+        Frontend::println("Done");
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct ContactListArgs {}
+impl ContactListArgs {
+    fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+    ) -> Result<bool, ClientError> {
+        let contacts = frontend.client().address_book.contacts();
+        if contacts.is_empty() {
+            Frontend::println("No contacts");
+        }
+        for contact in contacts {
+            Frontend::println(&format!("{}: {}", contact.label, contact.account));
+        }
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct DeriveCamoArgs {
+    index: u32,
+    /// Which Camo protocol versions to support.
+    /// A reasonable default will be used if no value is given.
+    #[arg(short, long, hide = true)]
+    versions: Option<Vec<ParsedCamoVersion>>,
+}
+impl DeriveCamoArgs {
+    fn execute<Frontend: WalletFrontend>(self, frontend: &Frontend) -> Result<bool, ClientError> {
+        let core_client = &frontend.client().core;
+
+        let mut versions = core_client.config.DEFAULT_CAMO_VERSIONS.clone();
+        if let Some(v) = self.versions {
+            versions = v.iter().map(|v| v.0).collect::<Vec<CamoVersion>>()
+        }
+        let (key, _) = core_client
+            .seed
+            .get_camo_key(self.index, CamoVersions::new(&versions))
+            .ok_or(ClientError::InvalidArguments)?;
+        let camo_account = key.to_camo_account();
+
+        Frontend::println(&camo_account.to_string());
+        Frontend::println(&format!(
+            "Notification account: {}",
+            camo_account.signer_account()
+        ));
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct ExportArgs {
+    /// CSV file to write (overwritten if it exists)
+    #[arg(long)]
+    csv: String,
+}
+impl ExportArgs {
+    async fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+    ) -> Result<bool, ClientError> {
+        Frontend::println("Downloading account histories...");
+        let path = Path::new(&self.csv);
+        let n_blocks = storage::export_history_csv(frontend.client_mut(), path).await?;
+        Frontend::println(&format!("Exported {n_blocks} blocks to {}", self.csv));
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct FrontierArgs {
+    /// nano_ account tracked by this wallet
+    account: Account,
+}
+impl FrontierArgs {
+    fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &Frontend,
+        json: bool,
+        mask: bool,
+    ) -> Result<bool, ClientError> {
+        let core_client = &frontend.client().core;
+        let Some(frontier) = core_client.frontiers_db.account_frontier(&self.account) else {
+            Frontend::println(&format!(
+                "The frontier of {} is not known (try 'refresh' first)",
+                self.account
+            ));
+            return Err(CoreClientError::AccountNotFound.into());
+        };
+        let block = &frontier.block;
+        let cached_work = frontier.cached_work().map(hex::encode_upper);
+        let valid_work = frontier.has_valid_work(&core_client.config);
+
+        if json {
+            Frontend::println_json(&json!({
+                "account": block.account.to_string(),
+                "unopened": frontier.is_unopened(),
+                "hash": hex::encode_upper(block.hash()),
+                "type": block.block_type.to_string(),
+                "previous": hex::encode_upper(block.previous),
+                "representative": block.representative.to_string(),
+                "balance": block.balance.to_string(),
+                "link": hex::encode_upper(block.link),
+                "signature": hex::encode_upper(block.signature.to_bytes()),
+                "work": hex::encode_upper(block.work),
+                "cached_work": cached_work,
+                "cached_work_valid": valid_work,
+            }));
+            return Ok(true);
+        }
+
+        if frontier.is_unopened() {
+            Frontend::println(&format!("{} is unopened", block.account));
+        }
+        let balance = match mask {
+            true => MASKED_AMOUNT.into(),
+            false => block.balance.to_string(),
+        };
+        let cached_work = match (cached_work, valid_work) {
+            (Some(work), true) => format!("{work} (valid)"),
+            (Some(work), false) => format!("{work} (does not meet the current difficulty)"),
+            (None, _) => "none".into(),
+        };
+        for (field, value) in [
+            ("hash", hex::encode_upper(block.hash())),
+            ("type", block.block_type.to_string()),
+            ("account", block.account.to_string()),
+            ("previous", hex::encode_upper(block.previous)),
+            ("representative", block.representative.to_string()),
+            ("balance", format!("{balance} raw")),
+            ("link", hex::encode_upper(block.link)),
+            ("signature", hex::encode_upper(block.signature.to_bytes())),
+            ("work", hex::encode_upper(block.work)),
+            ("cached work", cached_work),
+        ] {
+            Frontend::println(&format!("{field}: {value}"));
+        }
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct HistoryArgs {
+    /// nano_ account (defaults to all accounts in this wallet)
+    account: Option<Account>,
+    /// The maximum number of blocks to display per account
+    #[arg(short, long, default_value_t = 10)]
+    count: usize,
+}
+impl HistoryArgs {
+    /// `previous_balance` is `None` if the balance before this block is unknown
+    fn block_delta(block: &Block, previous_balance: Option<u128>, mask: bool) -> String {
+        match previous_balance {
+            Some(previous) if block.balance >= previous => {
+                format!("+{}", Amount::from(block.balance - previous).display(mask))
+            }
+            Some(previous) => format!("-{}", Amount::from(previous - block.balance).display(mask)),
+            None => "?".into(),
+        }
+    }
+
+    /// The balance before `history[i]`, where `history` is ordered from most recent,
+    /// or `None` if it is unknown
+    fn previous_balance(history: &[Block], i: usize) -> Option<u128> {
+        match history.get(i + 1) {
+            Some(previous) => Some(previous.balance),
+            // the first block of an account has no previous balance
+            None if history[i].previous == [0; 32] => Some(0),
+            None => None,
+        }
+    }
+
+    fn display_block<Frontend: WalletFrontend>(
+        block: &Block,
+        previous_balance: Option<u128>,
+        mask: bool,
+    ) {
+        let hash = hex::encode_upper(block.hash());
+        let delta = Self::block_delta(block, previous_balance, mask);
+        let counterparty = match block.block_type {
+            BlockType::Send => match Account::from_bytes(block.link) {
+                Ok(recipient) => format!("to {recipient}"),
+                Err(_) => "to an invalid account".into(),
+            },
+            BlockType::Receive => format!("from block {}", hex::encode_upper(block.link)),
+            _ => format!("representative {}", block.representative),
+        };
+        Frontend::println(&format!(
+            "{:?} {delta} Nano {counterparty} ({hash})",
+            block.block_type
+        ));
+    }
+
+    /// Amounts are in raw. `amount` is `null` if the balance before this block is unknown.
+    fn block_json(block: &Block, previous_balance: Option<u128>) -> JsonValue {
+        let amount = previous_balance.map(|previous| block.balance.abs_diff(previous).to_string());
+        json!({
+            "type": format!("{:?}", block.block_type).to_lowercase(),
+            "hash": hex::encode_upper(block.hash()),
+            "balance": block.balance.to_string(),
+            "amount": amount,
+            "link": hex::encode_upper(block.link),
+            "representative": block.representative.to_string(),
+        })
+    }
+
+    async fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+        json: bool,
+        mask: bool,
+    ) -> Result<bool, ClientError> {
+        let core_client = &mut frontend.client_mut().core;
+        let accounts = match self.account {
+            Some(account) => vec![account],
+            None => core_client.wallet_db.all_nano_accounts(),
+        };
+
+        let mut json_histories: Vec<JsonValue> = vec![];
+        for account in accounts {
+            // download one extra block, so that the amount of the oldest displayed block is known
+            let history = RpcManager()
+                .account_history(&core_client.config, &account, self.count + 1, None, None)
+                .await;
+            let history = match history {
+                Ok(success) => core_client.handle_rpc_success(success),
+                Err(err) if json => {
+                    json_histories.push(json!({
+                        "account": account.to_string(),
+                        "error": err.to_string(),
+                    }));
+                    continue;
+                }
+                Err(err) => {
+                    Frontend::println(&format!("Failed to download history of {account}: {err}"));
+                    continue;
+                }
+            };
+            if history.is_empty() {
+                continue;
+            }
+
+            if !json {
+                Frontend::println(&format!("{account}:"));
+            }
+            let mut json_blocks: Vec<JsonValue> = vec![];
+            for (i, block) in history.iter().take(self.count).enumerate() {
+                let previous_balance = Self::previous_balance(&history, i);
+                match json {
+                    true => json_blocks.push(Self::block_json(block, previous_balance)),
+                    false => Self::display_block::<Frontend>(block, previous_balance, mask),
+                }
+            }
+            if json {
+                json_histories.push(json!({
+                    "account": account.to_string(),
+                    "blocks": json_blocks,
+                }));
+            }
+        }
+
+        if json {
+            Frontend::println_json(&json_histories.into());
+        }
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct NotifyArgs {
+    /// Notifier nano_ account
+    notifier: Account,
+    /// Recipient camo_ account
+    recipient: CamoAccount,
+    /// The notification to send, encoded as a 64-character hex string (see 'camo_history')
+    notification: Hex32Bytes,
+    /// Amount of Nano that the notifier account should send
+    #[arg(short, long, default_value_t = Amount::from(CAMO_SENDER_DUST_THRESHOLD))]
+    amount: Amount,
+    /// Refuse to send the notification if it does not match any payment in 'camo_history'
+    #[arg(long)]
+    strict: bool,
+}
+impl NotifyArgs {
+    async fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+    ) -> Result<bool, ClientError> {
+        let client = frontend.client_mut();
+        let work_client = &mut client.work;
+        let core_client = &mut client.core;
+
+        if self.amount.value < CAMO_SENDER_DUST_THRESHOLD {
+            return Err(ClientError::AmountBelowDustThreshold);
+        }
+
+        // a typo would most likely result in an invalid curve point, but check anyway
+        let notification = Account::from_bytes(self.notification.0)
+            .inspect_err(|_| Frontend::println("The notification is not a valid payload"))?;
+
+        let known = client.camo_history.iter().any(|summary| {
+            summary.recipient == self.recipient && summary.notification == self.notification.0
+        });
+        if !known {
+            Frontend::println(&format!(
+                "Warning: this notification does not correspond to any payment to {} in 'camo_history'",
+                self.recipient
+            ));
+            if self.strict {
+                return Err(ClientError::UnknownNotification);
+            }
+        }
+
+        let payment = Payment {
+            sender: self.notifier.clone(),
+            amount: self.amount.into(),
+            recipient: self.recipient.signer_account(),
+            new_representative: Some(notification),
+        };
+        Frontend::println("Sending...");
+        let success = core_client.send(work_client, payment, None).await?;
+
+        let frontiers = core_client.handle_rpc_success(success);
+        core_client.set_new_frontiers(frontiers);
+        client.record_send(&self.notifier);
+        Frontend::println("Done");
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct PrivateKeyArgs {
+    /// The nano_ account (normal, derived, or notification account)
+    account: Account,
+}
+impl PrivateKeyArgs {
+    fn execute<Frontend: WalletFrontend>(self, frontend: &Frontend) -> Result<bool, ClientError> {
+        let core_client = &frontend.client().core;
+        let key = match core_client
+            .wallet_db
+            .find_key(&core_client.seed, &self.account)
+        {
+            Some(key) => key,
+            None => {
+                Frontend::println(&format!(
+                    "We must know the private key for {}",
+                    self.account
+                ));
+                return Err(CoreClientError::AccountNotFound.into());
+            }
+        };
+
+        Frontend::println(
+            "WARNING: anyone who knows this key can spend all funds in this account.",
+        );
+        Frontend::println("WARNING: never share it, and only enter it into software you trust.");
+        frontend.authenticate()?;
+        Frontend::println(&hex::encode_upper(key.as_bytes()));
+        Frontend::println(
+            "Note: this is the account's expanded (scalar) key, which is not accepted by all wallets",
+        );
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct ReceiveArgs {
+    /// List receivable transactions (default behavior)
+    #[arg(short, long, conflicts_with = "blocks", conflicts_with = "accounts")]
+    list: bool,
+    /// The block hashes to receive
+    #[arg(short, long, conflicts_with = "accounts", conflicts_with = "list")]
+    blocks: Vec<Hex32Bytes>,
+    /// The accounts to receive transactions on
+    #[arg(short, long, conflicts_with = "blocks", conflicts_with = "list")]
+    accounts: Vec<Account>,
+    /// Receive every cached receivable transaction, largest first (see 'RECEIVE_INDEX_RANGE')
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with = "blocks",
+        conflicts_with = "accounts",
+        conflicts_with = "list"
+    )]
+    all: bool,
+    /// Split each large transaction (see 'RECEIVE_SPLIT_THRESHOLD') across this many accounts,
+    /// each with a different representative.
+    /// Every extra account costs a send and a receive block (and their proof-of-work).
+    #[arg(short, long, conflicts_with = "list")]
+    split: Option<usize>,
+    /// Wait for the receive blocks to be confirmed (see 'CONFIRMATION_TIMEOUT_SECONDS')
+    #[arg(short, long, default_value_t = false, conflicts_with = "list")]
+    wait: bool,
+}
+impl ReceiveArgs {
+    /// Find the lowest-index account which is neither in the wallet nor opened, and add it to the wallet.
+    /// Only the first `DB_NUMBER_OF_ACCOUNTS_LIMIT` unused indexes are checked.
+    async fn fresh_account(client: &mut Client) -> Result<Account, ClientError> {
+        let core_client = &mut client.core;
+        let window = core_client.config.DB_NUMBER_OF_ACCOUNTS_LIMIT;
+        let candidates: Vec<_> = (0..u32::MAX)
+            .filter(|index| !core_client.wallet_db.account_db.contains_index(*index))
+            .take(window)
+            .map(|index| core_client.seed.get_key(index))
+            .collect();
+        let accounts: Vec<Account> = candidates.iter().map(|(key, _)| key.to_account()).collect();
+
+        let downloaded = core_client.download_frontiers(&accounts).await?;
+        let downloaded = core_client.handle_rpc_success(downloaded);
+        let fresh = candidates.into_iter().find(|(key, _)| {
+            let account = key.to_account();
+            downloaded
+                .new
+                .iter()
+                .filter(|frontier| frontier.block.account == account)
+                .all(|frontier| frontier.is_unopened())
+        });
+        let Some((key, info)) = fresh else {
+            return Err(ClientError::NoFreshAccount(window));
+        };
+
+        let account = key.to_account();
+        core_client
+            .wallet_db
+            .account_db
+            .insert(&core_client.config, info)?;
+        let frontiers = NewFrontiers {
+            new: downloaded
+                .new
+                .into_iter()
+                .filter(|frontier| frontier.block.account == account)
+                .collect(),
+        };
+        core_client.set_new_frontiers(frontiers);
+        Ok(account)
+    }
+
+    /// Move equal shares of `amount` from `account` to fresh accounts, each with a different representative
+    async fn split<Frontend: WalletFrontend>(
+        client: &mut Client,
+        account: &Account,
+        amount: u128,
+        parts: usize,
+    ) -> Result<(), ClientError> {
+        let current_representative = client
+            .core
+            .frontiers_db
+            .account_frontier(account)
+            .map(|frontier| frontier.block.representative.clone());
+        let mut representatives: Vec<Account> = client
+            .core
+            .config
+            .voting_representatives()
+            .iter()
+            .filter(|representative| Some(*representative) != current_representative.as_ref())
+            .cloned()
+            .collect();
+        representatives.truncate(parts.saturating_sub(1));
+        if representatives.len() + 1 < parts {
+            Frontend::println(&format!(
+                "Not enough representatives: splitting into {} parts",
+                representatives.len() + 1
+            ));
+        }
+        let share = amount / (representatives.len() as u128 + 1);
+
+        for representative in representatives {
+            let fresh = Self::fresh_account(client).await?;
+
+            let payment = Payment {
+                sender: account.clone(),
+                amount: share,
+                recipient: fresh.clone(),
+                new_representative: None,
+            };
+            let success = client.core.send(&mut client.work, payment, None).await?;
+            let frontiers = client.core.handle_rpc_success(success);
+            let send_hash = frontiers
+                .new
+                .first()
+                .map(|frontier| frontier.block.hash())
+                .ok_or(CoreClientError::AccountNotFound)?;
+            client.core.set_new_frontiers(frontiers);
+            client.record_send(account);
+
+            let receivable = Receivable {
+                recipient: fresh.clone(),
+                block_hash: send_hash,
+                amount: share,
+            };
+            let received = client
+                .core
+                .receive_block(&mut client.work, &receivable, Some(representative.clone()))
+                .await;
+            let success = match received {
+                Ok(success) => success,
+                Err(err) => {
+                    // the send was already published: keep track of the funds so that they can be received later
+                    Frontend::println(&format!(
+                        "Sent {} Nano to {fresh}, but failed to receive it: use 'receive' to try again",
+                        Amount::from(share)
+                    ));
+                    client.insert_receivable(vec![receivable]);
+                    return Err(err.into());
+                }
+            };
+            let frontiers = client.core.handle_rpc_success(success);
+            client.core.set_new_frontiers(frontiers);
+            client.record_receives(&fresh, 1);
+
+            Frontend::println(&format!(
+                "Moved {} Nano to {fresh} (representative: {representative})",
+                Amount::from(share)
+            ));
+        }
+        Ok(())
+    }
+
+    /// Consolidate the derived accounts of each camo account which received one of these transactions
+    /// (see 'AUTO_CONSOLIDATE_CAMO')
+    async fn auto_consolidate<Frontend: WalletFrontend>(
+        client: &mut Client,
+        recipients: &[(Account, [u8; 32], u128)],
+    ) -> Result<(), ClientError> {
+        let wallet_db = &client.core.wallet_db;
+        let mut camo_accounts: Vec<CamoAccount> = vec![];
+        for (account, _, _) in recipients {
+            let Some(info) = wallet_db.derived_account_db.get_info(account) else {
+                continue;
+            };
+            let Some(master) = wallet_db
+                .camo_account_db
+                .get_info_from_index(info.master_index)
+            else {
+                continue;
+            };
+            if !camo_accounts.contains(&master.account) {
+                camo_accounts.push(master.account.clone());
+            }
+        }
+
+        let min_balance = client.core.config.AUTO_CONSOLIDATE_CAMO_THRESHOLD;
+        for camo_account in camo_accounts {
+            let destination = match &client.core.config.AUTO_CONSOLIDATE_CAMO_DESTINATION {
+                Some(destination) => destination.clone(),
+                None => camo_account.signer_account(),
+            };
+            // e.g. every derived account is below the threshold, or holds no more than `RESERVE_RAW`
+            let eligible = client
+                .core
+                .get_derived_accounts_from_master(&camo_account)
+                .iter()
+                .filter(|account| *account != &destination)
+                .filter_map(|account| client.core.sweepable_balance(account))
+                .any(|balance| balance >= min_balance);
+            if !eligible {
+                continue;
+            }
+            Frontend::println(&format!(
+                "Consolidating {camo_account} into {destination}..."
+            ));
+            consolidate_camo_account::<Frontend>(client, &camo_account, &destination, min_balance)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+        json: bool,
+    ) -> Result<bool, ClientError> {
+        let client = frontend.client_mut();
+        let cached_receivable = &mut client.receivable;
+
+        let receivables: Vec<Receivable> = if !self.blocks.is_empty() {
+            let unmatched: Vec<String> = self
+                .blocks
+                .iter()
+                .filter(|block| !cached_receivable.contains_key(&block.0))
+                .map(|block| hex::encode_upper(block.0))
+                .collect();
+            if !unmatched.is_empty() {
+                return Err(ClientError::NoMatchingReceivable(unmatched.join(", ")));
+            }
+
+            self.blocks
+                .iter()
+                .filter_map(|block| cached_receivable.remove(&block.0))
+                .collect()
+        } else if !self.accounts.is_empty() {
+            let unmatched: Vec<String> = self
+                .accounts
+                .iter()
+                .filter(|account| {
+                    !cached_receivable
+                        .values()
+                        .any(|receivable| &receivable.recipient == *account)
+                })
+                .map(|account| account.to_string())
+                .collect();
+            if !unmatched.is_empty() {
+                return Err(ClientError::NoMatchingReceivable(unmatched.join(", ")));
+            }
+
+            client.take_receivable(&self.accounts)
+        } else if self.all {
+            // only accounts within 'RECEIVE_INDEX_RANGE'
+            let accounts = client
+                .core
+                .wallet_db
+                .nano_accounts_in_range(client.core.config.RECEIVE_INDEX_RANGE.as_ref());
+            let mut receivables = client.take_receivable(&accounts);
+            #[allow(clippy::unnecessary_sort_by)]
+            receivables.sort_by(|a, b| b.amount.cmp(&a.amount));
+            if receivables.is_empty() {
+                Frontend::println("No transactions to receive.");
+                return Ok(true);
+            }
+            receivables
+        } else {
+            let mut receivables: Vec<&Receivable> = cached_receivable.values().collect();
+            #[allow(clippy::unnecessary_sort_by)]
+            receivables.sort_by(|a, b| b.amount.cmp(&a.amount));
+            if json {
+                let receivables: Vec<JsonValue> = receivables
+                    .iter()
+                    .map(|receivable| {
+                        json!({
+                            "recipient": receivable.recipient.to_string(),
+                            "block_hash": hex::encode_upper(receivable.block_hash),
+                            "amount": receivable.amount.to_string(),
+                        })
+                    })
+                    .collect();
+                Frontend::println_json(&receivables.into());
+                return Ok(true);
+            }
+            if receivables.is_empty() {
+                Frontend::println("No transactions to receive.");
+            } else {
+                Frontend::println(
+                    "Specify which transactions to receive by account (-a) or block (-b), or use --all:",
+                );
+            }
+            for receivable in receivables {
+                Frontend::println(&format!(
+                    "{}: {} ({} Nano)",
+                    receivable.recipient,
+                    hex::encode_upper(receivable.block_hash),
+                    Amount::from(receivable.amount)
+                ));
+            }
+            return Ok(true);
+        };
+
+        let threshold = client.core.config.RECEIVE_SPLIT_THRESHOLD;
+        let to_split: Vec<(Account, [u8; 32], u128)> = match self.split {
+            Some(parts) if parts > 1 => receivables
+                .iter()
+                .filter(|receivable| receivable.amount >= threshold)
+                .filter(|receivable| {
+                    client
+                        .core
+                        .wallet_db
+                        .account_db
+                        .contains(&receivable.recipient)
+                })
+                .map(|receivable| {
+                    (
+                        receivable.recipient.clone(),
+                        receivable.block_hash,
+                        receivable.amount,
+                    )
+                })
+                .collect(),
+            _ => vec![],
+        };
+
+        let mut recipients: Vec<(Account, [u8; 32], u128)> = receivables
+            .iter()
+            .map(|receivable| {
+                (
+                    receivable.recipient.clone(),
+                    receivable.block_hash,
+                    receivable.amount,
+                )
+            })
+            .collect();
+        let attempted = recipients.len();
+
+        Frontend::println("Receiving...");
+        let result = client.receive_transactions(receivables).await;
+        // transactions which could not be received were returned to the cache
+        recipients.retain(|(_, block_hash, _)| !client.receivable.contains_key(block_hash));
+        if self.all {
+            let total: u128 = recipients.iter().map(|(_, _, amount)| amount).sum();
+            Frontend::println(&format!(
+                "Received {} Nano in {} of {attempted} transactions",
+                Amount::from(total),
+                recipients.len()
+            ));
+        }
+
+        for (account, block_hash, amount) in to_split {
+            // only split transactions which were actually received
+            if client.receivable.contains_key(&block_hash) {
+                continue;
+            }
+            Frontend::println(&format!(
+                "Splitting {} Nano received on {account}...",
+                Amount::from(amount)
+            ));
+            Self::split::<Frontend>(client, &account, amount, self.split.unwrap_or(1)).await?;
+        }
+
+        if client.core.config.AUTO_CONSOLIDATE_CAMO && !recipients.is_empty() {
+            Self::auto_consolidate::<Frontend>(client, &recipients).await?;
+        }
+
+        Frontend::println("Done");
+        if self.wait && !recipients.is_empty() {
+            let mut accounts: Vec<Account> = vec![];
+            for (account, _, _) in recipients {
+                if !accounts.contains(&account) {
+                    accounts.push(account);
+                }
+            }
+            wait_for_confirmation::<Frontend>(client, &accounts).await?;
+        }
+        result.map(|_| true)
     }
 }
 
@@ -436,21 +1709,56 @@ impl RefreshArgs {
         let client = frontend.client_mut();
         Frontend::println("Downloading receivable transactions...");
         let core_client = &mut client.core;
-        let accounts = core_client.wallet_db.all_nano_accounts();
+        let accounts = core_client
+            .wallet_db
+            .nano_accounts_in_range(core_client.config.RECEIVE_INDEX_RANGE.as_ref());
         let receivables = core_client.download_receivable(&accounts).await?;
-        let (receivables, infos) = core_client.handle_rpc_success(receivables);
+        let data = core_client.handle_rpc_success(receivables);
 
-        core_client.wallet_db.derived_account_db.insert_many(infos);
-        for account in &accounts {
-            client.remove_receivable(account);
+        core_client
+            .wallet_db
+            .derived_account_db
+            .insert_many(data.derived_info);
+        // keep the cached transactions of accounts which couldn't be checked
+        let checked: Vec<Account> = accounts
+            .iter()
+            .filter(|account| !data.unchecked.contains(account))
+            .cloned()
+            .collect();
+        let vanished = client.replace_receivable(&checked, data.receivable);
+        if client.core.config.REPORT_VANISHED_RECEIVABLE && !vanished.is_empty() {
+            Frontend::println(&format!(
+                "{} cached receivable transactions are no longer receivable (received elsewhere?):",
+                vanished.len()
+            ));
+            for receivable in vanished {
+                Frontend::println(&format!(
+                    "{}: {} ({} Nano)",
+                    receivable.recipient,
+                    hex::encode_upper(receivable.block_hash),
+                    Amount::from(receivable.amount)
+                ));
+            }
+        }
+        if !data.unchecked.is_empty() {
+            Frontend::println(&format!(
+                "{} derived accounts couldn't be checked for receivable transactions: retry 'refresh'",
+                data.unchecked.len()
+            ));
         }
-        client.insert_receivable(receivables);
 
         Frontend::println("Updating account frontiers...");
         let core_client = &mut client.core;
         let frontiers = core_client.download_frontiers(&accounts).await?;
         let frontiers = core_client.handle_rpc_success(frontiers);
         core_client.set_new_frontiers(frontiers);
+        client.clear_needs_refresh(&accounts);
+
+        let core_client = &mut client.core;
+        if core_client.config.CONFIRMATION_REQUIRED {
+            Frontend::println("Checking frontier confirmations...");
+            core_client.update_confirmed().await?;
+        }
 
         Frontend::println("Done");
         Ok(true)
@@ -480,61 +1788,361 @@ impl RemoveArgs {
     }
 }
 
+#[derive(Debug, Args)]
+struct RepArgs {
+    /// The nano_ account, or the label of a contact
+    account: AccountOrContact<Account>,
+}
+impl RepArgs {
+    async fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+        json: bool,
+    ) -> Result<bool, ClientError> {
+        let account = self.account.resolve(&frontend.client().address_book)?;
+        let core_client = &mut frontend.client_mut().core;
+        let representative = RpcManager()
+            .account_representative(&core_client.config, &account)
+            .await?;
+        let representative = core_client.handle_rpc_success(representative);
+
+        if json {
+            Frontend::println_json(&json!({
+                "account": account.to_string(),
+                "representative": representative.as_ref().map(|rep| rep.to_string()),
+            }));
+            return Ok(true);
+        }
+        match representative {
+            Some(representative) => Frontend::println(&representative.to_string()),
+            None => Frontend::println(&format!("{account} is unopened")),
+        }
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct RepresentativeArgs {
+    #[command(subcommand)]
+    command: RepresentativeCommandType,
+}
+impl RepresentativeArgs {
+    async fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+    ) -> Result<bool, ClientError> {
+        match self.command {
+            RepresentativeCommandType::Set(args) => args.execute(frontend).await,
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum RepresentativeCommandType {
+    /// Publish a change block setting the representative of one account, or of every opened account
+    Set(RepresentativeSetArgs),
+}
+
+#[derive(Debug, Args)]
+struct RepresentativeSetArgs {
+    /// The new representative (nano_ account, or the label of a contact)
+    representative: AccountOrContact<Account>,
+    /// The nano_ account to change the representative of
+    #[arg(short, long, required_unless_present = "all", conflicts_with = "all")]
+    account: Option<Account>,
+    /// Change the representative of every opened account in this wallet
+    #[arg(long, default_value_t = false)]
+    all: bool,
+}
+impl RepresentativeSetArgs {
+    async fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+    ) -> Result<bool, ClientError> {
+        let client = frontend.client_mut();
+        let representative = self.representative.resolve(&client.address_book)?;
+        let core_client = &client.core;
+
+        let accounts = match &self.account {
+            Some(account) => {
+                core_client
+                    .wallet_db
+                    .find_key(&core_client.seed, account)
+                    .ok_or(CoreClientError::AccountNotFound)?;
+                core_client.check_spendable(account)?;
+                vec![account.clone()]
+            }
+            None => core_client
+                .frontiers_db
+                .all_accounts()
+                .into_iter()
+                .filter(|account| {
+                    // skip watch-only, unopened and unconfirmed accounts
+                    core_client
+                        .wallet_db
+                        .find_key(&core_client.seed, account)
+                        .is_some()
+                        && core_client
+                            .frontiers_db
+                            .account_frontier(account)
+                            .is_some_and(|frontier| !frontier.is_unopened())
+                        && core_client.is_spendable(account)
+                })
+                .collect(),
+        };
+        let accounts: Vec<Account> = accounts
+            .into_iter()
+            .filter(|account| {
+                !core_client
+                    .frontiers_db
+                    .account_frontier(account)
+                    .is_some_and(|frontier| frontier.block.representative == representative)
+            })
+            .collect();
+        if accounts.is_empty() {
+            Frontend::println(&format!(
+                "No accounts to change: already represented by {representative}"
+            ));
+            return Ok(true);
+        }
+
+        let mut n_changed: usize = 0;
+        let mut n_work: usize = 0;
+        let mut n_failed: usize = 0;
+        let batch_size = client
+            .core
+            .config
+            .RPC_RECEIVE_TRANSACTIONS_BATCH_SIZE
+            .max(1);
+        for batch in accounts.chunks(batch_size) {
+            // request work for the whole batch at once, so that it is generated concurrently
+            for account in batch {
+                if let Some(frontier) = client.core.frontiers_db.account_frontier(account) {
+                    if !frontier.has_valid_work_for(&client.core.config, &BlockType::Change) {
+                        client
+                            .work
+                            .request_work(&client.core.config, frontier.work_hash(), None);
+                        n_work += 1;
+                    }
+                }
+            }
+
+            for account in batch {
+                Frontend::println(&format!("Changing the representative of {account}..."));
+                let result = client
+                    .core
+                    .change(&mut client.work, account, representative.clone())
+                    .await;
+                match result {
+                    Ok(success) => {
+                        let frontiers = client.core.handle_rpc_success(success);
+                        client.core.set_new_frontiers(frontiers);
+                        n_changed += 1;
+                    }
+                    Err(err) if self.all => {
+                        Frontend::println(&format!("Failed to change {account}: {err}"));
+                        n_failed += 1;
+                    }
+                    Err(err) => return Err(err.into()),
+                }
+            }
+        }
+
+        Frontend::println(&format!(
+            "Changed the representative of {n_changed} accounts to {representative} (generated work for {n_work} blocks)"
+        ));
+        if n_failed > 0 {
+            Frontend::println(&format!(
+                "{n_failed} accounts failed: try 'refresh', then run this command again"
+            ));
+        }
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct RepsArgs {
+    /// Flag representatives with at least this much Nano delegated from this wallet
+    #[arg(short, long)]
+    threshold: Option<Amount>,
+}
+impl RepsArgs {
+    fn execute<Frontend: WalletFrontend>(self, frontend: &Frontend) -> Result<bool, ClientError> {
+        balance::execute_representatives(frontend, self.threshold.map(u128::from))?;
+        Ok(true)
+    }
+}
+
 #[derive(Debug, Args)]
 struct RescanArgs {
     /// The camo_ account to rescan
     account: CamoAccount,
-    /// The block to use as the starting point (default is the account's frontier)
+    /// The block to use as the starting point (default is where the last rescan ended, or the account's frontier)
     #[arg(short, long)]
     head: Option<Hex32Bytes>,
+    /// Forget where the last rescan ended, and start from the account's frontier
+    #[arg(long, default_value_t = false)]
+    restart: bool,
+    /// Keep scanning until the start of the account's history is reached, instead of scanning one batch
+    #[arg(short, long, default_value_t = false)]
+    all: bool,
     /// Do not filter worthless accounts ("worthless" means 0 balance or pending transactions)
     #[arg(short = 'f', long, default_value_t = false)]
     no_filter: bool,
+    /// Download and display the balance, receivable amount and latest blocks of each account found
+    #[arg(short, long, default_value_t = false)]
+    detail: bool,
+    /// Re-download the account history, even if it was cached by a previous rescan
+    #[arg(long, default_value_t = false)]
+    no_cache: bool,
+    /// Log the (non-secret) inputs and outputs of each derivation, for troubleshooting
+    #[arg(long, default_value_t = false)]
+    trace: bool,
+    /// Check and save the accounts found this many at a time, to bound memory use during very large rescans
+    #[arg(long, value_name = "BATCH_SIZE")]
+    stream: Option<usize>,
 }
 impl RescanArgs {
+    /// Number of blocks displayed per account with `--detail`
+    const DETAIL_HISTORY_COUNT: usize = 5;
+
+    async fn display_detail<Frontend: WalletFrontend>(
+        client: &mut Client,
+        accounts: &[Account],
+    ) -> Result<(), ClientError> {
+        if accounts.is_empty() {
+            return Ok(());
+        }
+        Frontend::println(&format!(
+            "Downloading the history of {} accounts...",
+            accounts.len()
+        ));
+        // download one extra block, so that the amount of the oldest displayed block is known
+        let histories = client
+            .core
+            .download_derived_histories(accounts, Self::DETAIL_HISTORY_COUNT + 1)
+            .await?;
+        let histories = client.core.handle_rpc_success(histories);
+
+        let mut total: u128 = 0;
+        for (account, history) in accounts.iter().zip(&histories) {
+            let balance = history.first().map(|block| block.balance).unwrap_or(0);
+            let receivable: u128 = client
+                .receivable
+                .values()
+                .filter(|receivable| &receivable.recipient == account)
+                .map(|receivable| receivable.amount)
+                .sum();
+            total += balance + receivable;
+
+            Frontend::println(&format!(
+                "{account}: {} Nano (+ {} Nano receivable)",
+                Amount::from(balance),
+                Amount::from(receivable)
+            ));
+            for (i, block) in history.iter().take(Self::DETAIL_HISTORY_COUNT).enumerate() {
+                let previous_balance = HistoryArgs::previous_balance(history, i);
+                HistoryArgs::display_block::<Frontend>(block, previous_balance, false);
+            }
+        }
+        Frontend::println(&format!(
+            "Found {} Nano in {} accounts",
+            Amount::from(total),
+            accounts.len()
+        ));
+        Ok(())
+    }
+
     async fn execute<Frontend: WalletFrontend>(
         self,
         frontend: &mut Frontend,
     ) -> Result<bool, ClientError> {
         let client = frontend.client_mut();
-        let core_client = &mut client.core;
-
         let filter = !self.no_filter;
         let account = self.account.signer_account();
 
+        if self.restart {
+            client.set_rescan_head(&self.account, None);
+        }
+        let saved_head = client.rescan_head(&self.account);
+        if let (None, Some(saved_head)) = (&self.head, saved_head) {
+            Frontend::println(&format!(
+                "Resuming from block: {} (use '--restart' to start from the frontier)",
+                hex::encode(saved_head)
+            ));
+        }
+
+        let core_client = &mut client.core;
         let db_head = core_client
             .frontiers_db
             .account_frontier(&account)
             .map(|frontier| frontier.block.hash());
-        let head = self.head.map(|head| head.0).or(db_head);
+        let head = self.head.map(|head| head.0).or(saved_head).or(db_head);
 
         if let Some(head) = head {
             let batch_size = core_client.config.RPC_ACCOUNT_HISTORY_BATCH_SIZE;
 
-            let head_info_success = RpcManager().block_info(&core_client.config, head).await?;
-            let (head_info, mut rpc_failures) = head_info_success.into();
+            let head_info = RpcManager().block_info(&core_client.config, head).await?;
+            let head_info = core_client.handle_rpc_success(head_info);
             let head_height = head_info.map(|info| info.height).unwrap_or(0);
 
-            let bottom_height = head_height.saturating_sub(batch_size);
+            let to_scan = match self.all {
+                true => head_height,
+                false => min(head_height, batch_size),
+            };
             Frontend::println(&format!(
-                "Scanning {} blocks ({} -> {})...",
-                min(head_height, batch_size),
-                head_height,
-                bottom_height
+                "Scanning {to_scan} blocks ({head_height} -> {})...",
+                head_height - to_scan
             ));
-            let (rescan, rescan_rpc_failures) = core_client
-                .rescan_notifications_partial(&self.account, Some(head), None, filter)
-                .await?
-                .into();
-            rpc_failures.merge_with(rescan_rpc_failures);
-
-            if let Some(head) = rescan.new_head {
-                if head != [0; 32] {
-                    Frontend::println(&format!("Ended on block: {}", hex::encode(head)));
+            let mut found: Vec<Account> = vec![];
+            let progress = |rescan: &RescanData, status: &RescanStatus| {
+                if self.detail {
+                    found.extend(rescan.derived_info.iter().map(|info| info.account.clone()));
                 }
-            }
+                if self.all || self.stream.is_some() {
+                    Frontend::println(&format!(
+                        "Scanned {}/{to_scan} blocks, found {} accounts",
+                        status.blocks_scanned, status.accounts_found
+                    ));
+                }
+                self.all
+            };
+            let status = match self.stream {
+                Some(batch_size) => {
+                    Client::rescan_streamed(
+                        frontend,
+                        &self.account,
+                        head,
+                        filter,
+                        !self.no_cache,
+                        self.trace,
+                        batch_size,
+                        progress,
+                    )
+                    .await?
+                }
+                None => {
+                    frontend
+                        .client_mut()
+                        .rescan(
+                            &self.account,
+                            head,
+                            filter,
+                            !self.no_cache,
+                            self.trace,
+                            progress,
+                        )
+                        .await?
+                }
+            };
 
-            client.handle_rescan(rescan);
+            if self.detail {
+                Self::display_detail::<Frontend>(frontend.client_mut(), &found).await?;
+            }
+            match status.head {
+                Some(head) => Frontend::println(&format!("Ended on block: {}", hex::encode(head))),
+                None => Frontend::println("Reached the start of the account's history"),
+            }
         } else {
             Frontend::println("No blocks to scan. Maybe refresh?");
         }
@@ -544,11 +2152,298 @@ impl RescanArgs {
 }
 
 #[derive(Debug, Args)]
-struct SeedArgs {}
+struct RpcArgs {
+    #[command(subcommand)]
+    command: RpcCommandType,
+}
+impl RpcArgs {
+    async fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+    ) -> Result<bool, ClientError> {
+        match self.command {
+            RpcCommandType::Health(args) => args.execute(frontend).await,
+            RpcCommandType::Add(args) => args.execute(frontend),
+            RpcCommandType::Remove(args) => args.execute(frontend),
+            RpcCommandType::List(args) => args.execute(frontend),
+            RpcCommandType::Last(args) => args.execute(frontend),
+            RpcCommandType::Log(args) => args.execute(frontend),
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum RpcCommandType {
+    /// Probe each RPC with lightweight requests, and report which commands work
+    Health(RpcHealthArgs),
+    /// Add an RPC, with all commands enabled
+    Add(RpcAddArgs),
+    /// Remove an RPC
+    Remove(RpcRemoveArgs),
+    /// List the wallet's RPCs, along with their supported commands
+    List(RpcListArgs),
+    /// Display which RPC most recently served each command successfully
+    Last(RpcLastArgs),
+    /// Display recent RPC requests (see 'RPC_LOG_SIZE')
+    Log(RpcLogArgs),
+}
+
+#[derive(Debug, Args)]
+struct RpcHealthArgs {
+    /// Disable any commands which failed their probe
+    #[arg(short, long, default_value_t = false)]
+    fix: bool,
+}
+impl RpcHealthArgs {
+    async fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+    ) -> Result<bool, ClientError> {
+        let config = &mut frontend.client_mut().core.config;
+        if config.RPCS.is_empty() {
+            Frontend::println("No RPCs");
+            return Ok(true);
+        }
+
+        Frontend::println("Probing RPCs...");
+        let health = RpcManager().health(config).await?;
+        for rpc_health in &health {
+            let status = match (rpc_health.is_healthy(), rpc_health.average_latency()) {
+                (true, Some(latency)) => format!("ok ({} ms)", latency.as_millis()),
+                (true, None) => "no commands to probe".into(),
+                (false, _) => "failing".into(),
+            };
+            Frontend::println(&format!("{}: {status}", rpc_health.url));
+            for probe in &rpc_health.probes {
+                let result = match &probe.error {
+                    Some(err) => format!("error: {err}"),
+                    None => "ok".into(),
+                };
+                Frontend::println(&format!(
+                    "\t{}: {result} ({} ms)",
+                    probe.command,
+                    probe.latency.as_millis()
+                ));
+            }
+        }
+
+        let unhealthy: Vec<&RpcHealth> = health
+            .iter()
+            .filter(|rpc_health| !rpc_health.is_healthy())
+            .collect();
+        if unhealthy.is_empty() {
+            return Ok(true);
+        }
+        if !self.fix {
+            Frontend::println("Use --fix to disable the failing commands");
+            return Ok(true);
+        }
+        for rpc_health in unhealthy {
+            if let Some(rpc) = config
+                .RPCS
+                .iter_mut()
+                .find(|rpc| rpc.get_url() == rpc_health.url)
+            {
+                rpc.commands = rpc_health.corrected_commands(&rpc.commands);
+            }
+        }
+        Frontend::println("Disabled the failing commands");
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct RpcAddArgs {
+    /// URL of the RPC
+    url: String,
+}
+impl RpcAddArgs {
+    fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+    ) -> Result<bool, ClientError> {
+        let config = &mut frontend.client_mut().core.config;
+        if config.RPCS.iter().any(|rpc| rpc.get_url() == self.url) {
+            Frontend::println(&format!("{} is already in use", self.url));
+            return Err(ClientError::InvalidArguments);
+        }
+
+        config
+            .RPCS
+            .push(Rpc::new(RpcCommands::all(), &self.url, None)?);
+        Frontend::println("Done");
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct RpcRemoveArgs {
+    /// URL of the RPC
+    url: String,
+}
+impl RpcRemoveArgs {
+    fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+    ) -> Result<bool, ClientError> {
+        let config = &mut frontend.client_mut().core.config;
+        let Some(position) = config.RPCS.iter().position(|rpc| rpc.get_url() == self.url) else {
+            Frontend::println(&format!("{} is not in use", self.url));
+            return Err(ClientError::InvalidArguments);
+        };
+
+        config.RPCS.remove(position);
+        if config.RPC_PINNED.iter().any(|pin| pin.url == self.url) {
+            Frontend::println(&format!(
+                "Warning: {} is still pinned in 'RPC_PINNED'",
+                self.url
+            ));
+        }
+        if config.RPCS.is_empty() {
+            Frontend::println("Warning: no RPCs are left");
+        }
+        Frontend::println("Done");
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct RpcListArgs {}
+impl RpcListArgs {
+    fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+    ) -> Result<bool, ClientError> {
+        let config = &frontend.client().core.config;
+        let rpcs = &config.RPCS;
+        if rpcs.is_empty() {
+            Frontend::println("No RPCs");
+            return Ok(true);
+        }
+
+        let current_time = get_current_time();
+        for rpc in rpcs {
+            let status = match rpc.is_banned(current_time) {
+                true => format!(
+                    "banned for {}s",
+                    rpc.banned_until.saturating_sub(current_time)
+                ),
+                false => "ok".into(),
+            };
+            Frontend::println(&format!("{}: {status}", rpc.get_url()));
+            Frontend::println(&format!("\t{}", rpc.commands.supported().join(", ")));
+            Frontend::println(&format!("\tbatch size limit: {}", rpc.max_batch_size));
+        }
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct RpcLastArgs {}
+impl RpcLastArgs {
+    fn execute<Frontend: WalletFrontend>(self, _: &Frontend) -> Result<bool, ClientError> {
+        let mut last: Vec<RpcLogEntry> = vec![];
+        for entry in RpcManager().log().into_iter().rev() {
+            if !entry.is_success() || last.iter().any(|last| last.command == entry.command) {
+                continue;
+            }
+            last.push(entry);
+        }
+        if last.is_empty() {
+            Frontend::println("No successful RPC requests have been made");
+            return Ok(true);
+        }
+
+        let current_time = get_current_time();
+        last.sort_by(|a, b| a.command.cmp(&b.command));
+        for entry in last {
+            Frontend::println(&format!(
+                "{}: {} ({} ms, {}s ago)",
+                entry.command,
+                entry.url,
+                entry.latency.as_millis(),
+                current_time.saturating_sub(entry.timestamp)
+            ));
+        }
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct RpcLogArgs {
+    /// Number of requests to display
+    #[arg(short, long, default_value_t = 20)]
+    count: usize,
+}
+impl RpcLogArgs {
+    fn execute<Frontend: WalletFrontend>(self, _: &Frontend) -> Result<bool, ClientError> {
+        let log = RpcManager().log();
+        if log.is_empty() {
+            Frontend::println("No RPC requests have been made");
+            return Ok(true);
+        }
+
+        let current_time = get_current_time();
+        let skip = log.len().saturating_sub(self.count);
+        for entry in log.into_iter().skip(skip) {
+            let result = match &entry.error {
+                Some(err) => format!("error: {err}"),
+                None => "ok".to_string(),
+            };
+            Frontend::println(&format!(
+                "{}s ago: {} via {} ({} ms): {result}",
+                current_time.saturating_sub(entry.timestamp),
+                entry.command,
+                entry.url,
+                entry.latency.as_millis()
+            ));
+        }
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct SeedArgs {
+    /// Display the seed as a 24-word mnemonic
+    #[arg(short, long, default_value_t = false)]
+    mnemonic: bool,
+}
 impl SeedArgs {
     fn execute<Frontend: WalletFrontend>(self, frontend: &Frontend) -> Result<bool, ClientError> {
         frontend.authenticate()?;
-        Frontend::println(&frontend.client().core.seed.as_hex().to_string());
+        let seed = &frontend.client().core.seed;
+        match self.mnemonic {
+            true => Frontend::println(&seed.as_mnemonic()),
+            false => Frontend::println(&seed.as_hex()),
+        }
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct StatusArgs {}
+impl StatusArgs {
+    fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &Frontend,
+        json: bool,
+        mask: bool,
+    ) -> Result<bool, ClientError> {
+        status::execute(frontend, json, mask)?;
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct SummaryArgs {}
+impl SummaryArgs {
+    fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &Frontend,
+        json: bool,
+        mask: bool,
+    ) -> Result<bool, ClientError> {
+        balance::execute_summary(frontend, json, mask)?;
         Ok(true)
     }
 }
@@ -556,14 +2451,23 @@ impl SeedArgs {
 #[derive(Debug, Args)]
 struct SendArgs {
     /// Sender nano_ account (use 'any' to automatically select one)
-    sender: Account,
-    /// Amount of Nano to send to the recipient
-    amount: Amount,
-    /// Recipient nano_ account
-    recipient: Account,
+    sender: SenderAccount,
+    /// Amount of Nano to send to the recipient (use 'all' to send the sender's full balance)
+    amount: AmountSpec,
+    /// Recipient nano_ account, or the label of a contact
+    recipient: AccountOrContact<Account>,
     /// Set a new representative account
     #[arg(short, long)]
     representative: Option<Account>,
+    /// Generate stronger proof-of-work for faster confirmation (see 'PRIORITY_WORK_DIFFICULTY')
+    #[arg(short, long, default_value_t = false)]
+    priority: bool,
+    /// Send even if the sender's frontier may be out of date
+    #[arg(short, long, default_value_t = false)]
+    force: bool,
+    /// Wait for the send block to be confirmed (see 'CONFIRMATION_TIMEOUT_SECONDS')
+    #[arg(short, long, default_value_t = false)]
+    wait: bool,
 }
 impl SendArgs {
     async fn execute<Frontend: WalletFrontend>(
@@ -571,21 +2475,207 @@ impl SendArgs {
         frontend: &mut Frontend,
     ) -> Result<bool, ClientError> {
         let client = frontend.client_mut();
+        let recipient = self.recipient.resolve(&client.address_book)?;
+        client.core.update_confirmed().await?;
+        let sender = match (self.sender.0, &self.amount) {
+            (Some(sender), _) => sender,
+            (None, AmountSpec::Max) => {
+                Frontend::println("'all' cannot be used with an automatically selected sender");
+                return Err(ClientError::InvalidArguments);
+            }
+            (None, AmountSpec::Exact(amount)) => {
+                // accounts are sorted by balance, low to high
+                let required = amount.value.saturating_add(client.core.config.RESERVE_RAW);
+                let auto_selected = client
+                    .core
+                    .accounts_with_balance(required, slice::from_ref(&recipient));
+                let sender = match auto_selected.first() {
+                    Some(info) => info.block.account.clone(),
+                    None => return Err(CoreClientError::NotEnoughCoins.into()),
+                };
+                Frontend::println(&format!("Automatically selected {sender} as sender"));
+                sender
+            }
+        };
+        if client.core.wallet_db.is_watch_only(&sender) {
+            Frontend::println(&format!(
+                "{sender} is watch-only: its private key is not known"
+            ));
+            return Err(CoreClientError::AccountNotFound.into());
+        }
+        check_needs_refresh::<Frontend>(&client.needs_refresh, &[&sender], self.force)?;
+        client.core.check_spendable(&sender)?;
         let work_client = &mut client.work;
         let core_client = &mut client.core;
 
+        let amount = self.amount.resolve(
+            core_client.frontiers_db.account_balance(&sender),
+            core_client.config.RESERVE_RAW,
+        )?;
         let payment = Payment {
-            sender: self.sender,
-            amount: self.amount.into(),
-            recipient: self.recipient,
+            sender: sender.clone(),
+            amount,
+            recipient,
             new_representative: self.representative,
         };
+        let custom_difficulty = self
+            .priority
+            .then(|| core_client.config.PRIORITY_WORK_DIFFICULTY.to_be_bytes());
+
         Frontend::println("Sending...");
-        let success = core_client.send(work_client, payment).await?;
+        let success = core_client
+            .send(work_client, payment, custom_difficulty)
+            .await?;
 
         let frontiers = core_client.handle_rpc_success(success);
         core_client.set_new_frontiers(frontiers);
+        client.record_send(&sender);
         Frontend::println("Done");
+        if self.wait {
+            wait_for_confirmation::<Frontend>(client, slice::from_ref(&sender)).await?;
+        }
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct BatchSendArgs {
+    /// Sender nano_ account
+    sender: Account,
+    /// CSV (or TSV) file of `account,amount` lines
+    file: String,
+    /// Send even if the sender's frontier may be out of date
+    #[arg(short, long, default_value_t = false)]
+    force: bool,
+}
+impl BatchSendArgs {
+    async fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+    ) -> Result<bool, ClientError> {
+        let payments = BatchPayment::parse_file(&fs::read_to_string(&self.file)?)?;
+        if payments.is_empty() {
+            Frontend::println("No payments found");
+            return Ok(true);
+        }
+
+        let client = frontend.client_mut();
+        let sender = self.sender;
+        client.core.update_confirmed().await?;
+        if client.core.wallet_db.is_watch_only(&sender) {
+            Frontend::println(&format!(
+                "{sender} is watch-only: its private key is not known"
+            ));
+            return Err(CoreClientError::AccountNotFound.into());
+        }
+        check_needs_refresh::<Frontend>(&client.needs_refresh, &[&sender], self.force)?;
+        client.core.check_spendable(&sender)?;
+
+        let total = payments.iter().try_fold(0_u128, |total, payment| {
+            total.checked_add(payment.amount.value)
+        });
+        let balance = client.core.frontiers_db.account_balance(&sender);
+        if total.is_none() || total > balance {
+            return Err(CoreClientError::NotEnoughCoins.into());
+        }
+
+        let mut sent: Vec<(usize, [u8; 32])> = vec![];
+        for (i, payment) in payments.iter().enumerate() {
+            Frontend::println(&format!(
+                "[{}/{}] Sending {} Nano to {}...",
+                i + 1,
+                payments.len(),
+                payment.amount,
+                payment.recipient
+            ));
+            let core_payment = Payment {
+                sender: sender.clone(),
+                amount: payment.amount.value,
+                recipient: payment.recipient.clone(),
+                new_representative: None,
+            };
+            match client.core.send(&mut client.work, core_payment, None).await {
+                Ok(success) => {
+                    let frontiers = client.core.handle_rpc_success(success);
+                    let hash = frontiers.new.first().map(|frontier| frontier.block.hash());
+                    client.core.set_new_frontiers(frontiers);
+                    client.record_send(&sender);
+                    sent.extend(hash.map(|hash| (payment.line, hash)));
+                }
+                Err(err) => {
+                    Frontend::println(&format!("Failed to send line {}: {err}", payment.line));
+                    if !sent.is_empty() {
+                        Frontend::println("Already sent:");
+                    }
+                    for (line, hash) in &sent {
+                        Frontend::println(&format!("line {line}: {}", hex::encode_upper(hash)));
+                    }
+                    return Err(err.into());
+                }
+            }
+        }
+
+        Frontend::println(&format!(
+            "Sent {} Nano in {} payments",
+            Amount::from(total.unwrap_or(0)),
+            sent.len()
+        ));
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct SweepArgs {
+    /// Destination nano_ account, or the label of a contact
+    destination: AccountOrContact<Account>,
+    /// nano_ accounts to sweep
+    #[arg(short, long, required_unless_present_any = ["all", "camo"])]
+    source: Vec<Account>,
+    /// Sweep the derived accounts of these camo_ accounts
+    #[arg(short, long)]
+    camo: Vec<CamoAccount>,
+    /// Sweep every account in the wallet, including the derived accounts of camo_ accounts
+    #[arg(
+        short,
+        long,
+        default_value_t = false,
+        conflicts_with = "source",
+        conflicts_with = "camo"
+    )]
+    all: bool,
+}
+impl SweepArgs {
+    async fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+    ) -> Result<bool, ClientError> {
+        let client = frontend.client_mut();
+        let destination = self.destination.resolve(&client.address_book)?;
+
+        let spendable = client.core.wallet_db.spendable_nano_accounts();
+        if let Some(account) = self
+            .source
+            .iter()
+            .find(|account| !spendable.contains(account))
+        {
+            Frontend::println(&format!("We must know the private key for {account}"));
+            return Err(CoreClientError::AccountNotFound.into());
+        }
+        let mut accounts = match self.all {
+            true => spendable,
+            false => self.source,
+        };
+        for camo_account in &self.camo {
+            if !client.core.wallet_db.contains_camo_account(camo_account) {
+                Frontend::println(&format!("We must know the private key for {camo_account}"));
+                return Err(CoreClientError::AccountNotFound.into());
+            }
+            accounts.extend(client.core.get_derived_accounts_from_master(camo_account));
+        }
+
+        let refs: Vec<&Account> = accounts.iter().collect();
+        check_needs_refresh::<Frontend>(&client.needs_refresh, &refs, false)?;
+        sweep_accounts::<Frontend>(client, &accounts, &destination, 0).await?;
         Ok(true)
     }
 }
@@ -594,10 +2684,10 @@ impl SendArgs {
 struct SendCamoArgs {
     /// Sender nano_ account
     sender: Account,
-    /// Total amount of Nano to send to the recipient
-    amount: Amount,
-    /// Recipient camo_ account
-    recipient: CamoAccount,
+    /// Total amount of Nano to send to the recipient (use 'all' to send the sender's full balance)
+    amount: AmountSpec,
+    /// Recipient camo_ account, or the label of a contact
+    recipient: AccountOrContact<CamoAccount>,
     /// Automatically choose a notifier account and notification amount (disable for best privacy)
     #[arg(short, long, default_value_t = false)]
     auto: bool,
@@ -607,15 +2697,54 @@ struct SendCamoArgs {
     /// Amount of Nano that the notifier account should send (subtracted from `amount`)
     #[arg(short = 'A', long)]
     notifier_amount: Option<Amount>,
+    /// Send even if the sender's or notifier's frontier may be out of date
+    #[arg(short, long, default_value_t = false)]
+    force: bool,
+    /// Display what would be sent, and its privacy implications, without sending anything
+    #[arg(long, default_value_t = false)]
+    analyze: bool,
+    /// Log the (non-secret) inputs and outputs of the derivation, for troubleshooting
+    #[arg(long, default_value_t = false)]
+    trace: bool,
 }
 impl SendCamoArgs {
+    fn display_analysis<Frontend: WalletFrontend>(
+        payment: &CamoPayment,
+        derived: &Account,
+        notification: [u8; 32],
+    ) {
+        Frontend::println(&format!(
+            "Sender: {} sends {} Nano to {derived}",
+            payment.sender,
+            Amount::from(payment.sender_amount)
+        ));
+        Frontend::println(&format!(
+            "Notifier: {} sends {} Nano to {}",
+            payment.notifier,
+            Amount::from(payment.notification_amount),
+            payment.recipient.signer_account()
+        ));
+        Frontend::println(&format!("Notification: {}", hex::encode(notification)));
+
+        if payment.sender == payment.notifier {
+            Frontend::println(
+                "Privacy: reduced - sender and notifier are the same account, which links the payment to the notification",
+            );
+        } else {
+            Frontend::println("Privacy: sender and notifier are different accounts");
+        }
+        Frontend::println("Nothing was sent");
+    }
+
     async fn execute<Frontend: WalletFrontend>(
         self,
         frontend: &mut Frontend,
     ) -> Result<bool, ClientError> {
         let client = frontend.client_mut();
-        let work_client = &mut client.work;
+        let recipient = self.recipient.resolve(&client.address_book)?;
         let core_client = &mut client.core;
+        core_client.update_confirmed().await?;
+        core_client.check_spendable(&self.sender)?;
 
         let notifier_amount = if let Some(notifier_amount) = self.notifier_amount {
             // if a notifier amount was given
@@ -628,27 +2757,51 @@ impl SendCamoArgs {
             return Err(ClientError::InvalidArguments);
         };
 
+        // the notifier amount is subtracted from the total, even if 'all' was given
+        let amount = self.amount.resolve(
+            core_client.frontiers_db.account_balance(&self.sender),
+            core_client.config.RESERVE_RAW,
+        )?;
         if notifier_amount < CAMO_SENDER_DUST_THRESHOLD {
             return Err(ClientError::AmountBelowDustThreshold);
         }
-        if self.amount.value < max(notifier_amount, CAMO_SENDER_DUST_THRESHOLD) {
+        if amount < max(notifier_amount, CAMO_SENDER_DUST_THRESHOLD) {
             return Err(ClientError::AmountBelowDustThreshold);
         }
 
         let notifier = if let Some(notifier) = self.notifier {
             // if a notifier account was given
+            core_client.check_spendable(&notifier)?;
             notifier
         } else if self.auto {
             // if a notifier account was NOT given (must be selected automatically)
-            let auto_selected = core_client.accounts_with_balance(
-                notifier_amount,
-                &[self.sender.clone(), self.recipient.signer_account()],
-            );
+            // never use one of this wallet's own notification accounts as the notifier,
+            // which would link this payment to that camo_ account
+            let mut exclude = core_client
+                .wallet_db
+                .camo_account_db
+                .all_notification_accounts();
+            exclude.extend([self.sender.clone(), recipient.signer_account()]);
+            let required = notifier_amount.saturating_add(core_client.config.RESERVE_RAW);
+            let auto_selected = core_client.accounts_with_balance(required, &exclude);
             match auto_selected.first() {
                 // if another account can be automatically selected
                 Some(info) => info.block.account.clone(),
                 // if no accounts have the necessary balance, use the same account
-                None => self.sender.clone(),
+                None => {
+                    Frontend::println(&format!(
+                        "Warning: no account other than the sender {} has at least {} Nano to be the notifier",
+                        self.sender,
+                        Amount::from(required)
+                    ));
+                    Frontend::println(
+                        "Using the sender as the notifier links the payment to its notification: fund a separate account first for better privacy",
+                    );
+                    if core_client.config.STRICT_AUTO_NOTIFIER && !self.analyze {
+                        return Err(ClientError::NoSeparateNotifier);
+                    }
+                    self.sender.clone()
+                }
             }
         } else {
             Frontend::println("'notifier' is required if 'auto' is not set");
@@ -663,32 +2816,56 @@ impl SendCamoArgs {
             ));
         }
 
-        let sender_amount = self.amount.value - notifier_amount;
+        let sender_amount = amount - notifier_amount;
         let payment = CamoPayment {
-            sender: self.sender,
+            sender: self.sender.clone(),
             sender_amount,
             notifier: notifier.clone(),
             notification_amount: notifier_amount,
-            recipient: self.recipient.clone(),
+            recipient: recipient.clone(),
         };
 
+        let (derived, notification) = core_client.camo_transaction_memo(&payment)?;
+        let notification = notification_payload_bytes(notification);
+        if self.trace {
+            let sender_frontier = core_client
+                .frontiers_db
+                .account_frontier(&payment.sender)
+                .map(|frontier| hex::encode_upper(frontier.block.hash()))
+                .unwrap_or_default();
+            info!(
+                "Camo trace: sender {} at frontier {sender_frontier}, notification account {}, payload {}, derived {derived}",
+                payment.sender,
+                payment.recipient.signer_account(),
+                hex::encode_upper(notification)
+            );
+        }
+        if self.analyze {
+            Self::display_analysis::<Frontend>(&payment, &derived, notification);
+            return Ok(true);
+        }
+
+        check_needs_refresh::<Frontend>(
+            &client.needs_refresh,
+            &[&self.sender, &notifier],
+            self.force,
+        )?;
+
         // create the transaction summary
-        let (_, notification) = core_client.camo_transaction_memo(&payment)?;
         let tx_summary = CamoTxSummary {
-            recipient: self.recipient,
+            recipient,
             camo_amount: sender_amount,
-            total_amount: self.amount.value,
-            notification: notification_payload_bytes(notification),
+            total_amount: amount,
+            notification,
+            sender: Some(self.sender.clone()),
+            notifier: Some(notifier.clone()),
         };
         if client.camo_history.first() != Some(&tx_summary) {
             client.camo_history.insert(0, tx_summary);
         }
 
         Frontend::println("Sending...");
-        let success = core_client.send_camo(work_client, payment).await?;
-
-        let frontiers = core_client.handle_rpc_success(success);
-        core_client.set_new_frontiers(frontiers);
+        publish_camo_payment(client, payment).await?;
         Frontend::println("Done");
         Ok(true)
     }
@@ -701,3 +2878,365 @@ impl QuitArgs {
         Ok(false)
     }
 }
+
+#[derive(Debug, Args)]
+struct VerifyBackupArgs {}
+impl VerifyBackupArgs {
+    fn execute<Frontend: WalletFrontend>(self, frontend: &Frontend) -> Result<bool, ClientError> {
+        let client = frontend.client();
+        let key = frontend.encryption_key();
+        let params = KdfParams::from_config(&client.core.config);
+        let encrypted = client
+            .as_wallet_data()
+            .encrypt("verify_backup", key, params)?;
+        let decrypted = encrypted.decrypt(key)?;
+
+        if decrypted.same_as(&client.as_wallet_data())? {
+            Frontend::println(
+                "Backup verified: the wallet was encrypted and decrypted successfully",
+            );
+        } else {
+            Frontend::println(
+                "Verification failed: the decrypted wallet does not match the original. Do not rely on the saved file",
+            );
+        }
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct WatchArgs {
+    #[command(subcommand)]
+    command: WatchCommandType,
+}
+impl WatchArgs {
+    async fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+    ) -> Result<bool, ClientError> {
+        match self.command {
+            WatchCommandType::Add(args) => args.execute(frontend).await,
+            WatchCommandType::Remove(args) => args.execute(frontend),
+            WatchCommandType::List(args) => args.execute(frontend),
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum WatchCommandType {
+    /// Track the balance of a nano_ account which is not part of this wallet
+    Add(WatchAddArgs),
+    /// Stop tracking a watch-only account
+    Remove(WatchRemoveArgs),
+    /// List the watch-only accounts
+    List(WatchListArgs),
+}
+
+#[derive(Debug, Args)]
+struct WatchAddArgs {
+    /// nano_ account
+    account: Account,
+}
+impl WatchAddArgs {
+    async fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+    ) -> Result<bool, ClientError> {
+        let client = frontend.client_mut();
+        let core_client = &mut client.core;
+        let already_tracked = core_client
+            .wallet_db
+            .add_watch_only(&core_client.config, self.account.clone())?;
+        if already_tracked {
+            Frontend::println(&format!(
+                "{} is already tracked by this wallet",
+                self.account
+            ));
+            return Ok(true);
+        }
+        client.mark_needs_refresh(self.account);
+
+        let core_client = &mut client.core;
+        let downloaded = core_client.download_unknown_frontiers().await?;
+        let downloaded = core_client.handle_rpc_success(downloaded);
+        let accounts: Vec<Account> = downloaded
+            .new
+            .iter()
+            .map(|frontier| frontier.block.account.clone())
+            .collect();
+        core_client.set_new_frontiers(downloaded);
+        client.clear_needs_refresh(&accounts);
+
+        Frontend::println("Done");
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct WatchRemoveArgs {
+    /// nano_ account
+    account: Account,
+}
+impl WatchRemoveArgs {
+    fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+    ) -> Result<bool, ClientError> {
+        frontend.client_mut().remove_watch_only(&self.account)?;
+        Frontend::println("Done");
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct WatchListArgs {}
+impl WatchListArgs {
+    fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+    ) -> Result<bool, ClientError> {
+        let accounts = frontend.client().core.wallet_db.watch_only_accounts();
+        if accounts.is_empty() {
+            Frontend::println("No watch-only accounts");
+        }
+        for account in accounts {
+            Frontend::println(&account.to_string());
+        }
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct WeightArgs {
+    /// Download the current weight of each representative
+    #[arg(short, long, default_value_t = false)]
+    live: bool,
+}
+impl WeightArgs {
+    async fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+    ) -> Result<bool, ClientError> {
+        let live_weights = if self.live {
+            let core_client = &mut frontend.client_mut().core;
+            let mut weights = vec![];
+            for (representative, _, _) in balance::delegated_weights(core_client) {
+                let weight = RpcManager()
+                    .account_weight(&core_client.config, &representative)
+                    .await;
+                match weight {
+                    Ok(success) => weights.push(Some(core_client.handle_rpc_success(success))),
+                    Err(err) => {
+                        Frontend::println(&format!(
+                            "Failed to download weight of {representative}: {err}"
+                        ));
+                        weights.push(None)
+                    }
+                }
+            }
+            Some(weights)
+        } else {
+            None
+        };
+
+        balance::execute_weight(frontend, live_weights)?;
+        Ok(true)
+    }
+}
+
+#[derive(Debug, Args)]
+struct WorkArgs {
+    #[command(subcommand)]
+    command: WorkCommandType,
+}
+impl WorkArgs {
+    fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+    ) -> Result<bool, ClientError> {
+        match self.command {
+            WorkCommandType::Cache(args) => args.execute(frontend),
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum WorkCommandType {
+    /// List the cached work of each frontier
+    Cache(WorkCacheArgs),
+}
+
+#[derive(Debug, Args)]
+struct WorkCacheArgs {
+    /// Clear the cached work for these work hashes
+    #[arg(short, long, conflicts_with = "invalid")]
+    clear: Vec<Hex32Bytes>,
+    /// Clear all cached work which does not meet the current work difficulty
+    #[arg(short, long, default_value_t = false, conflicts_with = "clear")]
+    invalid: bool,
+}
+impl WorkCacheArgs {
+    fn execute<Frontend: WalletFrontend>(
+        self,
+        frontend: &mut Frontend,
+    ) -> Result<bool, ClientError> {
+        let core_client = &mut frontend.client_mut().core;
+
+        if self.invalid {
+            let cleared = core_client
+                .frontiers_db
+                .clear_invalid_work(&core_client.config);
+            Frontend::println(&format!("Cleared {cleared} invalid work caches"));
+            return Ok(true);
+        }
+        if !self.clear.is_empty() {
+            for work_hash in self.clear {
+                core_client.frontiers_db.clear_work_hash(work_hash.0)?;
+            }
+            Frontend::println("Done");
+            return Ok(true);
+        }
+
+        let frontiers = core_client.frontiers_db.frontiers();
+        for frontier in frontiers {
+            let status = match frontier.cached_work() {
+                Some(work) => {
+                    let validity = if frontier.has_valid_work(&core_client.config) {
+                        "valid"
+                    } else {
+                        "invalid"
+                    };
+                    format!("{} ({validity})", hex::encode(work))
+                }
+                None => "none".to_string(),
+            };
+            Frontend::println(&format!(
+                "{}: {} -> {status}",
+                frontier.block.account,
+                hex::encode_upper(frontier.work_hash())
+            ));
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core_client::constants::ONE_NANO;
+    use core_client::{nanopyrs::Signature, CoreClientConfig, SecretBytes, WalletSeed};
+    use std::cell::RefCell;
+    use tokio::runtime::Runtime;
+
+    thread_local! {
+        /// Lines printed by `TestFrontend`
+        static OUTPUT: RefCell<Vec<String>> = const { RefCell::new(vec![]) };
+    }
+
+    struct TestFrontend {
+        client: Client,
+        key: SecretBytes<32>,
+    }
+    impl WalletFrontend for TestFrontend {
+        fn println(s: &str) {
+            OUTPUT.with(|output| output.borrow_mut().push(s.to_owned()));
+        }
+        fn clear_screen() {}
+        fn authenticate(&self) -> Result<(), ClientError> {
+            Ok(())
+        }
+        fn encryption_key(&self) -> &SecretBytes<32> {
+            &self.key
+        }
+        fn client(&self) -> &Client {
+            &self.client
+        }
+        fn client_mut(&mut self) -> &mut Client {
+            &mut self.client
+        }
+    }
+
+    /// A frontend with no RPCs, and one opened account represented by `representative`
+    fn test_frontend(representative: &Account) -> TestFrontend {
+        let mut config = CoreClientConfig::default_with(vec![representative.clone()], vec![]);
+        config.CONFIRMATION_REQUIRED = false;
+        config.WORK_LOCAL_ENABLED = false;
+        let mut client = Client::new(WalletSeed::from([7; 32]), config.clone()).unwrap();
+
+        let (_, info) = client.core.seed.get_key(0);
+        let block = Block {
+            block_type: BlockType::Receive,
+            account: info.account.clone(),
+            previous: [1; 32],
+            representative: representative.clone(),
+            balance: 10,
+            link: [2; 32],
+            signature: Signature::default(),
+            work: [0; 8],
+        };
+        client
+            .core
+            .wallet_db
+            .account_db
+            .insert(&config, info)
+            .unwrap();
+        client
+            .core
+            .set_new_frontiers(vec![FrontierInfo::new(block, None)].into());
+        TestFrontend {
+            client,
+            key: SecretBytes::from([3; 32]),
+        }
+    }
+
+    #[test]
+    fn representative_set_unchanged() {
+        let representative = core_client::nanopyrs::constants::get_genesis_account();
+        let mut frontend = test_frontend(&representative);
+        let command = format!("representative set {representative} --all");
+
+        // nothing to change: the wallet must not exit
+        let runtime = Runtime::new().unwrap();
+        for _ in 0..2 {
+            let result = runtime.block_on(Command::execute(&mut frontend, &command));
+            assert!(matches!(result, Ok(true)));
+        }
+    }
+
+    #[test]
+    fn summary_pending_send() {
+        let representative = core_client::nanopyrs::constants::get_genesis_account();
+        let mut frontend = test_frontend(&representative);
+        let core = &mut frontend.client_mut().core;
+        let (_, info) = core.seed.get_key(0);
+
+        // an unconfirmed send of 6 Nano, following the 10 Nano frontier
+        let mut block = core
+            .frontiers_db
+            .account_frontier(&info.account)
+            .unwrap()
+            .block
+            .clone();
+        block.balance = 10 * ONE_NANO;
+        core.set_new_frontiers(vec![FrontierInfo::new(block.clone(), None)].into());
+        block.block_type = BlockType::Send;
+        block.previous = block.hash();
+        block.balance = 4 * ONE_NANO;
+        core.set_new_frontiers(vec![FrontierInfo::new(block, None)].into());
+
+        let runtime = Runtime::new().unwrap();
+        let result = runtime.block_on(Command::execute(&mut frontend, "summary"));
+        assert!(matches!(result, Ok(true)));
+        let output = OUTPUT.with(|output| output.take());
+        assert!(
+            output
+                == [
+                    "balance: 10 Nano",
+                    "pending send: 6 Nano",
+                    "available: 4 Nano",
+                    "receivable: 0 Nano",
+                    "total: 4 Nano",
+                ]
+        );
+    }
+}
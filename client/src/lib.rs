@@ -5,17 +5,24 @@ mod defaults;
 mod error;
 mod interface;
 
+pub mod status;
 pub mod storage;
 pub mod types;
 
 use core_client::{
-    rpc::WorkManager, Account, CamoAccount, CoreClient, CoreClientConfig, Receivable, RescanData,
+    price::PriceCache,
+    rpc::{get_current_time, WebsocketManager, WorkManager},
+    Account, CamoAccount, CoreClient, CoreClientConfig, Receivable, RescanData, SecretBytes,
     WalletSeed,
 };
 use defaults::{default_representatives, default_rpcs};
 use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use status::WalletStatus;
 use std::collections::HashMap;
-use types::CamoTxSummary;
+use log::warn;
+use std::slice;
+use types::{AccountActivity, AddressBook, CamoTxSummary, RescanProgress, RescanStatus};
 use zeroize::{Zeroize, ZeroizeOnDrop};
 use storage::WalletData;
 
@@ -47,11 +54,27 @@ impl From<ClientConfig> for CoreClientConfig {
 pub trait WalletFrontend {
     /// Print a string
     fn println(s: &str);
+    /// Print machine-readable output, used instead of `println()` when `--json` is given
+    fn println_json(value: &JsonValue) {
+        Self::println(&format!("{value:#}"))
+    }
+    /// Whether balances and amounts should be masked in human-readable output,
+    /// unless a command is given `--reveal`. `--json` output is never masked.
+    fn private_mode(&self) -> bool {
+        false
+    }
     /// Clear the terminal
     fn clear_screen();
     /// Authenticate the user: if the password is incorrect, returns an error.
     /// Useful for e.g. displaying the wallet's seed.
     fn authenticate(&self) -> Result<(), ClientError>;
+    /// Get the key which this frontend encrypts the wallet with when saving it
+    fn encryption_key(&self) -> &SecretBytes<32>;
+    /// Save the wallet to disk in the middle of a command, e.g. between the batches of a streamed rescan.
+    /// Frontends which cannot do so may ignore this.
+    fn save(&mut self) -> Result<(), ClientError> {
+        Ok(())
+    }
     /// Get this frontend's CliClient
     fn client(&self) -> &Client;
     /// Get this frontend's CliClient as mutable
@@ -64,8 +87,21 @@ pub struct Client {
     #[zeroize(skip)]
     pub receivable: HashMap<[u8; 32], Receivable>,
     pub camo_history: Vec<CamoTxSummary>,
+    pub activity: Vec<AccountActivity>,
+    pub address_book: AddressBook,
+    pub rescan_progress: Vec<RescanProgress>,
+    /// Accounts whose frontiers have not been downloaded since they were added
+    pub needs_refresh: Vec<Account>,
     #[zeroize(skip)]
     pub work: WorkManager,
+    /// Collects receivable transactions pushed by a node, as per `WEBSOCKET_ENABLED`
+    #[zeroize(skip)]
+    pub websocket: WebsocketManager,
+    /// Prices downloaded for `balance --fiat`
+    #[zeroize(skip)]
+    pub prices: PriceCache,
+    /// When the network's work difficulty was last downloaded, as per `WORK_DIFFICULTY_AUTO`
+    pub difficulty_updated: u64,
 }
 impl Client {
     pub fn new(
@@ -76,7 +112,14 @@ impl Client {
             core: CoreClient::new(seed, config),
             receivable: HashMap::new(),
             camo_history: vec![],
+            activity: vec![],
+            address_book: AddressBook::default(),
+            rescan_progress: vec![],
+            needs_refresh: vec![],
             work: WorkManager::default(),
+            websocket: WebsocketManager::default(),
+            prices: PriceCache::default(),
+            difficulty_updated: 0,
         };
         Ok(client)
     }
@@ -87,21 +130,152 @@ impl Client {
             .retain(|_, receivable| &receivable.recipient != account);
     }
 
+    /// Remove these accounts' receivable transactions from the DB, and return them
+    fn take_receivable(&mut self, accounts: &[Account]) -> Vec<Receivable> {
+        let hashes: Vec<[u8; 32]> = self
+            .receivable
+            .values()
+            .filter(|receivable| accounts.contains(&receivable.recipient))
+            .map(|receivable| receivable.block_hash)
+            .collect();
+        hashes
+            .iter()
+            .filter_map(|hash| self.receivable.remove(hash))
+            .collect()
+    }
+
+    /// Replace the cached receivable transactions of these accounts with `receivables`.
+    /// Returns the previously cached transactions which are no longer receivable,
+    /// e.g. because they were received by another instance of this wallet.
+    fn replace_receivable(&mut self, accounts: &[Account], receivables: Vec<Receivable>) -> Vec<Receivable> {
+        let mut vanished = self.take_receivable(accounts);
+        vanished.retain(|cached| {
+            !receivables
+                .iter()
+                .any(|receivable| receivable.block_hash == cached.block_hash)
+        });
+        self.insert_receivable(receivables);
+        vanished
+    }
+
+    /// Insert receivable transactions into the DB.
+    /// If the DB grows beyond `config::RECEIVABLE_CACHE_LIMIT`, the smallest transactions are dropped.
     fn insert_receivable(&mut self, receivables: Vec<Receivable>) {
         for receivable in receivables {
             self.receivable
                 .insert(receivable.block_hash, receivable);
         }
+
+        let limit = self.core.config.RECEIVABLE_CACHE_LIMIT;
+        if self.receivable.len() <= limit {
+            return;
+        }
+        let mut by_amount: Vec<(u128, [u8; 32])> = self
+            .receivable
+            .values()
+            .map(|receivable| (receivable.amount, receivable.block_hash))
+            .collect();
+        by_amount.sort_unstable();
+        for (_, block_hash) in &by_amount[..by_amount.len() - limit] {
+            self.receivable.remove(block_hash);
+        }
+    }
+
+    fn mark_needs_refresh(&mut self, account: Account) {
+        if !self.needs_refresh.contains(&account) {
+            self.needs_refresh.push(account)
+        }
+    }
+
+    fn clear_needs_refresh(&mut self, accounts: &[Account]) {
+        self.needs_refresh
+            .retain(|account| !accounts.contains(account));
+    }
+
+    /// Get the activity counters of this account, if any have been recorded
+    pub fn account_activity(&self, account: &Account) -> Option<&AccountActivity> {
+        self.activity.iter().find(|activity| &activity.account == account)
+    }
+
+    fn account_activity_mut(&mut self, account: &Account) -> &mut AccountActivity {
+        let index = match self
+            .activity
+            .iter()
+            .position(|activity| &activity.account == account)
+        {
+            Some(index) => index,
+            None => {
+                self.activity.push(AccountActivity::new(account.clone()));
+                self.activity.len() - 1
+            }
+        };
+        &mut self.activity[index]
+    }
+
+    /// Get the block that rescanning this camo account should resume from, if any
+    pub fn rescan_head(&self, camo_account: &CamoAccount) -> Option<[u8; 32]> {
+        self.rescan_progress
+            .iter()
+            .find(|progress| &progress.camo_account == camo_account)
+            .map(|progress| progress.last_scanned_head)
+    }
+
+    /// Set the block that rescanning this camo account should resume from.
+    /// `None` clears the saved progress, so that rescanning starts from the frontier again.
+    pub fn set_rescan_head(&mut self, camo_account: &CamoAccount, head: Option<[u8; 32]>) {
+        self.rescan_progress
+            .retain(|progress| &progress.camo_account != camo_account);
+        if let Some(head) = head {
+            self.rescan_progress.push(RescanProgress {
+                camo_account: camo_account.clone(),
+                last_scanned_head: head,
+            });
+        }
+    }
+
+    /// Record that this account has published a send block
+    fn record_send(&mut self, account: &Account) {
+        let activity = self.account_activity_mut(account);
+        activity.sends += 1;
+        activity.last_activity = get_current_time();
+    }
+
+    /// Record that this account has published `count` receive blocks
+    fn record_receives(&mut self, account: &Account, count: u64) {
+        let activity = self.account_activity_mut(account);
+        activity.receives += count;
+        activity.last_activity = get_current_time();
     }
 
     /// Remove an account from all DB's.
     /// This method works for both normal and derived Nano accounts.
     fn remove_account(&mut self, account: &Account) -> Result<(), ClientError> {
+        self.activity.retain(|activity| &activity.account != account);
         self.remove_receivable(account);
         self.core.remove_account(account)?;
         Ok(())
     }
 
+    /// Stop tracking a watch-only account, removing it from all DB's.
+    fn remove_watch_only(&mut self, account: &Account) -> Result<(), ClientError> {
+        self.core.wallet_db.remove_watch_only(account)?;
+        self.activity.retain(|activity| &activity.account != account);
+        self.remove_receivable(account);
+        self.clear_needs_refresh(slice::from_ref(account));
+        // the frontier is missing if it was never downloaded
+        let _ = self.core.frontiers_db.remove(account);
+        Ok(())
+    }
+
+    /// Get the total amount of the cached receivable transactions, excluding those of watch-only accounts
+    pub fn receivable_total(&self) -> u128 {
+        self.receivable
+            .values()
+            .filter(|receivable| !self.core.wallet_db.is_watch_only(&receivable.recipient))
+            .map(|receivable| receivable.amount)
+            .sum()
+    }
+
     /// Remove a camo account, and its derived accounts, from all DB's.
     fn remove_camo_account(&mut self, camo_account: &CamoAccount) -> Result<(), ClientError> {
         let derived = self.core.get_derived_accounts_from_master(camo_account);
@@ -110,10 +284,44 @@ impl Client {
         }
 
         self.remove_receivable(&camo_account.signer_account());
+        self.set_rescan_head(camo_account, None);
         self.core.remove_camo_account(camo_account)?;
         Ok(())
     }
 
+    /// Receive the given transactions, updating the frontiers DB and activity counters.
+    /// Transactions which could not be received are returned to the receivable DB.
+    async fn receive_transactions(&mut self, receivables: Vec<Receivable>) -> Result<(), ClientError> {
+        let attempted: Vec<([u8; 32], Account)> = receivables
+            .iter()
+            .map(|receivable| (receivable.block_hash, receivable.recipient.clone()))
+            .collect();
+
+        let result = self.core.receive(&mut self.work, receivables).await;
+        let frontiers = self.core.handle_rpc_success(result.successes);
+        self.core.set_new_frontiers(frontiers);
+
+        let (return_value, mut unreceived, overflowed) = if let Err(err) = result.failures {
+            (Err(err.err.into()), err.unreceived, err.overflowed)
+        } else {
+            (Ok(()), vec![], vec![])
+        };
+
+        for (block_hash, recipient) in attempted {
+            let was_received = !unreceived
+                .iter()
+                .any(|receivable| receivable.block_hash == block_hash);
+            if was_received {
+                self.record_receives(&recipient, 1);
+            }
+        }
+        if self.core.config.RECEIVE_DROP_OVERFLOWING {
+            unreceived.retain(|receivable| !overflowed.contains(receivable));
+        }
+        self.insert_receivable(unreceived);
+        return_value
+    }
+
     fn handle_rescan(&mut self, rescan: RescanData) {
         self.core.set_new_frontiers(rescan.new_frontiers);
         self.core
@@ -121,30 +329,217 @@ impl Client {
             .derived_account_db
             .insert_many(rescan.derived_info);
         self.insert_receivable(rescan.receivable);
+        if let Some(history) = rescan.history {
+            self.core.history_cache.insert(history);
+        }
+    }
+
+    /// Rescan the notification history of a camo account one page at a time,
+    /// starting from `head` and saving the rescan progress after each page.
+    ///
+    /// `progress` is called after each page with that page's data and the running totals.
+    /// Scanning continues until it returns `false`, or the start of the history is reached.
+    pub async fn rescan(
+        &mut self,
+        camo_account: &CamoAccount,
+        head: [u8; 32],
+        filter: bool,
+        use_cache: bool,
+        trace: bool,
+        mut progress: impl FnMut(&RescanData, &RescanStatus) -> bool,
+    ) -> Result<RescanStatus, ClientError> {
+        let mut status = RescanStatus {
+            head: Some(head),
+            ..RescanStatus::default()
+        };
+        while let Some(head) = status.head {
+            let rescan = self
+                .core
+                .rescan_notifications_partial(camo_account, Some(head), None, filter, use_cache, trace)
+                .await?;
+            let rescan = self.core.handle_rpc_success(rescan);
+
+            // the history is fully scanned once the `previous` of an open block is reached
+            status.head = rescan.new_head.filter(|head| head != &[0; 32]);
+            status.pages += 1;
+            status.blocks_scanned += rescan.blocks_scanned;
+            status.accounts_found += rescan.derived_info.len();
+            let next = progress(&rescan, &status);
+
+            self.handle_rescan(rescan);
+            self.set_rescan_head(camo_account, status.head);
+            if !next {
+                break;
+            }
+        }
+        Ok(status)
+    }
+
+    /// Like `rescan()`, but the accounts derived from each page are checked `batch_size` at a time,
+    /// and the wallet is saved with `WalletFrontend::save()` after each batch.
+    ///
+    /// This bounds memory use when scanning camo accounts which have received very many payments.
+    /// `progress` is called after each batch, and scanning continues until it returns `false`
+    /// at the end of a page, or the start of the history is reached.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn rescan_streamed<Frontend: WalletFrontend>(
+        frontend: &mut Frontend,
+        camo_account: &CamoAccount,
+        head: [u8; 32],
+        filter: bool,
+        use_cache: bool,
+        trace: bool,
+        batch_size: usize,
+        mut progress: impl FnMut(&RescanData, &RescanStatus) -> bool,
+    ) -> Result<RescanStatus, ClientError> {
+        let batch_size = batch_size.max(1);
+        let mut status = RescanStatus {
+            head: Some(head),
+            ..RescanStatus::default()
+        };
+        while let Some(head) = status.head {
+            let client = frontend.client_mut();
+            let scan = client
+                .core
+                .scan_notifications_partial(camo_account, Some(head), None, use_cache, trace)
+                .await?;
+            let mut scan = client.core.handle_rpc_success(scan);
+            if let Some(history) = scan.history.take() {
+                client.core.history_cache.insert(history);
+            }
+
+            // the history is fully scanned once the `previous` of an open block is reached
+            status.head = scan.new_head.filter(|head| head != &[0; 32]);
+            status.pages += 1;
+            status.blocks_scanned += scan.blocks_scanned;
+
+            // always check at least one (possibly empty) batch, so that `progress` is called for each page
+            let mut unchecked = std::mem::take(&mut scan.derived_info);
+            let next = loop {
+                let remaining = unchecked.split_off(batch_size.min(unchecked.len()));
+                let batch = std::mem::replace(&mut unchecked, remaining);
+
+                let client = frontend.client_mut();
+                let rescan = client.core.check_derived_accounts(batch, filter).await?;
+                let rescan = client.core.handle_rpc_success(rescan);
+                status.accounts_found += rescan.derived_info.len();
+                let next = progress(&rescan, &status);
+
+                client.handle_rescan(rescan);
+                frontend.save()?;
+                if unchecked.is_empty() {
+                    break next;
+                }
+            };
+
+            // only move on once every account derived from this page has been saved
+            frontend.client_mut().set_rescan_head(camo_account, status.head);
+            frontend.save()?;
+            if !next {
+                break;
+            }
+        }
+        Ok(status)
     }
 
     /// Update the work cache.
     /// Returns `Ok(true)` if we should save the wallet data.
     ///
     /// Saves finished requests and makes new ones (if neccessary).
+    /// Requests lost when the wallet was closed are made again, since their frontiers still need work.
     pub async fn update_work_cache(&mut self) -> Result<bool, ClientError> {
+        let mut should_save = self.update_work_difficulty().await;
+        should_save |= self.update_websocket().await;
+
         // Handle finished requests
-        let should_save = self
+        should_save |= self
             .core
             .handle_work_results(&mut self.work)
             .await?;
 
+        // Watch-only accounts can't publish blocks, so they never need work
+        let watch_only: Vec<[u8; 32]> = self
+            .core
+            .frontiers_db
+            .accounts_frontiers(self.core.wallet_db.watch_only_accounts())
+            .into_iter()
+            .flatten()
+            .map(|frontier| frontier.work_hash())
+            .collect();
+
         // Make new requests
         for work_hash in self.core.frontiers_db.needs_work() {
-            if self.work.n_requests() >= 2 {
+            if watch_only.contains(&work_hash) {
+                continue;
+            }
+            if self.work.n_requests() >= self.core.config.MAX_CONCURRENT_WORK_REQUESTS {
                 break;
             }
+            // invalid work was returned too many times
+            if self.work.is_exhausted(&self.core.config, work_hash) {
+                continue;
+            }
             self.work
-                .request_work(&self.core.config, work_hash);
+                .request_work(&self.core.config, work_hash, None);
         }
         Ok(should_save)
     }
 
+    /// Add the receivable transactions pushed by the websocket (if `WEBSOCKET_ENABLED`) to the receivable DB.
+    /// Returns `true` if any were added.
+    ///
+    /// Notification accounts are not listened to, since camo payments need to be found with `refresh`.
+    async fn update_websocket(&mut self) -> bool {
+        let config = &self.core.config;
+        let wallet_db = &self.core.wallet_db;
+        let notification_accounts = wallet_db.camo_account_db.all_notification_accounts();
+        let accounts: Vec<Account> = wallet_db
+            .nano_accounts_in_range(config.RECEIVE_INDEX_RANGE.as_ref())
+            .into_iter()
+            .filter(|account| !notification_accounts.contains(account))
+            .collect();
+
+        let receivables: Vec<Receivable> = self
+            .websocket
+            .update(config, &accounts)
+            .await
+            .into_iter()
+            .filter(|receivable| receivable.amount >= config.NORMAL_DUST_THRESHOLD)
+            .filter(|receivable| !self.receivable.contains_key(&receivable.block_hash))
+            .collect();
+        if receivables.is_empty() {
+            return false;
+        }
+        self.insert_receivable(receivables);
+        true
+    }
+
+    /// Download the network's work difficulty, if `WORK_DIFFICULTY_AUTO` and it is due.
+    /// Returns `true` if the difficulty changed.
+    async fn update_work_difficulty(&mut self) -> bool {
+        let config = &self.core.config;
+        let now = get_current_time();
+        let due = self.difficulty_updated + config.WORK_DIFFICULTY_AUTO_INTERVAL_SECONDS;
+        if !config.WORK_DIFFICULTY_AUTO || now < due {
+            return false;
+        }
+
+        // failures are retried at the next interval
+        self.difficulty_updated = now;
+        match self.core.update_work_difficulty().await {
+            Ok(changed) => changed,
+            Err(err) => {
+                warn!("Failed to download the network's work difficulty: {err}");
+                false
+            }
+        }
+    }
+
+    /// Get a read-only snapshot of the wallet, intended for monitoring tools
+    pub fn status(&self) -> WalletStatus {
+        status::wallet_status(self)
+    }
+
     pub fn as_wallet_data(&self) -> WalletData {
         WalletData {
             seed: self.core.seed.clone(),
@@ -152,6 +547,10 @@ impl Client {
             frontiers_db: self.core.frontiers_db.clone(),
             cached_receivable: self.receivable.clone(),
             camo_history: self.camo_history.clone(),
+            activity: self.activity.clone(),
+            address_book: self.address_book.clone(),
+            rescan_progress: self.rescan_progress.clone(),
+            history_cache: self.core.history_cache.clone(),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,170 @@
+use super::error::ClientError;
+use super::types::Amount;
+use super::{Client, WalletFrontend};
+use core_client::{rpc::get_current_time, Account};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountKind {
+    Normal,
+    Notification,
+    Derived,
+    #[serde(rename = "watch_only")]
+    WatchOnly,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountStatus {
+    pub account: String,
+    pub kind: AccountKind,
+    /// Balance in raw
+    pub balance: String,
+    /// Cached receivable amount in raw
+    pub receivable: String,
+    pub has_cached_work: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RpcStatus {
+    pub url: String,
+    pub banned: bool,
+    pub banned_until: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkCacheStatus {
+    /// Number of frontiers with cached work
+    pub cached: usize,
+    /// Number of frontiers in the DB
+    pub total: usize,
+}
+
+/// Read-only snapshot of the wallet, intended for monitoring tools
+#[derive(Debug, Clone, Serialize)]
+pub struct WalletStatus {
+    /// Total balance in raw
+    pub balance: String,
+    /// Total cached receivable amount in raw
+    pub receivable: String,
+    pub accounts: Vec<AccountStatus>,
+    pub rpcs: Vec<RpcStatus>,
+    pub work_cache: WorkCacheStatus,
+    pub camo_history: usize,
+}
+
+fn account_status(client: &Client, account: Account, kind: AccountKind) -> AccountStatus {
+    let frontier = client.core.frontiers_db.account_frontier(&account);
+    let balance = frontier.map(|frontier| frontier.block.balance).unwrap_or(0);
+    let receivable: u128 = client
+        .receivable
+        .values()
+        .filter(|receivable| receivable.recipient == account)
+        .map(|receivable| receivable.amount)
+        .sum();
+
+    AccountStatus {
+        account: account.to_string(),
+        kind,
+        balance: balance.to_string(),
+        receivable: receivable.to_string(),
+        has_cached_work: frontier
+            .map(|frontier| frontier.cached_work().is_some())
+            .unwrap_or(false),
+    }
+}
+
+pub fn wallet_status(client: &Client) -> WalletStatus {
+    let core_client = &client.core;
+    let wallet_db = &core_client.wallet_db;
+
+    let accounts = [
+        (wallet_db.account_db.all_accounts(), AccountKind::Normal),
+        (
+            wallet_db.camo_account_db.all_notification_accounts(),
+            AccountKind::Notification,
+        ),
+        (
+            wallet_db.derived_account_db.all_accounts(),
+            AccountKind::Derived,
+        ),
+        (
+            wallet_db.watch_only_accounts().to_vec(),
+            AccountKind::WatchOnly,
+        ),
+    ]
+    .into_iter()
+    .flat_map(|(accounts, kind)| {
+        accounts
+            .into_iter()
+            .map(move |account| account_status(client, account, kind))
+    })
+    .collect();
+
+    let current_time = get_current_time();
+    let rpcs = core_client
+        .config
+        .RPCS
+        .iter()
+        .map(|rpc| RpcStatus {
+            url: rpc.get_url().to_owned(),
+            banned: rpc.is_banned(current_time),
+            banned_until: rpc.banned_until,
+        })
+        .collect();
+
+    let frontiers = core_client.frontiers_db.frontiers();
+    let work_cache = WorkCacheStatus {
+        cached: frontiers
+            .iter()
+            .filter(|frontier| frontier.cached_work().is_some())
+            .count(),
+        total: frontiers.len(),
+    };
+
+    WalletStatus {
+        balance: core_client.wallet_balance().to_string(),
+        receivable: client.receivable_total().to_string(),
+        accounts,
+        rpcs,
+        work_cache,
+        camo_history: client.camo_history.len(),
+    }
+}
+
+pub fn execute<Frontend: WalletFrontend>(
+    frontend: &Frontend,
+    json: bool,
+    mask: bool,
+) -> Result<(), ClientError> {
+    let status = frontend.client().status();
+    if json {
+        Frontend::println_json(&serde_json::to_value(&status)?);
+        return Ok(());
+    }
+
+    let client = frontend.client();
+    let total = Amount::from(client.core.wallet_balance());
+    let receivable = Amount::from(client.receivable_total());
+    let usable_rpcs = status.rpcs.iter().filter(|rpc| !rpc.banned).count();
+
+    Frontend::println(&format!(
+        "total: {} Nano (+ {} Nano receivable)",
+        total.display(mask),
+        receivable.display(mask)
+    ));
+    Frontend::println(&format!("accounts: {}", status.accounts.len()));
+    Frontend::println(&format!(
+        "RPCs: {usable_rpcs} usable ({} total)",
+        status.rpcs.len()
+    ));
+    Frontend::println(&format!(
+        "work cache: {} out of {} frontiers",
+        status.work_cache.cached, status.work_cache.total
+    ));
+    Frontend::println(&format!(
+        "camo history: {} transactions",
+        status.camo_history
+    ));
+    Ok(())
+}
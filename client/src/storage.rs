@@ -1,26 +1,115 @@
-use super::types::CamoTxSummary;
+use super::types::{
+    AccountActivity, AddressBook, Amount, CamoTxSummary, LegacyCamoTxSummary, RescanProgress,
+};
 use crate::{ClientError, CoreClient, Client};
 use aes_gcm::{
     aead::{Aead, AeadCore, KeyInit, OsRng},
     Aes256Gcm, Key, Nonce,
 };
-use argon2::Argon2;
+use argon2::{Algorithm, Argon2, Params, Version};
+use bincode::Options;
 use core_client::{
     frontiers::FrontiersDB,
-    wallet::{WalletDB, WalletSeed},
+    history::HistoryCache,
+    price::PriceCache,
+    wallet::{LegacyWalletDB, WalletDB, WalletSeed},
     CoreClientConfig,
-    rpc::WorkManager,
-    Receivable, SecretBytes,
+    rpc::{RpcManager, WebsocketManager, WorkManager},
+    Account, Block, BlockType, Receivable, SecretBytes,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
-/// Slow hash for password hashing
-fn key_hash(key: &[u8], salt: &[u8]) -> Result<Key<Aes256Gcm>, ClientError> {
-    let mut output = [0_u8; 32];
-    Argon2::default().hash_password_into(key, salt, &mut output)?;
-    Ok(output.into())
+/// Format version of wallets saved by v0.1.1, which did not store a version
+const LEGACY_WALLET_DATA_VERSION: u32 = 0;
+/// Format version of the `WalletData` saved by this version
+const WALLET_DATA_VERSION: u32 = 1;
+
+/// Slow hash for password hashing.
+///
+/// The identifier of the function used is stored in each `EncryptedWallet`,
+/// so that new functions can be added without breaking existing wallets.
+///
+/// Wallets saved before v0.1.2 have no identifier or parameters stored,
+/// which is equivalent to `Argon2id` with `KdfParams::default()`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeyDerivation {
+    /// Argon2id with the parameters stored in the `EncryptedWallet`
+    #[default]
+    Argon2id,
+}
+impl KeyDerivation {
+    /// The identifier stored in an `EncryptedWallet`
+    pub fn id(&self) -> &'static str {
+        match self {
+            KeyDerivation::Argon2id => "argon2id",
+        }
+    }
+
+    /// Returns `Err` if the identifier is unknown, e.g. if the wallet was saved by a newer version
+    pub fn from_id(id: &str) -> Result<KeyDerivation, ClientError> {
+        match id {
+            "argon2id" => Ok(KeyDerivation::Argon2id),
+            _ => Err(ClientError::UnsupportedKeyDerivation(id.into())),
+        }
+    }
+
+    fn key_hash(
+        &self,
+        key: &[u8],
+        salt: &[u8],
+        params: &KdfParams,
+    ) -> Result<Key<Aes256Gcm>, ClientError> {
+        let mut output = [0_u8; 32];
+        match self {
+            KeyDerivation::Argon2id => {
+                let params = Params::new(
+                    params.memory_kib,
+                    params.iterations,
+                    params.parallelism,
+                    Some(output.len()),
+                )?;
+                Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                    .hash_password_into(key, salt, &mut output)?
+            }
+        }
+        Ok(output.into())
+    }
+}
+
+fn legacy_key_derivation() -> String {
+    KeyDerivation::Argon2id.id().into()
+}
+
+/// Parameters of the `KeyDerivation`, stored in each `EncryptedWallet` (only used by `KeyDerivation::Argon2id`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Zeroize, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+impl KdfParams {
+    /// The parameters to save new wallets with, see `WALLET_KDF_MEMORY_KIB`
+    pub fn from_config(config: &CoreClientConfig) -> KdfParams {
+        KdfParams {
+            memory_kib: config.WALLET_KDF_MEMORY_KIB,
+            iterations: config.WALLET_KDF_ITERATIONS,
+            parallelism: config.WALLET_KDF_PARALLELISM,
+        }
+    }
+}
+impl Default for KdfParams {
+    /// The `argon2` crate's defaults, as used by wallets saved before v0.1.2
+    fn default() -> Self {
+        KdfParams {
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+        }
+    }
 }
 
 #[derive(Debug, Zeroize, Serialize, Deserialize)]
@@ -31,16 +120,27 @@ pub struct WalletData {
     #[zeroize(skip)]
     pub cached_receivable: HashMap<[u8; 32], Receivable>,
     pub camo_history: Vec<CamoTxSummary>,
+    /// Added in v0.1.2
+    pub activity: Vec<AccountActivity>,
+    /// Added in v0.1.2
+    pub address_book: AddressBook,
+    /// Added in v0.1.2
+    pub history_cache: HistoryCache,
+    /// Added in v0.1.2
+    pub rescan_progress: Vec<RescanProgress>,
 }
 impl WalletData {
+    /// `params` are usually `KdfParams::from_config()`
     pub fn encrypt(
         mut self,
         id: &str,
         key: &SecretBytes<32>,
+        params: KdfParams,
     ) -> Result<EncryptedWallet, ClientError> {
+        let kdf = KeyDerivation::default();
         let salt = rand::random::<[u8; 32]>();
         let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
-        let key = key_hash(key.as_bytes(), &salt)?;
+        let key = kdf.key_hash(key.as_bytes(), &salt, &params)?;
 
         let cipher = Aes256Gcm::new(&key);
         let mut data = bincode::serialize(&self)?;
@@ -52,42 +152,142 @@ impl WalletData {
         data.zeroize();
         Ok(EncryptedWallet {
             id: id.into(),
+            kdf: kdf.id().into(),
+            kdf_params: params,
+            version: WALLET_DATA_VERSION,
             salt: hex::encode(salt),
             nonce: hex::encode(nonce),
             data: hex::encode(encrypted),
         })
     }
 
-    pub fn to_client(self, config: CoreClientConfig) -> Client {
-        let client = CoreClient {
-            seed: self.seed,
-            config,
-            wallet_db: self.wallet_db,
-            frontiers_db: self.frontiers_db,
+    /// Whether or not both wallets contain the same data.
+    /// The databases are compared in serialized form, since they do not implement `PartialEq`.
+    pub fn same_as(&self, other: &WalletData) -> Result<bool, ClientError> {
+        let serialize = |data: &WalletData| {
+            bincode::serialize(&(
+                &data.seed,
+                &data.wallet_db,
+                &data.frontiers_db,
+                &data.camo_history,
+                &data.activity,
+                &data.address_book,
+                &data.history_cache,
+                &data.rescan_progress,
+            ))
         };
+        let mut serialized = serialize(self)?;
+        let mut other_serialized = serialize(other)?;
+        let same = serialized == other_serialized
+            && self.cached_receivable == other.cached_receivable;
+
+        serialized.zeroize();
+        other_serialized.zeroize();
+        Ok(same)
+    }
+
+    /// Note that cached work which does not meet the current work difficulty is dropped,
+    /// so that it will be re-requested by `Client::update_work_cache()`.
+    pub fn to_client(self, config: CoreClientConfig) -> Client {
+        let mut client = CoreClient::new(self.seed, config);
+        client.wallet_db = self.wallet_db;
+        client.frontiers_db = self.frontiers_db;
+        client.frontiers_db.clear_invalid_work(&client.config);
+        client.history_cache = self.history_cache;
+        // accounts whose frontiers were never downloaded
+        let needs_refresh = client
+            .wallet_db
+            .all_nano_accounts()
+            .into_iter()
+            .filter(|account| client.frontiers_db.account_frontier(account).is_none())
+            .collect();
 
         Client {
             core: client,
             receivable: self.cached_receivable,
             camo_history: self.camo_history,
+            activity: self.activity,
+            address_book: self.address_book,
+            rescan_progress: self.rescan_progress,
+            needs_refresh,
             work: WorkManager::default(),
+            websocket: WebsocketManager::default(),
+            prices: PriceCache::default(),
+            difficulty_updated: 0,
+        }
+    }
+}
+
+fn migrate_camo_history(history: Vec<LegacyCamoTxSummary>) -> Vec<CamoTxSummary> {
+    history.into_iter().map(CamoTxSummary::from).collect()
+}
+
+/// Wallet data as stored by v0.1.1, used to migrate older wallets
+#[derive(Debug, Zeroize, Deserialize)]
+struct LegacyWalletData {
+    seed: WalletSeed,
+    wallet_db: LegacyWalletDB,
+    frontiers_db: FrontiersDB,
+    #[zeroize(skip)]
+    cached_receivable: HashMap<[u8; 32], Receivable>,
+    camo_history: Vec<LegacyCamoTxSummary>,
+}
+impl From<LegacyWalletData> for WalletData {
+    fn from(value: LegacyWalletData) -> Self {
+        WalletData {
+            seed: value.seed,
+            wallet_db: value.wallet_db.into(),
+            frontiers_db: value.frontiers_db,
+            cached_receivable: value.cached_receivable,
+            camo_history: migrate_camo_history(value.camo_history),
+            activity: vec![],
+            address_book: AddressBook::default(),
+            history_cache: HistoryCache::default(),
+            rescan_progress: vec![],
         }
     }
 }
 
+/// Deserialize bytes serialized with `bincode::serialize()`, rejecting any trailing bytes
+fn deserialize_exact<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, ClientError> {
+    Ok(bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .reject_trailing_bytes()
+        .deserialize(bytes)?)
+}
+
+/// Deserialize wallet data stored in the given format version (see `EncryptedWallet::version`)
+fn deserialize_wallet_data(bytes: &[u8], version: u32) -> Result<WalletData, ClientError> {
+    match version {
+        LEGACY_WALLET_DATA_VERSION => Ok(deserialize_exact::<LegacyWalletData>(bytes)?.into()),
+        WALLET_DATA_VERSION => deserialize_exact(bytes),
+        _ => Err(ClientError::UnsupportedWalletVersion(version)),
+    }
+}
+
 #[derive(Debug, Clone, Zeroize, ZeroizeOnDrop, Serialize, Deserialize)]
 pub struct EncryptedWallet {
     pub id: String,
+    /// Identifier of the `KeyDerivation` used (added in v0.1.2)
+    #[serde(default = "legacy_key_derivation")]
+    pub kdf: String,
+    /// Parameters of the `KeyDerivation` used (added in v0.1.2)
+    #[serde(default)]
+    pub kdf_params: KdfParams,
+    /// Format version of the encrypted `WalletData` (added in v0.1.2)
+    #[serde(default)]
+    pub version: u32,
     pub salt: String,
     pub nonce: String,
     pub data: String,
 }
 impl EncryptedWallet {
     pub fn decrypt(&self, key: &SecretBytes<32>) -> Result<WalletData, ClientError> {
+        let kdf = KeyDerivation::from_id(&self.kdf)?;
         let salt = hex::decode(&self.salt)?;
         let nonce = hex::decode(&self.nonce)?;
         let nonce = Nonce::from_slice(&nonce);
-        let key = key_hash(key.as_bytes(), &salt)?;
+        let key = kdf.key_hash(key.as_bytes(), &salt, &self.kdf_params)?;
 
         let cipher = Aes256Gcm::new(&key);
         let mut data = hex::decode(&self.data)?;
@@ -95,9 +295,349 @@ impl EncryptedWallet {
             .decrypt(nonce, data.as_ref())
             .map_err(ClientError::InvalidPassword)?;
 
-        let wallet: WalletData = bincode::deserialize(&plaintext)?;
+        let wallet = deserialize_wallet_data(&plaintext, self.version);
         plaintext.zeroize();
         data.zeroize();
-        Ok(wallet)
+        wallet
+    }
+}
+
+/// Columns of the CSV written by `export_history_csv`
+const HISTORY_CSV_HEADER: &str =
+    "timestamp,height,account,type,counterparty,amount_raw,amount_nano,hash,note";
+
+/// Format an account's history (newest first, as returned by `account_history`) as CSV rows.
+///
+/// `infos` maps block hashes to their `(timestamp, height)`, where known.
+/// Camo notification blocks from `camo_history` are annotated with their notification.
+fn history_csv_rows(
+    account: &Account,
+    history: &[Block],
+    infos: &HashMap<[u8; 32], (u64, usize)>,
+    camo_history: &[CamoTxSummary],
+) -> Vec<String> {
+    let mut rows = vec![];
+    for (i, block) in history.iter().enumerate() {
+        let hash = block.hash();
+        let (timestamp, height) = match infos.get(&hash) {
+            Some((timestamp, height)) => (timestamp.to_string(), height.to_string()),
+            None => (String::new(), String::new()),
+        };
+
+        let counterparty = match block.block_type {
+            BlockType::Send => Account::from_bytes(block.link)
+                .map(|recipient| recipient.to_string())
+                .unwrap_or_default(),
+            BlockType::Receive => hex::encode_upper(block.link),
+            _ => block.representative.to_string(),
+        };
+
+        // amounts are signed, and empty if the balance before this block is unknown
+        let previous_balance = match history.get(i + 1) {
+            Some(previous) => Some(previous.balance),
+            // the first block of an account has no previous balance
+            None if block.previous == [0; 32] => Some(0),
+            None => None,
+        };
+        let (amount_raw, amount_nano) = match previous_balance {
+            Some(previous) if block.balance >= previous => {
+                let amount = block.balance - previous;
+                (amount.to_string(), Amount::from(amount).to_string())
+            }
+            Some(previous) => {
+                let amount = previous - block.balance;
+                (format!("-{amount}"), format!("-{}", Amount::from(amount)))
+            }
+            None => (String::new(), String::new()),
+        };
+
+        // the notification is encoded as the representative of the notifier's send block
+        let note = camo_history
+            .iter()
+            .filter(|_| block.block_type == BlockType::Send)
+            .find(|summary| summary.notification == block.representative.compressed.to_bytes())
+            .map(|summary| {
+                format!(
+                    "camo notification {} for {}",
+                    hex::encode(summary.notification),
+                    summary.recipient
+                )
+            })
+            .unwrap_or_default();
+
+        rows.push(
+            [
+                timestamp,
+                height,
+                account.to_string(),
+                block.block_type.to_string(),
+                counterparty,
+                amount_raw,
+                amount_nano,
+                hex::encode_upper(hash),
+                note,
+            ]
+            .join(","),
+        );
+    }
+    rows
+}
+
+/// Download the full history of each of the wallet's `nano_` accounts, and write it to `path` as CSV
+/// (see `HISTORY_CSV_HEADER`), e.g. for accounting software. Returns the number of blocks written.
+///
+/// Amounts are given both in raw, for precision, and in Nano. Sends are negative.
+pub async fn export_history_csv(client: &mut Client, path: &Path) -> Result<usize, ClientError> {
+    let core_client = &mut client.core;
+    let batch_size = core_client.config.RPC_ACCOUNT_HISTORY_BATCH_SIZE.max(1);
+
+    let mut csv = vec![HISTORY_CSV_HEADER.to_string()];
+    for account in core_client.wallet_db.all_nano_accounts() {
+        let mut history: Vec<Block> = vec![];
+        loop {
+            let page = RpcManager()
+                .account_history(
+                    &core_client.config,
+                    &account,
+                    batch_size,
+                    None,
+                    Some(history.len()),
+                )
+                .await?;
+            let page = core_client.handle_rpc_success(page);
+            let done = page.len() < batch_size;
+            history.extend(page);
+            if done {
+                break;
+            }
+        }
+        if history.is_empty() {
+            continue;
+        }
+
+        let hashes: Vec<[u8; 32]> = history.iter().map(|block| block.hash()).collect();
+        let infos = RpcManager()
+            .blocks_info(&core_client.config, &hashes)
+            .await?;
+        let infos: HashMap<[u8; 32], (u64, usize)> = core_client
+            .handle_rpc_success(infos)
+            .into_iter()
+            .flatten()
+            .map(|info| (info.block.hash(), (info.timestamp, info.height)))
+            .collect();
+        csv.extend(history_csv_rows(
+            &account,
+            &history,
+            &infos,
+            &client.camo_history,
+        ));
+    }
+
+    fs::write(path, csv.join("\n") + "\n")?;
+    Ok(csv.len() - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core_client::constants::ONE_NANO;
+    use core_client::wallet::{AccountDB, CamoAccountDB, DerivedAccountDB};
+    use core_client::{CamoAccount, Signature};
+
+    /// `WalletDB` as serialized before watch-only accounts were added
+    fn legacy_wallet_db() -> (AccountDB, CamoAccountDB, DerivedAccountDB) {
+        (
+            AccountDB::default(),
+            CamoAccountDB::default(),
+            DerivedAccountDB::default(),
+        )
+    }
+
+    fn wallet_data(seed: [u8; 32]) -> WalletData {
+        WalletData {
+            seed: WalletSeed::from(seed),
+            wallet_db: WalletDB::default(),
+            frontiers_db: FrontiersDB::default(),
+            cached_receivable: HashMap::new(),
+            camo_history: vec![],
+            activity: vec![],
+            address_book: AddressBook::default(),
+            history_cache: HistoryCache::default(),
+            rescan_progress: vec![],
+        }
+    }
+
+    /// Cheap parameters, to keep the tests fast
+    fn test_kdf_params() -> KdfParams {
+        KdfParams {
+            memory_kib: 1024,
+            iterations: 1,
+            parallelism: 1,
+        }
+    }
+
+    #[test]
+    fn encryption_round_trip() {
+        let key = SecretBytes::from([3; 32]);
+        let encrypted = wallet_data([7; 32])
+            .encrypt("wallet", &key, test_kdf_params())
+            .unwrap();
+
+        let decrypted = encrypted.decrypt(&key).unwrap();
+        assert!(decrypted.same_as(&wallet_data([7; 32])).unwrap());
+        assert!(!decrypted.same_as(&wallet_data([8; 32])).unwrap());
+        assert!(encrypted.decrypt(&SecretBytes::from([4; 32])).is_err());
+    }
+
+    #[test]
+    fn key_derivation() {
+        let key = SecretBytes::from([3; 32]);
+        let mut encrypted = wallet_data([7; 32])
+            .encrypt("wallet", &key, test_kdf_params())
+            .unwrap();
+        assert!(encrypted.kdf == KeyDerivation::Argon2id.id());
+        assert!(encrypted.kdf_params == test_kdf_params());
+
+        // the stored parameters are used for decryption
+        let mut tampered = encrypted.clone();
+        tampered.kdf_params.iterations = 2;
+        assert!(matches!(
+            tampered.decrypt(&key),
+            Err(ClientError::InvalidPassword(_))
+        ));
+
+        encrypted.kdf = "scrypt-unknown".into();
+        assert!(matches!(
+            encrypted.decrypt(&key),
+            Err(ClientError::UnsupportedKeyDerivation(_))
+        ));
+    }
+
+    #[test]
+    fn decrypt_legacy_wallet() {
+        // v0.1.1 derived the key with `Argon2::default()`
+        let mut expected = [0_u8; 32];
+        Argon2::default()
+            .hash_password_into(&[3; 32], &[5; 32], &mut expected)
+            .unwrap();
+        let derived = KeyDerivation::Argon2id
+            .key_hash(&[3; 32], &[5; 32], &KdfParams::default())
+            .unwrap();
+        assert!(derived.as_slice() == expected);
+
+        let key = SecretBytes::from([3; 32]);
+        let encrypted = wallet_data([7; 32])
+            .encrypt("wallet", &key, KdfParams::default())
+            .unwrap();
+
+        // wallets saved before the identifier and parameters were added
+        let mut json = serde_json::to_value(&encrypted).unwrap();
+        json.as_object_mut().unwrap().remove("kdf");
+        json.as_object_mut().unwrap().remove("kdf_params");
+        let legacy: EncryptedWallet = serde_json::from_value(json).unwrap();
+        assert!(legacy.kdf == KeyDerivation::Argon2id.id());
+        assert!(legacy.kdf_params == KdfParams::default());
+        assert!(legacy.decrypt(&key).unwrap().same_as(&wallet_data([7; 32])).unwrap());
+    }
+
+    #[test]
+    fn deserialize_legacy_wallet_data() {
+        let seed = WalletSeed::from([7; 32]);
+        let camo: CamoAccount = "camo_18wydi3gmaw4aefwhkijrjw4qd87i4tc85wbnij95gz4em3qssickhpoj9i4t6taqk46wdnie7aj8ijrjhtcdgsp3c1oqnahct3otygxx4k7f3o4".parse().unwrap();
+        // `LegacyCamoTxSummary`: recipient, camo amount, total amount, and notification
+        let legacy_summary = (camo.clone(), 1_u128, 2_u128, [4_u8; 32]);
+        let legacy = (
+            seed.clone(),
+            legacy_wallet_db(),
+            FrontiersDB::default(),
+            HashMap::<[u8; 32], Receivable>::new(),
+            vec![legacy_summary],
+        );
+        let bytes = bincode::serialize(&legacy).unwrap();
+
+        let wallet = deserialize_wallet_data(&bytes, LEGACY_WALLET_DATA_VERSION).unwrap();
+        assert!(wallet.seed.as_hex() == seed.as_hex());
+        assert!(wallet.activity.is_empty());
+        assert!(wallet.address_book.contacts().is_empty());
+
+        // camo history recorded before the sender and notifier were stored
+        assert!(wallet.camo_history.len() == 1);
+        let summary = &wallet.camo_history[0];
+        assert!(summary.recipient == camo);
+        assert!(summary.camo_amount == 1);
+        assert!(summary.total_amount == 2);
+        assert!(summary.notification == [4; 32]);
+        assert!(summary.sender.is_none());
+        assert!(summary.notifier.is_none());
+
+        // the version is not guessed from the data
+        assert!(deserialize_wallet_data(&bytes, WALLET_DATA_VERSION).is_err());
+        let bytes = bincode::serialize(&wallet_data([7; 32])).unwrap();
+        assert!(deserialize_wallet_data(&bytes, LEGACY_WALLET_DATA_VERSION).is_err());
+        assert!(matches!(
+            deserialize_wallet_data(&bytes, WALLET_DATA_VERSION + 1),
+            Err(ClientError::UnsupportedWalletVersion(_))
+        ));
+    }
+
+    #[test]
+    fn history_csv() {
+        let account = core_client::Key::from_seed(&[9; 32].into(), 0).to_account();
+        let recipient = core_client::Key::from_seed(&[9; 32].into(), 1).to_account();
+        let payload = core_client::Key::from_seed(&[9; 32].into(), 2).to_account();
+        let camo: CamoAccount = "camo_18wydi3gmaw4aefwhkijrjw4qd87i4tc85wbnij95gz4em3qssickhpoj9i4t6taqk46wdnie7aj8ijrjhtcdgsp3c1oqnahct3otygxx4k7f3o4".parse().unwrap();
+        let summary = CamoTxSummary {
+            recipient: camo.clone(),
+            camo_amount: 1,
+            total_amount: 2,
+            notification: payload.compressed.to_bytes(),
+            sender: None,
+            notifier: None,
+        };
+
+        let open = Block {
+            block_type: BlockType::Receive,
+            account: account.clone(),
+            previous: [0; 32],
+            representative: recipient.clone(),
+            balance: 10 * ONE_NANO,
+            link: [5; 32],
+            signature: Signature::default(),
+            work: [0; 8],
+        };
+        let send = Block {
+            block_type: BlockType::Send,
+            account: account.clone(),
+            previous: open.hash(),
+            representative: payload.clone(),
+            balance: 7 * ONE_NANO + ONE_NANO / 2,
+            link: recipient.compressed.to_bytes(),
+            signature: Signature::default(),
+            work: [0; 8],
+        };
+        let infos = HashMap::from([(send.hash(), (1700000000, 2))]);
+
+        let rows = history_csv_rows(&account, &[send.clone(), open.clone()], &infos, &[summary]);
+        assert!(rows.len() == 2);
+        assert!(
+            rows[0]
+                == format!(
+                    "1700000000,2,{account},send,{recipient},-2500000000000000000000000000000,-2.5,{},camo notification {} for {camo}",
+                    hex::encode_upper(send.hash()),
+                    hex::encode(payload.compressed.to_bytes())
+                )
+        );
+        assert!(
+            rows[1]
+                == format!(
+                    ",,{account},receive,{},10000000000000000000000000000000,10,{},",
+                    hex::encode_upper([5; 32]),
+                    hex::encode_upper(open.hash())
+                )
+        );
+
+        // the balance before the oldest block is unknown
+        let rows = history_csv_rows(&account, std::slice::from_ref(&send), &infos, &[]);
+        assert!(rows[0].contains(&format!(",send,{recipient},,,")));
     }
 }
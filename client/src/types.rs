@@ -1,32 +1,110 @@
 use super::error::ClientError;
 use core_client::constants::ONE_NANO;
-use core_client::{nanopyrs::NanoError, Account, CamoAccount, CamoVersion};
+use core_client::{nanopyrs::NanoError, Account, CamoAccount, CamoVersion, CoreClientError};
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
+use std::marker::PhantomData;
 use std::str::FromStr;
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
+/// Displayed instead of amounts in private mode
+pub const MASKED_AMOUNT: &str = "••••";
+
 #[derive(Debug, Clone, PartialEq, Eq, Zeroize, ZeroizeOnDrop, Serialize, Deserialize)]
 pub struct CamoTxSummary {
     pub recipient: CamoAccount,
     pub camo_amount: u128,
     pub total_amount: u128,
     pub notification: [u8; 32],
+    /// Used to retry the payment.
+    /// `None` for payments recorded before v0.1.2.
+    pub sender: Option<Account>,
+    /// Used to retry the payment.
+    /// `None` for payments recorded before v0.1.2.
+    pub notifier: Option<Account>,
 }
-impl Display for CamoTxSummary {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
+impl CamoTxSummary {
+    /// Display the payment, masking its amounts if `mask` is set
+    pub fn display(&self, mask: bool) -> String {
+        format!(
             "Sending {} {} Nano ({} total) with notification {}",
             self.recipient,
-            Amount::from(self.camo_amount),
-            Amount::from(self.total_amount),
+            Amount::from(self.camo_amount).display(mask),
+            Amount::from(self.total_amount).display(mask),
             hex::encode(self.notification)
         )
     }
 }
+impl Display for CamoTxSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.display(false))
+    }
+}
 
-#[derive(Debug, Clone)]
+/// Camo transaction summary as stored before v0.1.2, used to migrate older wallets
+#[derive(Debug, Zeroize, Deserialize)]
+pub(crate) struct LegacyCamoTxSummary {
+    recipient: CamoAccount,
+    camo_amount: u128,
+    total_amount: u128,
+    notification: [u8; 32],
+}
+impl From<LegacyCamoTxSummary> for CamoTxSummary {
+    fn from(value: LegacyCamoTxSummary) -> Self {
+        CamoTxSummary {
+            recipient: value.recipient,
+            camo_amount: value.camo_amount,
+            total_amount: value.total_amount,
+            notification: value.notification,
+            sender: None,
+            notifier: None,
+        }
+    }
+}
+
+/// Local, purely informational activity counters for an account
+#[derive(Debug, Clone, PartialEq, Eq, Zeroize, ZeroizeOnDrop, Serialize, Deserialize)]
+pub struct AccountActivity {
+    pub account: Account,
+    /// Number of send blocks published by this wallet
+    pub sends: u64,
+    /// Number of receive blocks published by this wallet
+    pub receives: u64,
+    /// UNIX timestamp (in seconds) of the last published block
+    pub last_activity: u64,
+}
+impl AccountActivity {
+    pub fn new(account: Account) -> AccountActivity {
+        AccountActivity {
+            account,
+            sends: 0,
+            receives: 0,
+            last_activity: 0,
+        }
+    }
+}
+
+/// How far back the notification history of a camo account has been rescanned
+#[derive(Debug, Clone, PartialEq, Eq, Zeroize, ZeroizeOnDrop, Serialize, Deserialize)]
+pub struct RescanProgress {
+    pub camo_account: CamoAccount,
+    /// The block to resume scanning from
+    pub last_scanned_head: [u8; 32],
+}
+
+/// Running totals of a rescan over multiple pages of history, see `Client::rescan()`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RescanStatus {
+    /// Number of `account_history` pages scanned
+    pub pages: usize,
+    pub blocks_scanned: usize,
+    /// Number of derived accounts found (after filtering, if enabled)
+    pub accounts_found: usize,
+    /// The block to continue scanning from, or `None` if the start of the history was reached
+    pub head: Option<[u8; 32]>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Zeroize, Serialize, Deserialize)]
 pub enum ParsedAccount {
     Nano(Account),
     Camo(CamoAccount),
@@ -39,6 +117,130 @@ impl FromStr for ParsedAccount {
         account.or(camo)
     }
 }
+impl Display for ParsedAccount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParsedAccount::Nano(account) => write!(f, "{account}"),
+            ParsedAccount::Camo(account) => write!(f, "{account}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Zeroize, ZeroizeOnDrop, Serialize, Deserialize)]
+pub struct Contact {
+    pub label: String,
+    pub account: ParsedAccount,
+}
+
+/// Labelled accounts, which can be used in place of account addresses
+#[derive(Debug, Clone, Default, Zeroize, ZeroizeOnDrop, Serialize, Deserialize)]
+pub struct AddressBook {
+    contacts: Vec<Contact>,
+}
+impl AddressBook {
+    /// Labels must be alphanumeric, so that they can never be mistaken for an account
+    pub fn is_valid_label(label: &str) -> bool {
+        !label.is_empty() && label.chars().all(|c| c.is_alphanumeric())
+    }
+
+    pub fn contacts(&self) -> &[Contact] {
+        &self.contacts
+    }
+
+    pub fn get(&self, label: &str) -> Option<&ParsedAccount> {
+        self.contacts
+            .iter()
+            .find(|contact| contact.label == label)
+            .map(|contact| &contact.account)
+    }
+
+    /// Add a contact, returning `Err` if the label is invalid or already in use
+    pub fn add(&mut self, label: String, account: ParsedAccount) -> Result<(), ClientError> {
+        if !AddressBook::is_valid_label(&label) {
+            return Err(ClientError::InvalidContactLabel);
+        }
+        if self.get(&label).is_some() {
+            return Err(ClientError::ContactAlreadyExists);
+        }
+        self.contacts.push(Contact { label, account });
+        Ok(())
+    }
+
+    /// Remove a contact, returning it
+    pub fn remove(&mut self, label: &str) -> Result<Contact, ClientError> {
+        let index = self
+            .contacts
+            .iter()
+            .position(|contact| contact.label == label)
+            .ok_or_else(|| ClientError::ContactNotFound(label.into()))?;
+        Ok(self.contacts.remove(index))
+    }
+}
+
+/// Account types which can be stored in the address book
+pub trait FromContact: FromStr + Sized {
+    fn from_contact(account: &ParsedAccount) -> Option<Self>;
+}
+impl FromContact for Account {
+    fn from_contact(account: &ParsedAccount) -> Option<Self> {
+        match account {
+            ParsedAccount::Nano(account) => Some(account.clone()),
+            ParsedAccount::Camo(_) => None,
+        }
+    }
+}
+impl FromContact for CamoAccount {
+    fn from_contact(account: &ParsedAccount) -> Option<Self> {
+        match account {
+            ParsedAccount::Camo(account) => Some(account.clone()),
+            ParsedAccount::Nano(_) => None,
+        }
+    }
+}
+
+/// An account, or the label of a contact in the address book
+#[derive(Debug, Clone)]
+pub struct AccountOrContact<T: FromContact> {
+    input: String,
+    _account: PhantomData<T>,
+}
+impl<T: FromContact> AccountOrContact<T> {
+    /// Parse the account, looking up the address book if a label was given
+    pub fn resolve(&self, address_book: &AddressBook) -> Result<T, ClientError> {
+        if let Ok(account) = self.input.parse::<T>() {
+            return Ok(account);
+        }
+        address_book
+            .get(&self.input)
+            .and_then(T::from_contact)
+            .ok_or_else(|| ClientError::ContactNotFound(self.input.clone()))
+    }
+}
+impl<T: FromContact> FromStr for AccountOrContact<T> {
+    type Err = ClientError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if T::from_str(s).is_err() && !AddressBook::is_valid_label(s) {
+            return Err(ClientError::InvalidArguments);
+        }
+        Ok(AccountOrContact {
+            input: s.into(),
+            _account: PhantomData,
+        })
+    }
+}
+
+/// A `nano_` account, or `None` if 'any' was given (select one automatically)
+#[derive(Debug, Clone)]
+pub struct SenderAccount(pub Option<Account>);
+impl FromStr for SenderAccount {
+    type Err = NanoError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "any" {
+            return Ok(SenderAccount(None));
+        }
+        Ok(SenderAccount(Some(Account::from_str(s)?)))
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct ParsedCamoVersion(pub CamoVersion);
@@ -70,6 +272,15 @@ impl From<Hex32Bytes> for [u8; 32] {
 pub struct Amount {
     pub value: u128,
 }
+impl Amount {
+    /// Display the amount in Nano, or `MASKED_AMOUNT` if `mask` is set
+    pub fn display(&self, mask: bool) -> String {
+        match mask {
+            true => MASKED_AMOUNT.into(),
+            false => self.to_string(),
+        }
+    }
+}
 impl From<Amount> for u128 {
     fn from(value: Amount) -> Self {
         value.value
@@ -119,10 +330,77 @@ impl Display for Amount {
     }
 }
 
+/// An exact amount, or 'all' (or 'max') for the account's full balance
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AmountSpec {
+    Exact(Amount),
+    Max,
+}
+impl AmountSpec {
+    /// Resolve to an exact amount, given the sending account's balance (`None` if unopened).
+    /// `reserve` is left on the account if sending its full balance.
+    pub fn resolve(&self, balance: Option<u128>, reserve: u128) -> Result<u128, ClientError> {
+        match self {
+            AmountSpec::Exact(amount) => Ok(amount.value),
+            AmountSpec::Max => match balance.map(|balance| balance.saturating_sub(reserve)) {
+                Some(amount) if amount > 0 => Ok(amount),
+                _ => Err(CoreClientError::NotEnoughCoins.into()),
+            },
+        }
+    }
+}
+impl FromStr for AmountSpec {
+    type Err = ClientError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" | "max" => Ok(AmountSpec::Max),
+            _ => Ok(AmountSpec::Exact(Amount::from_str(s)?)),
+        }
+    }
+}
+
+/// A payment read from a `batch_send` file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchPayment {
+    /// Line number in the file, starting from 1
+    pub line: usize,
+    pub recipient: Account,
+    pub amount: Amount,
+}
+impl BatchPayment {
+    /// Parse `account,amount` lines, which may also be tab-separated.
+    /// Blank lines and lines starting with `#` are skipped.
+    pub fn parse_file(contents: &str) -> Result<Vec<BatchPayment>, ClientError> {
+        let mut payments = vec![];
+        for (index, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = || ClientError::InvalidBatchLine(index + 1);
+            let (recipient, amount) = line
+                .split_once(',')
+                .or_else(|| line.split_once('\t'))
+                .ok_or_else(invalid)?;
+            payments.push(BatchPayment {
+                line: index + 1,
+                recipient: recipient.trim().parse().map_err(|_| invalid())?,
+                amount: amount.trim().parse().map_err(|_| invalid())?,
+            })
+        }
+        Ok(payments)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Amount;
-    use core_client::constants::*;
+    use super::{
+        AccountOrContact, AddressBook, Amount, AmountSpec, BatchPayment, ParsedAccount,
+        MASKED_AMOUNT,
+    };
+    use core_client::{constants::*, Account, Key};
 
     fn _amount_from_str(s: &str) -> u128 {
         s.parse::<Amount>().unwrap().value
@@ -169,4 +447,80 @@ mod tests {
         assert!(Amount::from(amount).to_string() == "10222.020022");
         assert!(Amount::from(amount).to_string() != "10222.020023");
     }
+
+    #[test]
+    fn amount_display() {
+        let amount = Amount::from(ONE_NANO * 83);
+        assert!(amount.display(false) == "83");
+        assert!(amount.display(true) == MASKED_AMOUNT);
+    }
+
+    #[test]
+    fn batch_payments() {
+        let account = Key::from_seed(&[3; 32].into(), 0).to_account();
+        let contents = format!("# recipient,amount\n{account},1.5\n\n{account}\t 2 \n");
+        let payments = BatchPayment::parse_file(&contents).unwrap();
+        assert!(payments.len() == 2);
+        assert!(payments[0].line == 2);
+        assert!(payments[0].recipient == account);
+        assert!(payments[0].amount.value == ONE_MILLI_NANO * 1500);
+        assert!(payments[1].line == 4);
+        assert!(payments[1].amount.value == ONE_NANO * 2);
+
+        let contents = format!("{account},1\n{account};1\n");
+        assert!(matches!(
+            BatchPayment::parse_file(&contents),
+            Err(crate::ClientError::InvalidBatchLine(2))
+        ));
+        assert!(BatchPayment::parse_file("nano_invalid,1").is_err());
+    }
+
+    #[test]
+    fn amount_spec() {
+        let all: AmountSpec = "all".parse().unwrap();
+        assert!(all == AmountSpec::Max);
+        assert!("max".parse::<AmountSpec>().unwrap() == AmountSpec::Max);
+        assert!(all.resolve(Some(ONE_NANO), 0).unwrap() == ONE_NANO);
+        assert!(all.resolve(Some(0), 0).is_err());
+        assert!(all.resolve(None, 0).is_err());
+        assert!(all.resolve(Some(ONE_NANO), ONE_RAW).unwrap() == ONE_NANO - ONE_RAW);
+        assert!(all.resolve(Some(ONE_NANO), ONE_NANO).is_err());
+
+        let exact: AmountSpec = "1.5".parse().unwrap();
+        assert!(exact.resolve(None, ONE_NANO).unwrap() == ONE_MILLI_NANO * 1500);
+        assert!("everything".parse::<AmountSpec>().is_err());
+    }
+
+    #[test]
+    fn address_book() {
+        let account = Key::from_seed(&[3; 32].into(), 0).to_account();
+        let other = Key::from_seed(&[4; 32].into(), 0).to_account();
+        let mut book = AddressBook::default();
+        book.add("alice".into(), ParsedAccount::Nano(account.clone()))
+            .unwrap();
+
+        assert!(book
+            .add("alice".into(), ParsedAccount::Nano(other.clone()))
+            .is_err());
+        assert!(book
+            .add("not valid".into(), ParsedAccount::Nano(other.clone()))
+            .is_err());
+        assert!(book
+            .add(String::new(), ParsedAccount::Nano(other.clone()))
+            .is_err());
+
+        let by_label: AccountOrContact<Account> = "alice".parse().unwrap();
+        assert!(by_label.resolve(&book).unwrap() == account);
+        let by_account: AccountOrContact<Account> = other.to_string().parse().unwrap();
+        assert!(by_account.resolve(&book).unwrap() == other);
+        let unknown: AccountOrContact<Account> = "bob".parse().unwrap();
+        assert!(unknown.resolve(&book).is_err());
+        assert!("nano_invalid!"
+            .parse::<AccountOrContact<Account>>()
+            .is_err());
+
+        book.remove("alice").unwrap();
+        assert!(by_label.resolve(&book).is_err());
+        assert!(book.remove("alice").is_err());
+    }
 }
@@ -0,0 +1,91 @@
+use super::history::CommandHistory;
+use clap::CommandFactory;
+use client::Command;
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::MemHistory;
+use rustyline::validate::Validator;
+use rustyline::{CompletionType, Config, Context, Editor, Helper};
+
+pub type LineEditor = Editor<CommandCompleter, MemHistory>;
+
+/// Create the line editor for the interactive CLI, with up-arrow recall of `history`.
+///
+/// The editor only keeps its history in memory: `CommandHistory` decides what is saved to disk.
+pub fn line_editor(history: &CommandHistory) -> rustyline::Result<LineEditor> {
+    let config = Config::builder()
+        .auto_add_history(false)
+        .completion_type(CompletionType::List)
+        .build();
+    let mut editor = Editor::with_history(config, MemHistory::new())?;
+    editor.set_helper(Some(CommandCompleter(Command::command())));
+    for command in history.commands() {
+        editor.add_history_entry(command.as_str())?;
+    }
+    Ok(editor)
+}
+
+/// Tab completion of (sub)command names
+pub struct CommandCompleter(clap::Command);
+impl CommandCompleter {
+    /// Returns the start of the word being completed, and the names it could be completed to
+    fn completions(&self, line: &str) -> (usize, Vec<String>) {
+        let start = line.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let partial = &line[start..];
+
+        // find the (sub)command whose subcommands are being typed
+        let mut command = &self.0;
+        for word in line[..start].split_whitespace() {
+            match command.find_subcommand(word) {
+                Some(subcommand) => command = subcommand,
+                None => return (start, vec![]),
+            }
+        }
+
+        let names = command
+            .get_subcommands()
+            .filter(|subcommand| !subcommand.is_hide_set())
+            .map(|subcommand| subcommand.get_name())
+            .filter(|name| name.starts_with(partial))
+            .map(|name| name.to_owned())
+            .collect();
+        (start, names)
+    }
+}
+impl Completer for CommandCompleter {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.completions(&line[..pos]))
+    }
+}
+impl Hinter for CommandCompleter {
+    type Hint = String;
+}
+impl Highlighter for CommandCompleter {}
+impl Validator for CommandCompleter {}
+impl Helper for CommandCompleter {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completions() {
+        let completer = CommandCompleter(Command::command());
+        assert!(completer.completions("qui") == (0, vec!["quit".to_owned()]));
+        assert!(completer.completions("rpc ad") == (4, vec!["add".to_owned()]));
+        assert!(completer.completions("  rpc  ad") == (7, vec!["add".to_owned()]));
+        assert!(completer.completions("nonexistent ad") == (12, vec![]));
+
+        let (start, names) = completer.completions("");
+        assert!(start == 0);
+        assert!(names.contains(&"balance".to_owned()));
+    }
+}
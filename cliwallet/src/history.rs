@@ -0,0 +1,104 @@
+use log::warn;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Maximum number of commands kept in the history file
+const HISTORY_LIMIT: usize = 1000;
+
+/// Commands entered into the interactive CLI, persisted to a file (if any).
+///
+/// The file is plaintext, so it is only used if the user opts in.
+/// Passwords are read separately, and are never recorded.
+#[derive(Debug, Default)]
+pub struct CommandHistory {
+    path: Option<PathBuf>,
+    commands: Vec<String>,
+}
+impl CommandHistory {
+    /// Load the history from `path`, or only keep it in memory if `path` is `None`
+    pub fn load(path: Option<PathBuf>) -> CommandHistory {
+        let Some(path) = path else {
+            return CommandHistory::default();
+        };
+
+        // the file will not exist the first time the CLI is used
+        let contents = fs::read_to_string(&path).unwrap_or_default();
+        let mut commands: Vec<String> = contents.lines().map(|line| line.to_owned()).collect();
+        if commands.len() > HISTORY_LIMIT {
+            commands.drain(..commands.len() - HISTORY_LIMIT);
+            if let Err(err) = fs::write(&path, commands.join("\n") + "\n") {
+                warn!("Failed to truncate command history file: {err}");
+            }
+        }
+        CommandHistory {
+            path: Some(path),
+            commands,
+        }
+    }
+
+    /// The recorded commands, oldest first
+    pub fn commands(&self) -> &[String] {
+        &self.commands
+    }
+
+    /// Expand `!!` to the previous command, and `!-n` to the `n`th previous command.
+    /// Other input is returned as-is.
+    ///
+    /// Returns `None` if the referenced command is not in the history.
+    pub fn expand(&self, input: &str) -> Option<String> {
+        let back = match input.trim() {
+            "!!" => 1,
+            trimmed => match trimmed.strip_prefix("!-") {
+                Some(n) => n.parse::<usize>().ok().filter(|n| *n > 0)?,
+                None => return Some(input.to_owned()),
+            },
+        };
+        let index = self.commands.len().checked_sub(back)?;
+        Some(self.commands[index].clone())
+    }
+
+    /// Record a command, skipping empty commands and immediate repeats
+    pub fn push(&mut self, command: &str) {
+        let command = command.trim();
+        if command.is_empty() || self.commands.last().is_some_and(|last| last == command) {
+            return;
+        }
+        self.commands.push(command.to_owned());
+
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| writeln!(file, "{command}"));
+        if let Err(err) = result {
+            warn!("Failed to save command history: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CommandHistory;
+
+    #[test]
+    fn expand() {
+        let mut history = CommandHistory::default();
+        assert!(history.expand("!!").is_none());
+        assert!(history.expand("balance").as_deref() == Some("balance"));
+
+        history.push("balance");
+        history.push("refresh\n");
+        history.push("refresh");
+        history.push("  ");
+        assert!(history.expand("!!").as_deref() == Some("refresh"));
+        assert!(history.expand("!-1").as_deref() == Some("refresh"));
+        assert!(history.expand("!-2").as_deref() == Some("balance"));
+        assert!(history.expand("!-3").is_none());
+        assert!(history.expand("!-0").is_none());
+        assert!(history.expand("!-x").is_none());
+    }
+}
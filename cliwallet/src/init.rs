@@ -1,8 +1,9 @@
 use super::error::CliError;
+use super::history::CommandHistory;
 use super::logging::{LevelFilter, Logger};
 use super::storage::{
-    config_location, delete_wallet, get_wallet_names, init_files, load_wallet, save_config,
-    save_wallet, wallet_exists,
+    config_location, delete_wallet, get_wallet_names, history_location, init_files, load_wallet,
+    save_config, save_wallet, wallet_exists,
 };
 use super::CliClient;
 use clap::{Args, Parser, Subcommand};
@@ -12,6 +13,7 @@ use client::{
     ClientConfig, ClientError,
 };
 use nanopyrs::hashes::blake2b256;
+use std::path::PathBuf;
 use zeroize::Zeroize;
 
 pub fn prompt_password() -> Result<SecretBytes<32>, ClientError> {
@@ -44,9 +46,48 @@ pub struct Init {
     /// Levels: 'off', 'error', 'warn', 'info', 'debug', 'trace'
     #[arg(long, default_value_t = LevelFilter::Info)]
     log: LevelFilter,
+    /// Wallet command to execute after loading, instead of starting the interactive CLI.
+    /// May be given multiple times: commands are executed in order, then the wallet exits.
+    #[arg(long, global = true)]
+    exec: Vec<String>,
+    /// Save the history of the interactive CLI's commands, so that it can be recalled in later sessions.
+    /// The history file is NOT encrypted: anyone who can read it can see the accounts and amounts used.
+    #[arg(long, global = true, default_value_t = false)]
+    save_history: bool,
+    /// File in which to save the command history (implies '--save-history').
+    /// Defaults to 'history.txt' next to the configuration file.
+    #[arg(long, global = true)]
+    history_file: Option<PathBuf>,
+    /// Mask balances and amounts in the output of commands, unless they are given '--reveal'
+    #[arg(long, global = true, default_value_t = false)]
+    private: bool,
 }
 impl Init {
-    pub fn execute(self) -> Result<(Option<CliClient>, Logger), CliError> {
+    pub fn private(&self) -> bool {
+        self.private
+    }
+
+    /// Load the command history, as configured by `--save-history` and `--history-file`.
+    /// By default, the history is only kept in memory.
+    pub fn history(&self) -> CommandHistory {
+        if !self.save_history && self.history_file.is_none() {
+            return CommandHistory::load(None);
+        }
+        let path = match &self.history_file {
+            Some(path) => Some(path.clone()),
+            None => match history_location() {
+                Ok(path) => Some(path),
+                Err(err) => {
+                    println!("Failed to find command history file: {err}");
+                    None
+                }
+            },
+        };
+        CommandHistory::load(path)
+    }
+
+    /// Returns the client (if any), the logger, and the commands given with `--exec`
+    pub fn execute(self) -> Result<(Option<CliClient>, Logger, Vec<String>), CliError> {
         let client = match self.command {
             InitType::New(args) => args.execute(),
             InitType::Import(args) => args.execute(),
@@ -59,7 +100,7 @@ impl Init {
         // load files to ensure they've been created
         init_files()?;
 
-        Ok((client, self.log.into()))
+        Ok((client, self.log.into(), self.exec))
     }
 }
 
@@ -106,16 +147,29 @@ struct ImportArgs {
     /// Name of the wallet that will be created
     name: String,
     /// The 64-character hexadecimal seed to be imported
-    seed: Seed,
+    #[arg(required_unless_present = "mnemonic")]
+    seed: Option<Seed>,
+    /// Import a 24-word mnemonic (in quotes) instead of a hexadecimal seed
+    #[arg(short, long, conflicts_with = "seed")]
+    mnemonic: Option<String>,
 }
 impl ImportArgs {
-    fn execute(self) -> Result<Option<CliClient>, CliError> {
+    fn execute(mut self) -> Result<Option<CliClient>, CliError> {
         if wallet_exists(&self.name)? {
             return Err(CliError::WalletAlreadyExists);
         }
 
+        // check the seed before prompting for a password
+        let seed = match (&self.seed, &mut self.mnemonic) {
+            (Some(seed), _) => WalletSeed::from(seed.0),
+            (None, Some(mnemonic)) => {
+                let seed = WalletSeed::from_mnemonic(mnemonic);
+                mnemonic.zeroize();
+                seed.map_err(ClientError::from)?
+            }
+            (None, None) => unreachable!("clap requires a seed or mnemonic"),
+        };
         let key = prompt_confirmed_password()?;
-        let seed = WalletSeed::from(self.seed.0);
 
         let cli_client = CliClient::new(seed, self.name, key)?;
         save_wallet(&cli_client, &cli_client.name, &cli_client.key)?;
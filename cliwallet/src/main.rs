@@ -1,6 +1,8 @@
 #![warn(unused_crate_dependencies, unsafe_code)]
 
+mod editor;
 mod error;
+mod history;
 mod init;
 mod logging;
 mod storage;
@@ -10,13 +12,15 @@ use client::{
     core::{SecretBytes, WalletSeed},
     Client, ClientError, Command, WalletFrontend,
 };
+use editor::line_editor;
 use error::CliError;
+use history::CommandHistory;
 use init::{prompt_password, Init};
 use log::debug;
-use std::io::{stdin, stdout, Write};
+use rustyline::error::ReadlineError;
 use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
 use std::time::{Duration, Instant};
-use storage::{load_config, save_config, save_wallet_overriding};
+use storage::{load_config, load_wallet, save_config, save_wallet_overriding};
 use tokio::runtime::Runtime;
 use tokio::task;
 use zeroize::{Zeroize, ZeroizeOnDrop};
@@ -31,11 +35,18 @@ struct CliClient {
     name: String,
     key: SecretBytes<32>,
     client: Client,
+    /// Mask balances and amounts in the output of commands
+    private: bool,
 }
 impl CliClient {
     fn new(seed: WalletSeed, name: String, key: SecretBytes<32>) -> Result<CliClient, CliError> {
         let client = Client::new(seed, load_config()?)?;
-        Ok(CliClient { name, key, client })
+        Ok(CliClient {
+            name,
+            key,
+            client,
+            private: false,
+        })
     }
 
     fn save_to_disk(&mut self) -> Result<(), CliError> {
@@ -44,11 +55,26 @@ impl CliClient {
         save_wallet_overriding(self, &self.name, &self.key)
     }
 
-    async fn work_cache_loop(mut self, stop: Receiver<()>) -> Result<CliClient, CliError> {
+    /// How long the wallet may go without a command before it is locked, if at all
+    fn auto_lock_duration(&self) -> Option<Duration> {
+        match self.client.core.config.AUTO_LOCK_SECONDS {
+            0 => None,
+            seconds => Some(Duration::from_secs(seconds)),
+        }
+    }
+
+    /// Returns `Ok(None)` if the wallet was locked due to inactivity,
+    /// in which case it has been saved to disk and zeroized.
+    async fn work_cache_loop(
+        mut self,
+        stop: Receiver<()>,
+        last_command: Instant,
+    ) -> Result<Option<CliClient>, CliError> {
         // Try not to spam the disk:
         // Save at most once per 2 seconds.
         let mut last_save = Instant::now();
         let mut should_save = false;
+        let auto_lock = self.auto_lock_duration();
 
         loop {
             let message = stop.recv_timeout(Duration::from_millis(10));
@@ -62,47 +88,167 @@ impl CliClient {
                     last_save = Instant::now();
                     should_save = false;
                 }
+
+                // Lock the wallet: dropping `self` zeroizes the seed
+                if auto_lock.is_some_and(|duration| last_command.elapsed() >= duration) {
+                    self.save_to_disk()?;
+                    println!("\nWallet locked due to inactivity. Press enter to unlock.");
+                    return Ok(None);
+                }
             }
             // Yes stop signal
             else {
                 break;
             }
         }
-        Ok(self)
+        Ok(Some(self))
     }
 
-    async fn _start_cli(mut self) {
+    /// Reload a locked wallet from disk, prompting for the password until it is correct
+    fn unlock(name: &str) -> CliClient {
         loop {
-            print!("> ");
-            stdout().flush().expect("failed to flush stdout");
+            let result = prompt_password()
+                .map_err(CliError::from)
+                .and_then(|key| load_wallet(name, key));
+            match result {
+                Ok(cli_client) => return cli_client,
+                Err(err) => println!("{err:?}: {err}"),
+            }
+        }
+    }
+
+    /// If `CONFIRMATION_REQUIRED`, download the confirmation status of the wallet's frontiers.
+    /// It is not saved to disk, so the balances of a freshly loaded wallet are not counted until this is done.
+    async fn update_confirmed(&mut self) {
+        if !self.client.core.config.CONFIRMATION_REQUIRED {
+            return;
+        }
+        println!("Checking frontier confirmations...");
+        if let Err(err) = self.client.core.update_confirmed().await {
+            println!("Failed to check frontier confirmations: {err}");
+        }
+    }
+
+    async fn _start_cli(mut self, mut history: CommandHistory) {
+        // resume any work requests which were lost when the wallet was last closed
+        if let Err(err) = self.client.update_work_cache().await {
+            println!("Failed to resume work requests: {err}");
+        }
+        self.update_confirmed().await;
+        if self.client.core.config.REPUBLISH_ON_LOAD {
+            let progress =
+                |checked, total| println!("Checked {checked}/{total} unconfirmed blocks");
+            match self.client.core.republish_pending(progress).await {
+                Ok(republished) if !republished.is_empty() => {
+                    println!("Republished {} unconfirmed blocks", republished.len())
+                }
+                Ok(_) => (),
+                Err(err) => println!("Failed to republish unconfirmed blocks: {err}"),
+            }
+        }
 
+        let mut editor = line_editor(&history).expect("failed to start line editor");
+        let mut last_command = Instant::now();
+        loop {
             let (sender, receiver) = channel();
 
-            let work_cache_loop = task::spawn(self.work_cache_loop(receiver));
+            let name = self.name.clone();
+            let private = self.private;
+            let work_cache_loop = task::spawn(self.work_cache_loop(receiver, last_command));
 
-            let mut input = String::new();
-            stdin().read_line(&mut input).expect("failed to read stdin");
+            // Ctrl-C clears the line, and Ctrl-D exits
+            let input = match editor.readline("> ") {
+                Ok(input) => input,
+                Err(ReadlineError::Interrupted) => String::new(),
+                Err(ReadlineError::Eof) => "quit".into(),
+                Err(err) => panic!("failed to read stdin: {err}"),
+            };
 
-            sender.send(()).expect("Failed to stop work cache loop");
-            self = work_cache_loop
+            // the work cache loop will have already stopped if the wallet was locked
+            let _ = sender.send(());
+            let unlocked = work_cache_loop
                 .await
                 .expect("Failed to await work cache loop")
                 .expect("Error in work cache loop");
+            // any input given while the wallet was locked is discarded
+            let Some(cli_client) = unlocked else {
+                self = Self::unlock(&name);
+                self.private = private;
+                if let Err(err) = self.client.update_work_cache().await {
+                    println!("Failed to resume work requests: {err}");
+                }
+                self.update_confirmed().await;
+                last_command = Instant::now();
+                continue;
+            };
+            self = cli_client;
+
+            // `!!` and `!-n` recall previous commands
+            let Some(expanded) = history.expand(&input) else {
+                println!("No such command in history");
+                continue;
+            };
+            if expanded.trim() != input.trim() {
+                println!("> {expanded}");
+            }
+            history.push(&expanded);
+            if !expanded.trim().is_empty() {
+                let _ = editor.add_history_entry(expanded.trim());
+            }
+            let input = expanded;
+
+            if !self.execute_command(&input).await {
+                break;
+            }
+            last_command = Instant::now();
+        }
+    }
 
-            let result = Command::execute(&mut self, &input).await;
-            self.save_to_disk().expect("Failed to save wallet to disk");
+    /// Execute a single command and save the wallet.
+    /// Returns `false` if the wallet should exit.
+    async fn execute_command(&mut self, input: &str) -> bool {
+        let result = Command::execute(self, input).await;
+        self.save_to_disk().expect("Failed to save wallet to disk");
 
-            match result {
-                Ok(true) => (),
-                Ok(false) => break,
-                Err(err) => println!("{err:?}: {err}"),
+        match result {
+            Ok(should_continue) => should_continue,
+            Err(err) => {
+                println!("{err:?}: {err}");
+                true
+            }
+        }
+    }
+
+    /// Execute the given commands non-interactively, then exit
+    async fn _exec_commands(mut self, commands: Vec<String>) {
+        self.update_confirmed().await;
+        for command in commands {
+            println!("> {command}");
+            if let Err(err) = self.client.update_work_cache().await {
+                println!("Failed to update work cache: {err}");
+            }
+            if !self.execute_command(&command).await {
+                return;
             }
         }
+
+        // cache any work which finished while executing the commands
+        match self.client.update_work_cache().await {
+            Ok(true) => self.save_to_disk().expect("Failed to save wallet to disk"),
+            Ok(false) => (),
+            Err(err) => println!("Failed to update work cache: {err}"),
+        }
     }
 
-    fn start(self) {
+    /// Start the interactive CLI, or execute `commands` and exit if any are given
+    fn start(mut self, commands: Vec<String>, history: CommandHistory, private: bool) {
+        self.private = private;
         let rt = Runtime::new().expect("could not create Tokio runtime");
-        rt.block_on(self._start_cli());
+        if commands.is_empty() {
+            rt.block_on(self._start_cli(history));
+        } else {
+            rt.block_on(self._exec_commands(commands));
+        }
     }
 }
 impl WalletFrontend for CliClient {
@@ -110,6 +256,10 @@ impl WalletFrontend for CliClient {
         println!("{s}");
     }
 
+    fn private_mode(&self) -> bool {
+        self.private
+    }
+
     fn clear_screen() {
         print!("{}[2J", 27 as char);
     }
@@ -122,6 +272,17 @@ impl WalletFrontend for CliClient {
         }
     }
 
+    fn encryption_key(&self) -> &SecretBytes<32> {
+        &self.key
+    }
+
+    fn save(&mut self) -> Result<(), ClientError> {
+        self.save_to_disk().map_err(|err| match err {
+            CliError::ClientError(err) => err,
+            err => ClientError::FileError(std::io::Error::other(err)),
+        })
+    }
+
     fn client(&self) -> &Client {
         &self.client
     }
@@ -132,9 +293,12 @@ impl WalletFrontend for CliClient {
 }
 
 fn main() {
-    let init = Init::parse().execute();
-    let (client, logger) = match init {
-        Ok((client, logger)) => (client, logger),
+    let init = Init::parse();
+    let history = init.history();
+    let private = init.private();
+    let init = init.execute();
+    let (client, logger, commands) = match init {
+        Ok((client, logger, commands)) => (client, logger, commands),
         Err(err) => {
             println!("{:?}", err);
             return;
@@ -146,5 +310,7 @@ fn main() {
         Err(err) => println!("Failed to start logging: {err}"),
     }
 
-    client.expect("Failed to initialize client").start();
+    client
+        .expect("Failed to initialize client")
+        .start(commands, history, private);
 }
@@ -3,16 +3,24 @@ use super::CliClient;
 use aes_gcm::Error as AESError;
 use client::{
     core::{CoreClientConfig, SecretBytes},
-    storage::{EncryptedWallet, WalletData},
+    storage::{EncryptedWallet, KdfParams, WalletData},
     ClientConfig, ClientError,
 };
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 use zeroize::{Zeroize, ZeroizeOnDrop};
 
 const APP_DATA_FOLDER_NAME: &str = "CamoNano-rs";
 
+/// Wallet names may contain (unicode) letters, numbers, spaces, dashes and underscores.
+/// Leading or trailing spaces are not allowed, nor is the reserved name "config".
 fn is_valid_name(name: &str) -> bool {
-    name.chars().all(|c| c.is_alphanumeric()) && name != "config"
+    !name.is_empty()
+        && name.trim() == name
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == ' ' || c == '-' || c == '_')
+        && name != "config"
 }
 
 #[derive(Debug, Clone, Default, Zeroize, ZeroizeOnDrop, Serialize, Deserialize)]
@@ -47,7 +55,8 @@ impl UserWallets {
         }
 
         let data: WalletData = cli_client.client.as_wallet_data();
-        let encrypted = data.encrypt(name, key)?;
+        let params = KdfParams::from_config(&cli_client.client.core.config);
+        let encrypted = data.encrypt(name, key, params)?;
         self.wallets.push(encrypted);
         Ok(())
     }
@@ -81,7 +90,7 @@ impl UserWallets {
             .decrypt(&key)?;
 
         let client = data.to_client(config);
-        Ok(CliClient { name: name.into(), key, client })
+        Ok(CliClient { name: name.into(), key, client, private: false })
     }
 
     fn delete_wallet(&mut self, name: &str, key: &SecretBytes<32>) -> Result<(), CliError> {
@@ -110,6 +119,12 @@ pub fn config_location() -> Result<String, CliError> {
         .into())
 }
 
+/// Return the default path of the command history file
+pub fn history_location() -> Result<PathBuf, CliError> {
+    let path = confy::get_configuration_file_path(APP_DATA_FOLDER_NAME, "config")?;
+    Ok(path.with_file_name("history.txt"))
+}
+
 /// Save the config file to disk
 pub fn save_config(config: ClientConfig) -> Result<(), CliError> {
     Ok(confy::store(APP_DATA_FOLDER_NAME, "config", config)?)
@@ -178,3 +193,40 @@ pub fn init_files() -> Result<(), CliError> {
     UserWallets::load_from_disk()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::is_valid_name;
+
+    #[test]
+    fn valid_names() {
+        for name in [
+            "wallet",
+            "Wallet2",
+            "my wallet",
+            "my-wallet_2",
+            "portefeuille",
+            "钱包",
+        ] {
+            assert!(is_valid_name(name), "{name}")
+        }
+    }
+
+    #[test]
+    fn invalid_names() {
+        for name in [
+            "",
+            " ",
+            " wallet",
+            "wallet ",
+            "config",
+            "../wallet",
+            "a/b",
+            "a\\b",
+            "a\tb",
+            "💰",
+        ] {
+            assert!(!is_valid_name(name), "{name}")
+        }
+    }
+}